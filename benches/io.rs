@@ -0,0 +1,92 @@
+//! Throughput of the FASTA and FASTQ readers on a large input.
+//!
+//! The input is the FASTQ test dataset, repeated until it is about 100MB in size, so it stays in
+//! memory and only the parsing is measured. Run with `cargo bench`.
+
+use std::fs;
+use std::io;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use umgap::io::fasta;
+use umgap::io::fastq;
+
+const TARGET_SIZE: usize = 100_000_000; // 100MB
+
+fn large_fastq() -> Vec<u8> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/A1.fq");
+    let sample = fs::read(path).expect("missing test data");
+    sample.repeat(TARGET_SIZE / sample.len() + 1)
+}
+
+fn as_fasta(fastq: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(fastq.len());
+    {
+        let mut writer = fasta::Writer::new(&mut output, "", false);
+        for record in fastq::Reader::new(fastq).records() {
+            let record = record.unwrap();
+            writer
+                .write_record(fasta::Record {
+                    header: record.header,
+                    sequence: vec![record.sequence],
+                })
+                .unwrap();
+        }
+    }
+    output
+}
+
+fn fastq_throughput(c: &mut Criterion) {
+    let input = large_fastq();
+    let mut group = c.benchmark_group("fastq");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut reader = fastq::Reader::new(io::Cursor::new(&input));
+            let mut residues = 0;
+            while let Some(record) = reader.read_record_ref().unwrap() {
+                residues += record.sequence.len();
+            }
+            residues
+        })
+    });
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            fastq::Reader::new(io::Cursor::new(&input))
+                .records()
+                .map(|record| record.unwrap().sequence.len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+fn fasta_throughput(c: &mut Criterion) {
+    let input = as_fasta(&large_fastq());
+    let mut group = c.benchmark_group("fasta");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut reader = fasta::Reader::new(io::Cursor::new(&input), true);
+            let mut residues = 0;
+            while let Some(record) = reader.read_record_ref().unwrap() {
+                residues += record.sequence[0].len();
+            }
+            residues
+        })
+    });
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            fasta::Reader::new(io::Cursor::new(&input), true)
+                .records()
+                .map(|record| record.unwrap().sequence[0].len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fastq_throughput, fasta_throughput);
+criterion_main!(benches);
@@ -32,11 +32,14 @@ Some default configurations for the UMGAP.
 
 Usage: $0 -1 <fasta> [-t <type>] [-z] -o <output>
        $0 -1 <fastq1> -2 <fastq2> [-t <type>] [-z] -o <output>
+       $0 -1 <sam> [-t <type>] [-z] -o <output>
 
 Where:
-  <fasta>   A (optionally GZIP-compressed) FASTA file of reads.
-  <fastq1>  A (optionally GZIP-compressed) FASTQ file of reads (first end).
-  <fastq2>  A (optionally GZIP-compressed) FASTQ file of reads (second end).
+  <fasta>   A (optionally compressed) FASTA file of reads.
+  <fastq1>  A (optionally compressed) FASTQ file of reads (first end).
+  <fastq2>  A (optionally compressed) FASTQ file of reads (second end).
+  <sam>     A SAM or BAM file of unaligned reads (single or both ends),
+            recognized by its '.sam', '.sam.gz' or '.bam' extension.
   <type>    The type of analysis, any of:
               - max-sensitivity
               - high-sensitivity
@@ -150,41 +153,49 @@ while getopts c:1:2:t:zo: f; do
 		esac
 		log "- Using '$type'."
 
-		if [ -n "$infile1" ]; then
-			log "- found input file 1: '$infile1'"
-			filetype="$(file --mime-type "$infile1")" || \
-				crash "Could not determine filetype of '$infile1'."
-			if [ "$filetype" != "${filetype%gzip}" ]; then
-				log "- input file 1 is compressed"
-				mkfifo "$tmp/gunzip1-$count"
-				{ zcat "$infile1" > "$tmp/gunzip1-$count" || kill "$self"; } > /dev/null &
-				infile1="$tmp/gunzip1-$count"
-			fi
-		fi
-
-		if [ -n "$infile2" ]; then
-			log "- found input file 2: '$infile2'"
-			filetype="$(file --mime-type "$infile2")" || \
-				crash "Could not determine filetype of '$infile2'."
-			if [ "$filetype" != "${filetype%gzip}" ]; then
-				log "- input file 2 is compressed"
-				mkfifo  "$tmp/gunzip2-$count"
-				{ zcat "$infile2" > "$tmp/gunzip2-$count" || kill "$self"; } > /dev/null &
-				infile2="$tmp/gunzip2-$count"
-			fi
-		fi
+		case "$infile1" in
+		*.sam|*.sam.gz|*.bam) sam="true" ;;
+		*) sam="false" ;;
+		esac
 
 		if [ -n "$infile2" ]; then
-			if [ -n "$infile1" ]; then
+			if [ "$sam" = "true" ]; then
+				crash "A SAM or BAM file holds both ends, give it without a second input file."
+			elif [ -n "$infile1" ]; then
 				log "- found two input files, assuming paired-end FASTQ"
+				# fastq2fasta decompresses its input files itself
 				mkfifo "$tmp/infile-$count"
 				{ umgap fastq2fasta "$infile1" "$infile2" > "$tmp/infile-$count" || kill "$self"; } > /dev/null &
 				infile="$tmp/infile-$count"
 			else
 				crash "Encountered a second input file without a first."
 			fi
+		elif [ "$sam" = "true" ]; then
+			log "- found a SAM or BAM input file: '$infile1'"
+			# keep the /1 and /2 suffixes of the mates, so they are joined later on
+			mkfifo "$tmp/infile-$count"
+			{ umgap fastq2fasta --sam -k "$infile1" > "$tmp/infile-$count" || kill "$self"; } > /dev/null &
+			infile="$tmp/infile-$count"
 		elif [ -n "$infile1" ]; then
-			infile="$infile1"
+			log "- found input file 1: '$infile1'"
+			# the umgap commands decompress their input themselves, but FGSpp doesn't
+			filetype="$(file --brief --mime-type "$infile1")" || \
+				crash "Could not determine filetype of '$infile1'."
+			case "$filetype" in
+			*gzip) decompress="gzip" ;;
+			*bzip2) decompress="bzip2" ;;
+			*xz) decompress="xz" ;;
+			*zstd) decompress="zstd" ;;
+			*) decompress= ;;
+			esac
+			if [ -n "$decompress" ]; then
+				log "- input file 1 is compressed with $decompress"
+				mkfifo "$tmp/decompress-$count"
+				{ "$decompress" -dc "$infile1" > "$tmp/decompress-$count" || kill "$self"; } > /dev/null &
+				infile="$tmp/decompress-$count"
+			else
+				infile="$infile1"
+			fi
 		else
 			crash "Encountered an output file without input files."
 		fi
@@ -30,6 +30,7 @@ Where:
 Options:
   -t        Output a CSV frequency table on species rank.
   -w        Output an HTML webpage of an interactive visualization.
+  -o        Build the HTML webpage using the Unipept API instead of locally.
   -u        Print a shareable URL to a online interactive visualisation.
   -c dir    The configuration directory. Defaults to '$config_default'.
   -r rank   Set the rank for the CSV frequency table (default: species).
@@ -78,11 +79,13 @@ getconfigdir() {
 debug "parsing the arguments"
 
 rank="species"
-while getopts c:r:wtu f; do
+online="false"
+while getopts c:r:wotu f; do
 	case "$f" in
 	c) configdir="$OPTARG" ;;
 	r) rank="$OPTARG" ;;
 	w) type="html" ;;
+	o) online="true" ;;
 	t) type="csv" ;;
 	u) type="url" ;;
 	\?) crash "$USAGE" '' ;;
@@ -103,8 +106,8 @@ if ! umgap -V > /dev/null; then
 	crash 'Cannot find the umgap executable. Please ensure it is installed and located in your $PATH.'
 fi
 
-debug "checking if we have a taxons file for the frequency table"
-if [ "$type" = "csv" ]; then
+debug "checking if we have a taxons file for the frequency table or webpage"
+if [ "$type" = "csv" -o "$type" = "html" -a "$online" = "false" ]; then
 	versions="$(find -H "$(getconfigdir)" -mindepth 1 -maxdepth 1 \
 	                 -printf '%P\n' | sort -n)"
 	for candidate in $versions; do
@@ -133,7 +136,12 @@ url)
 	done
 	;;
 html)
-	umgap taxa2tree < "$1" ;;
+	if [ "$online" = "true" ]; then
+		umgap taxa2tree --online < "$1"
+	else
+		umgap taxa2tree "$(getconfigdir)/$version/taxons.tsv" < "$1"
+	fi
+	;;
 csv)
 	inputfiles=""
 	for file in "$@"; do
@@ -3,6 +3,7 @@
 use std::iter::{Iterator, Peekable};
 
 use crate::rank::RANK_COUNT;
+use crate::taxon;
 use crate::taxon::{Lineage, Taxon, TaxonId, TaxonList};
 
 /// An Iterator which yields the aggegrated pairs.
@@ -10,8 +11,10 @@ pub struct LineageAggregator<T: Iterator<Item = (String, TaxonId)>>(Peekable<Lin
 
 impl<T: Iterator<Item = (String, TaxonId)>> LineageAggregator<T> {
     /// Aggregates the given records using a taxonomy derived from taxa.
-    pub fn new(records: T, taxa: Vec<Taxon>) -> Self {
-        LineageAggregator(LineageIterator::new(records, taxa).peekable())
+    pub fn new(records: T, taxa: Vec<Taxon>) -> taxon::Result<Self> {
+        Ok(LineageAggregator(
+            LineageIterator::new(records, taxa)?.peekable(),
+        ))
     }
 }
 
@@ -21,12 +24,12 @@ struct LineageIterator<T: Iterator<Item = (String, TaxonId)>> {
 }
 
 impl<T: Iterator<Item = (String, TaxonId)>> LineageIterator<T> {
-    pub fn new(records: T, taxa: Vec<Taxon>) -> Self {
-        let taxon_list = TaxonList::new_with_unknown(taxa, true);
-        LineageIterator {
+    pub fn new(records: T, taxa: Vec<Taxon>) -> taxon::Result<Self> {
+        let taxon_list = TaxonList::new_with_unknown(taxa, true)?;
+        Ok(LineageIterator {
             records,
             taxon_list,
-        }
+        })
     }
 }
 
@@ -4,6 +4,7 @@ use std::cmp::min;
 use std::iter::{Iterator, Peekable};
 
 use crate::rank::Rank;
+use crate::taxon;
 use crate::taxon::{Taxon, TaxonId, TaxonList, TaxonTree};
 
 /// An Iterator which yields the aggregated pairs.
@@ -16,18 +17,16 @@ pub struct RankAggregator<T: Iterator<Item = (String, TaxonId)>> {
 impl<T: Iterator<Item = (String, TaxonId)>> RankAggregator<T> {
     /// Uses a taxonomy constructed from taxa to aggregate. Assumes the
     /// taxon with TaxonId 0 is absent or represents an "unknown" taxon.
-    pub fn new(records: T, taxa: Vec<Taxon>) -> Self {
-        let taxon_tree = TaxonTree::new(&taxa);
-        let taxon_list = TaxonList::new_with_unknown(taxa, true);
-        RankAggregator {
+    pub fn new(records: T, taxa: Vec<Taxon>) -> taxon::Result<Self> {
+        let taxon_tree = TaxonTree::new(&taxa)?;
+        let taxon_list = TaxonList::new_with_unknown(taxa, true)?;
+        Ok(RankAggregator {
             records: records.peekable(),
             ancestors: taxon_tree.snapping(&taxon_list, true),
-            ranks: taxon_list
-                .0
-                .iter()
-                .map(|mt| mt.as_ref().map(|t| t.rank))
+            ranks: (0..taxon_list.len())
+                .map(|id| taxon_list.get(id).map(|t| t.rank))
                 .collect(),
-        }
+        })
     }
 
     fn raise_to_rank(&self, taxon: TaxonId, target: Rank) -> Option<TaxonId> {
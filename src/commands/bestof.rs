@@ -3,6 +3,7 @@
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 use crate::taxon::TaxonId;
 
@@ -54,7 +55,7 @@ pub fn bestof(args: BestOf) -> errors::Result<()> {
 
     // Combine frames and process them
     let mut chunk = Vec::with_capacity(args.frames);
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         let record = record?;
         if chunk.len() < args.frames - 1 {
             chunk.push(record);
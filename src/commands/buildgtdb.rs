@@ -0,0 +1,124 @@
+//! The `umgap buildgtdb` command.
+
+use std::fs;
+use std::io;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::io::gtdb;
+use crate::io::gtdb::Gtdb;
+use crate::taxon;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Builds a taxonomy TSV-file from the GTDB taxonomy
+///
+/// The `umgap buildgtdb` command converts the [GTDB](https://gtdb.ecogenomic.org/) taxonomy into
+/// the taxonomy TSV-file used by the other commands, so the GTDB taxonomy can be used instead of
+/// the NCBI taxonomy.
+///
+/// The GTDB taxonomy files (such as `bac120_taxonomy.tsv` and `ar53_taxonomy.tsv`) or metadata
+/// files (such as `bac120_metadata.tsv`) are passed as arguments to this command, and may be
+/// compressed. The taxonomy of the lineages of all genomes is written to *standard output*. As GTDB
+/// taxa have no numeric IDs, each taxon gets an ID derived from its name. The domains of GTDB are
+/// domains in the resulting taxonomy as well.
+///
+/// ```sh
+/// $ umgap buildgtdb bac120_taxonomy.tsv.gz ar53_taxonomy.tsv.gz > gtdb-taxons.tsv
+/// ```
+///
+/// When two names lead to the same ID, one of them gets another free ID, which may differ between
+/// GTDB releases. Pass the taxonomy built from the previous release with the `-p` option to keep
+/// its IDs: its taxa keep their ID, and the IDs of removed taxa aren't reused.
+///
+/// ```sh
+/// $ umgap buildgtdb -p gtdb-r214-taxons.tsv bac120_taxonomy_r220.tsv.gz > gtdb-r220-taxons.tsv
+/// ```
+///
+/// With the `-m` option, a mapping from NCBI taxon IDs to the IDs of GTDB taxa is written to the
+/// given file. This requires the metadata files, which contain the NCBI taxon ID of each genome.
+/// Each of these NCBI taxa is mapped onto the lowest common ancestor of the GTDB taxa of its
+/// genomes. When the NCBI taxonomy is given with the `-n` option, their ancestors are mapped as
+/// well, so the results of an index built on the NCBI taxonomy can be translated into GTDB taxa
+/// with `umgap maptaxa`.
+///
+/// ```sh
+/// $ umgap buildgtdb -m ncbi2gtdb.tsv -n taxons.tsv bac120_metadata.tsv.gz > gtdb-taxons.tsv
+/// ```
+pub struct BuildGtdb {
+    /// Write the mapping from NCBI taxon IDs to GTDB taxon IDs to this file
+    #[structopt(short = "m", long = "mapping", parse(from_os_str))]
+    pub mapping_file: Option<PathBuf>,
+
+    /// The taxonomy TSV-file built from a previous GTDB release, whose IDs are kept
+    #[structopt(short = "p", long = "previous", parse(from_os_str))]
+    pub previous_file: Option<PathBuf>,
+
+    /// An NCBI taxonomy TSV-file, to map the ancestors of the NCBI taxa as well
+    #[structopt(short = "n", long = "ncbi", parse(from_os_str))]
+    pub ncbi_file: Option<PathBuf>,
+
+    /// The GTDB taxonomy or metadata files
+    #[structopt(parse(from_os_str), required = true)]
+    pub gtdb_files: Vec<PathBuf>,
+}
+
+/// Implements the buildgtdb command.
+pub fn buildgtdb(args: BuildGtdb) -> errors::Result<()> {
+    if args.ncbi_file.is_some() && args.mapping_file.is_none() {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "The NCBI taxonomy is only used to build a mapping.".into(),
+        )
+        .into());
+    }
+
+    let mut genomes = Vec::new();
+    for file in args.gtdb_files.iter() {
+        genomes.extend(gtdb::read_genomes(BufReader::new(compression::open(
+            file,
+        )?))?);
+    }
+    let taxonomy = match &args.previous_file {
+        Some(previous_file) => {
+            Gtdb::with_previous(&genomes, &taxon::read_taxa_file(previous_file)?)?
+        }
+        None => Gtdb::new(&genomes)?,
+    };
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for taxon in taxonomy.taxa.iter() {
+        writeln!(handle, "{}", taxon)?;
+    }
+    handle.flush()?;
+
+    if let Some(mapping_file) = args.mapping_file {
+        let mapping = match args.ncbi_file {
+            Some(ncbi_file) => {
+                let remapping = taxon::Remapping::for_taxonomy(&ncbi_file)?;
+                let ncbi = taxon::TaxonList::new(taxon::read_taxa_file(&ncbi_file)?)?;
+                for genome in genomes.iter_mut() {
+                    // Deleted taxa are remapped to 0, and left out of the mapping.
+                    genome.ncbi_taxon = genome
+                        .ncbi_taxon
+                        .map(|id| remapping.remap(id))
+                        .filter(|&id| id != 0);
+                }
+                remapping.report();
+                taxonomy.ncbi_mapping(&genomes, Some(&ncbi))
+            }
+            None => taxonomy.ncbi_mapping(&genomes, None),
+        };
+        let mut mapping = mapping.into_iter().collect::<Vec<_>>();
+        mapping.sort();
+        let mut file = io::BufWriter::new(fs::File::create(mapping_file)?);
+        for (ncbi, gtdb) in mapping {
+            writeln!(file, "{}\t{}", ncbi, gtdb)?;
+        }
+        file.flush()?;
+    }
+    Ok(())
+}
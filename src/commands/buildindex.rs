@@ -3,6 +3,7 @@
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 
 #[derive(Debug, StructOpt)]
 #[structopt(verbatim_doc_comment)]
@@ -33,7 +34,7 @@ pub fn buildindex(_args: BuildIndex) -> errors::Result<()> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
         .delimiter(b'\t')
-        .from_reader(io::stdin());
+        .from_reader(compression::stdin()?);
 
     let mut index = fst::MapBuilder::new(io::stdout())?;
 
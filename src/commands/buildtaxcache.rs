@@ -0,0 +1,63 @@
+//! The `umgap buildtaxcache` command.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::taxcache;
+use crate::names::NameIndex;
+use crate::taxon;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Converts a taxonomy TSV-file into a binary taxonomy cache
+///
+/// The `umgap buildtaxcache` command converts a taxonomy into a binary format, which the other
+/// commands map into memory instead of parsing it. This saves most of the time spent on loading
+/// the taxonomy, which dominates the running time on small inputs.
+///
+/// The taxonomy to be converted and the cache to be written are passed as arguments to this
+/// command. The cache can be used wherever a taxonomy TSV-file is expected, as it is recognized by
+/// its first bytes. A remapping of merged and deleted taxa (see `umgap buildtaxonomy -m`) and the
+/// synonyms of the taxa (see `umgap buildtaxonomy -s`) are copied along, so they are used with the
+/// cache as well.
+///
+/// ```sh
+/// $ umgap buildtaxcache taxons.tsv taxons.bin
+/// $ umgap taxa2agg taxons.bin < input.fa
+/// ```
+///
+/// The taxonomy is checked like `umgap checktaxonomy` does, as the commands don't check the taxa
+/// they read from a cache. The command fails on the first problem found.
+///
+/// As the cache is mapped into memory, it should not be overwritten while it's in use.
+pub struct BuildTaxCache {
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+
+    /// The taxonomy cache to write
+    #[structopt(parse(from_os_str))]
+    pub cache_file: PathBuf,
+}
+
+/// Implements the buildtaxcache command.
+pub fn buildtaxcache(args: BuildTaxCache) -> errors::Result<()> {
+    let taxons = taxon::read_taxa_file(&args.taxon_file)?;
+    if let Some(problem) = taxon::validate(&taxons).into_iter().next() {
+        return Err(taxon::Error::from(problem).into());
+    }
+    let file = io::BufWriter::new(fs::File::create(&args.cache_file)?);
+    taxcache::write(&taxons, file)?;
+
+    let remap_file = taxon::Remapping::path_for(&args.taxon_file);
+    if remap_file.exists() {
+        fs::copy(remap_file, taxon::Remapping::path_for(&args.cache_file))?;
+    }
+    let synonyms_file = NameIndex::synonyms_path(&args.taxon_file);
+    if synonyms_file.exists() {
+        fs::copy(synonyms_file, NameIndex::synonyms_path(&args.cache_file))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! The `umgap buildtaxonomy` command.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::taxdump::Taxdump;
+use crate::taxon;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Builds a taxonomy TSV-file from the NCBI taxonomy dump
+///
+/// The `umgap buildtaxonomy` command converts the NCBI taxonomy, as distributed in the
+/// `taxdump.tar.gz` archive, into the taxonomy TSV-file used by the other commands. This way, any
+/// release of the NCBI taxonomy can be used, instead of the one downloaded from Unipept.
+///
+/// The directory containing the extracted `nodes.dmp` and `names.dmp` files is passed as an
+/// argument to this command. The files may be compressed. The taxonomy is written to *standard
+/// output*, a taxon on each line with its ID, scientific name, rank, parent ID, and whether it's
+/// valid. A taxon is invalid if it (or one of its ancestors) is not an actual group of organisms,
+/// such as "environmental samples", "uncultured bacterium" or "Escherichia sp.", just like in the
+/// taxonomy used by Unipept.
+///
+/// ```sh
+/// $ wget https://ftp.ncbi.nlm.nih.gov/pub/taxonomy/taxdump.tar.gz
+/// $ mkdir taxdump
+/// $ tar -xzf taxdump.tar.gz -C taxdump
+/// $ umgap buildtaxonomy taxdump > taxons.tsv
+/// ```
+///
+/// With the `-m` option, the IDs of the taxa that were merged into another taxon or deleted are
+/// written to the given file, each with its current ID (or 0 for a deleted taxon). When this file
+/// is named after the taxonomy with `.remap` appended, the commands reading taxon IDs will
+/// automatically replace these outdated IDs, with a warning. Existing results can be upgraded with
+/// `umgap remaptaxa`.
+///
+/// ```sh
+/// $ umgap buildtaxonomy -m taxons.tsv.remap taxdump > taxons.tsv
+/// ```
+///
+/// With the `-s` option, the other names of the taxa, such as their synonyms and common names, are
+/// written to the given file. When this file is named after the taxonomy with `.synonyms`
+/// appended, taxa can also be looked up by these names, for instance with `umgap taxonsearch`.
+///
+/// ```sh
+/// $ umgap buildtaxonomy -s taxons.tsv.synonyms taxdump > taxons.tsv
+/// ```
+pub struct BuildTaxonomy {
+    /// Write the merged and deleted taxon IDs to this file
+    #[structopt(short = "m", long = "remap", parse(from_os_str))]
+    pub remap_file: Option<PathBuf>,
+
+    /// Write the synonyms of the taxa to this file
+    #[structopt(short = "s", long = "synonyms", parse(from_os_str))]
+    pub synonyms_file: Option<PathBuf>,
+
+    /// The directory containing the NCBI taxonomy dump
+    #[structopt(parse(from_os_str))]
+    pub taxdump: PathBuf,
+}
+
+/// Implements the buildtaxonomy command.
+pub fn buildtaxonomy(args: BuildTaxonomy) -> errors::Result<()> {
+    let taxdump = Taxdump::from_dir(&args.taxdump)?;
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for taxon in taxdump.taxa.iter() {
+        writeln!(handle, "{}", taxon)?;
+    }
+    handle.flush()?;
+
+    if let Some(synonyms_file) = args.synonyms_file {
+        let mut file = io::BufWriter::new(fs::File::create(synonyms_file)?);
+        for (id, name) in taxdump.synonyms.iter() {
+            writeln!(file, "{}\t{}", id, name)?;
+        }
+        file.flush()?;
+    }
+
+    if let Some(remap_file) = args.remap_file {
+        let remapping = taxon::Remapping::new(
+            taxdump.merged.into_iter().collect(),
+            taxdump.deleted.into_iter().collect(),
+        );
+        let mut file = io::BufWriter::new(fs::File::create(remap_file)?);
+        write!(file, "{}", remapping)?;
+        file.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! The `umgap checktaxonomy` command.
+
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::io::taxcache;
+use crate::taxon;
+use crate::taxon::Taxon;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Reports the problems in a taxonomy TSV-file
+///
+/// The `umgap checktaxonomy` command checks whether a taxonomy can be used by the other commands,
+/// which only check for a single root when loading it, and `umgap buildtaxcache`, which fails on
+/// the first problem. Instead, all problems are reported at once, each on a line on *standard
+/// output*:
+///
+/// * lines that can't be parsed as a taxon, such as lines with an unknown rank,
+/// * taxon IDs that occur more than once,
+/// * taxa with a parent that doesn't exist,
+/// * taxa that are their own ancestors,
+/// * a missing root (a taxon that is its own parent), or more than one root,
+/// * taxon IDs that are too large for the number of taxa.
+///
+/// The command fails if any problem was found.
+///
+/// ```sh
+/// $ umgap checktaxonomy taxons.tsv
+/// Line 1377: Matching variant not found
+/// Duplicate Taxon ID: 2759
+/// Taxon 3041 has an unknown parent 3040
+/// Taxa forming a cycle: [33090, 2763]
+/// Error: Found 4 problems in the taxonomy.
+/// ```
+pub struct CheckTaxonomy {
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+}
+
+/// Implements the checktaxonomy command.
+pub fn checktaxonomy(args: CheckTaxonomy) -> errors::Result<()> {
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    let mut problems = 0;
+
+    // Parse the lines one by one, to report all unparseable lines
+    let taxa = if taxcache::is_cache(&args.taxon_file)? {
+        taxon::read_taxa_file(&args.taxon_file)?
+    } else {
+        let mut taxa = Vec::new();
+        let reader = io::BufReader::new(compression::open(&args.taxon_file)?);
+        for (i, line) in reader.lines().enumerate() {
+            match line?.parse::<Taxon>() {
+                Ok(taxon) => taxa.push(taxon),
+                Err(error) => {
+                    writeln!(handle, "Line {}: {}", i + 1, error)?;
+                    problems += 1;
+                }
+            }
+        }
+        taxa
+    };
+
+    for problem in taxon::validate(&taxa) {
+        writeln!(handle, "{}", problem)?;
+        problems += 1;
+    }
+    if let Err(error) = taxon::TaxonList::new(taxa) {
+        writeln!(handle, "{}", error)?;
+        problems += 1;
+    }
+    handle.flush()?;
+
+    if problems > 0 {
+        return Err(format!("Found {} problems in the taxonomy.", problems).into());
+    }
+    Ok(())
+}
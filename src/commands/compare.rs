@@ -0,0 +1,372 @@
+//! The `umgap compare` command.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::diversity;
+use crate::errors;
+use crate::io::compression;
+use crate::rank;
+use crate::rank::Rank;
+use crate::taxon;
+use crate::taxon::TaxonId;
+
+#[rustfmt::skip]
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
+/// Compares the taxa in multiple samples
+///
+/// The `umgap compare` command compares the taxon counts of multiple samples, each given as a file
+/// argument with a single taxon ID on each line (FASTA headers and taxon ID 0 are skipped). It
+/// writes a TSV matrix to *standard output*, with the measure selected with `-m`:
+///
+/// * `table`: the normalised abundance of each taxon in each sample,
+/// * `alpha`: the richness, Shannon index and Gini-Simpson index of each sample,
+/// * `bray-curtis`, `jaccard`, `euclidean`: the pairwise distances between the samples,
+/// * `unifrac`, `weighted-unifrac`: the pairwise (normalised weighted) UniFrac distances between
+///   the samples, using the taxonomy as a tree with branches of length 1.
+///
+/// The counts are normalised with `-n` before they're compared, to relative abundances (the
+/// default), with the centred log-ratio transformation (`clr`, only usable with `table` and
+/// `euclidean`), or by rarefaction (`rarefy`) to the depth given with `-d`, by default the
+/// total count of the smallest sample. Samples with a smaller total count are left out. The alpha
+/// diversities are calculated on the rarefied counts when rarefying, and on the raw counts
+/// otherwise.
+///
+/// The taxonomy to be used is passed as first argument to this command. This is a preprocessed
+/// version of the NCBI taxonomy. With the `-r` option, taxa are first snapped to their ancestor on
+/// the given rank.
+///
+/// ```sh
+/// $ umgap compare -m alpha taxons.tsv a.txt b.txt c.txt
+/// sample	richness	shannon	simpson
+/// a.txt	12	1.8364	0.7812
+/// b.txt	9	1.2011	0.5977
+/// c.txt	15	2.0135	0.8230
+/// $ umgap compare -r species -m bray-curtis taxons.tsv a.txt b.txt c.txt
+/// sample	a.txt	b.txt	c.txt
+/// a.txt	0.0000	0.4615	0.2500
+/// b.txt	0.4615	0.0000	0.5833
+/// c.txt	0.2500	0.5833	0.0000
+/// $ umgap compare -n rarefy -d 1000 -m unifrac taxons.tsv a.txt b.txt c.txt > unifrac.tsv
+/// ```
+pub struct Compare {
+    /// The measure to calculate
+    #[structopt(
+        short = "m",
+        long = "measure",
+        default_value = "bray-curtis",
+        possible_values = &Measure::variants()
+    )]
+    pub measure: Measure,
+
+    /// The normalisation of the counts
+    #[structopt(
+        short = "n",
+        long = "normalisation",
+        default_value = "relative",
+        possible_values = &Normalisation::variants()
+    )]
+    pub normalisation: Normalisation,
+
+    /// The depth to rarefy to
+    #[structopt(short = "d", long = "depth")]
+    pub depth: Option<usize>,
+
+    /// The seed for rarefaction
+    #[structopt(short = "s", long = "seed", default_value = "1")]
+    pub seed: u64,
+
+    /// The pseudocount added to each count for the CLR transformation
+    #[structopt(short = "p", long = "pseudocount", default_value = "0.5")]
+    pub pseudocount: f64,
+
+    /// The rank to snap the taxa to
+    #[structopt(
+        short = "r",
+        long = "rank",
+        possible_values = &Rank::variants()
+    )]
+    pub rank: Option<Rank>,
+
+    /// The number of decimals in the output
+    #[structopt(long = "decimals", default_value = "4")]
+    pub decimals: usize,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+
+    /// The samples to compare
+    #[structopt(parse(from_os_str), required = true)]
+    pub input_files: Vec<PathBuf>,
+}
+
+/// Implements the compare command.
+pub fn compare(args: Compare) -> errors::Result<()> {
+    if args.rank == Some(rank::Rank::NoRank) {
+        return Err(errors::ErrorKind::InvalidInvocation("Snap to an actual rank.".into()).into());
+    }
+    if args.normalisation == Normalisation::Clr
+        && args.measure != Measure::Table
+        && args.measure != Measure::Euclidean
+    {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "CLR-transformed counts can only be compared with the euclidean distance.".into(),
+        )
+        .into());
+    }
+
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
+    let snapping = args.rank.map(|rank| {
+        tree.filter_ancestors(|tid| by_id.get(tid).map(|t| t.rank == rank).unwrap_or(false))
+    });
+
+    // Count the taxa in each sample
+    let mut samples = Vec::with_capacity(args.input_files.len());
+    for file in args.input_files.iter() {
+        let mut counts = HashMap::new();
+        for line in BufReader::new(compression::open(file)?).lines() {
+            let line = line?;
+            if line.starts_with('>') || line.trim().is_empty() {
+                continue;
+            }
+            // The taxon ID may be followed by the functional annotations, separated by a tab.
+            let taxon = line
+                .split('\t')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .parse::<TaxonId>()?;
+            let taxon = remapping.remap(taxon);
+            if taxon == 0 {
+                continue;
+            }
+            by_id.get_or_unknown(taxon)?;
+            let taxon = match &snapping {
+                Some(snapping) => snapping[taxon].unwrap_or(tree.root),
+                None => taxon,
+            };
+            *counts.entry(taxon).or_insert(0) += 1;
+        }
+        samples.push((file.to_string_lossy().into_owned(), counts));
+    }
+
+    // Align the counts of all samples on the same list of taxa
+    let taxa = samples
+        .iter()
+        .flat_map(|(_, counts)| counts.keys().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut samples = samples
+        .into_iter()
+        .map(|(name, counts)| {
+            let counts = taxa
+                .iter()
+                .map(|taxon| counts.get(taxon).copied().unwrap_or(0))
+                .collect::<Vec<_>>();
+            (name, counts)
+        })
+        .collect::<Vec<_>>();
+
+    if args.normalisation == Normalisation::Rarefy {
+        let depth = args
+            .depth
+            .or_else(|| samples.iter().map(|(_, c)| c.iter().sum::<usize>()).min())
+            .unwrap_or(0);
+        let mut random = diversity::Random::new(args.seed);
+        samples.retain(|(name, counts)| {
+            let keep = counts.iter().sum::<usize>() >= depth;
+            if !keep {
+                eprintln!(
+                    "Leaving out {}, which has a total count below {}.",
+                    name, depth
+                );
+            }
+            keep
+        });
+        for (_, counts) in samples.iter_mut() {
+            *counts = diversity::rarefy(counts, depth, &mut random);
+        }
+    }
+
+    let names = samples
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let counts = samples
+        .into_iter()
+        .map(|(_, counts)| counts.into_iter().map(|c| c as f64).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let abundances = counts
+        .iter()
+        .map(|counts| match args.normalisation {
+            Normalisation::Relative => diversity::relative(counts),
+            Normalisation::Clr => diversity::clr(counts, args.pseudocount),
+            Normalisation::Rarefy => counts.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let precision = args.decimals;
+
+    match args.measure {
+        Measure::Table => {
+            write!(stdout, "taxon_id\ttaxon_name")?;
+            for name in names.iter() {
+                write!(stdout, "\t{}", name)?;
+            }
+            writeln!(stdout)?;
+            for (i, &taxon) in taxa.iter().enumerate() {
+                if counts.iter().all(|counts| counts[i] <= 0.0) {
+                    continue;
+                }
+                write!(stdout, "{}\t{}", taxon, by_id.get_or_unknown(taxon)?.name)?;
+                for abundances in abundances.iter() {
+                    write!(stdout, "\t{:.*}", precision, abundances[i])?;
+                }
+                writeln!(stdout)?;
+            }
+        }
+        Measure::Alpha => {
+            writeln!(stdout, "sample\trichness\tshannon\tsimpson")?;
+            for (name, counts) in names.iter().zip(counts.iter()) {
+                writeln!(
+                    stdout,
+                    "{}\t{}\t{:.*}\t{:.*}",
+                    name,
+                    diversity::richness(counts),
+                    precision,
+                    diversity::shannon(counts),
+                    precision,
+                    diversity::simpson(counts)
+                )?;
+            }
+        }
+        measure => {
+            let unifrac = diversity::UniFrac::new(&tree, &by_id);
+            write!(stdout, "sample")?;
+            for name in names.iter() {
+                write!(stdout, "\t{}", name)?;
+            }
+            writeln!(stdout)?;
+            for (name, a) in names.iter().zip(abundances.iter()) {
+                write!(stdout, "{}", name)?;
+                for b in abundances.iter() {
+                    let distance = match measure {
+                        Measure::BrayCurtis => diversity::bray_curtis(a, b),
+                        Measure::Jaccard => diversity::jaccard(a, b),
+                        Measure::Euclidean => diversity::euclidean(a, b),
+                        Measure::UniFrac => unifrac.unweighted(&taxa, a, b)?,
+                        Measure::WeightedUniFrac => unifrac.weighted(&taxa, a, b)?,
+                        Measure::Table | Measure::Alpha => unreachable!(),
+                    };
+                    write!(stdout, "\t{:.*}", precision, distance)?;
+                }
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    remapping.report();
+    Ok(())
+}
+
+/// A measure to calculate
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Table,
+    Alpha,
+    BrayCurtis,
+    Jaccard,
+    Euclidean,
+    UniFrac,
+    WeightedUniFrac,
+}
+
+static MEASURES: &[&str] = &[
+    "table",
+    "alpha",
+    "bray-curtis",
+    "jaccard",
+    "euclidean",
+    "unifrac",
+    "weighted-unifrac",
+];
+impl Measure {
+    fn variants() -> &'static [&'static str] {
+        MEASURES
+    }
+}
+
+impl FromStr for Measure {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Measure::Table),
+            "alpha" => Ok(Measure::Alpha),
+            "bray-curtis" => Ok(Measure::BrayCurtis),
+            "jaccard" => Ok(Measure::Jaccard),
+            "euclidean" => Ok(Measure::Euclidean),
+            "unifrac" => Ok(Measure::UniFrac),
+            "weighted-unifrac" => Ok(Measure::WeightedUniFrac),
+            _ => Err(ErrorKind::ParseMeasureError(s.to_string()).into()),
+        }
+    }
+}
+
+/// A normalisation of the counts
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalisation {
+    Relative,
+    Clr,
+    Rarefy,
+}
+
+static NORMALISATIONS: &[&str] = &["relative", "clr", "rarefy"];
+impl Normalisation {
+    fn variants() -> &'static [&'static str] {
+        NORMALISATIONS
+    }
+}
+
+impl FromStr for Normalisation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "relative" => Ok(Normalisation::Relative),
+            "clr" => Ok(Normalisation::Clr),
+            "rarefy" => Ok(Normalisation::Rarefy),
+            _ => Err(ErrorKind::ParseNormalisationError(s.to_string()).into()),
+        }
+    }
+}
+
+error_chain! {
+    errors {
+        /// Unparseable Measure
+        ParseMeasureError(measure: String) {
+            description("Unparseable measure")
+            display("Unparseable measure: {}", measure)
+        }
+        /// Unparseable Normalisation
+        ParseNormalisationError(normalisation: String) {
+            description("Unparseable normalisation")
+            display("Unparseable normalisation: {}", normalisation)
+        }
+    }
+}
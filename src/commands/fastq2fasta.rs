@@ -1,12 +1,18 @@
 //! The `umgap fastq2fasta` command.
 
-use std::fs;
 use std::io;
+use std::iter;
 use std::path::PathBuf;
 
+use crate::dna::merge::Merger;
+use crate::dna::trim::Trimmer;
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 use crate::io::fastq;
+use crate::io::fastq::MateName;
+use crate::io::fastq::QualityEncoding;
+use crate::io::sam;
 use crate::utils;
 
 #[rustfmt::skip]
@@ -21,7 +27,28 @@ use crate::utils;
 /// from each of these files, and the record header and sequence are written to *standard output* in
 /// FASTA format, dropping the quality scores, until any of the files runs out or records.
 ///
+/// The input files may be compressed with gzip, bzip2, xz or zstd, which is detected
+/// automatically.
+///
 /// This command is generally used to combine two paired-end FASTQ files into a single FASTA file.
+/// In that case, the records taken together from each file should be mates: their read names
+/// should be equal, and their mate numbers (a `/1` or `/2` suffix, or an Illumina `1:N:0` comment)
+/// should match the order of the files. The files should contain the same number of records.
+/// Otherwise, the command fails.
+///
+/// With `--sam`, the input files are read as SAM or BAM files of unaligned reads instead, of which
+/// secondary and supplementary records are skipped. Other records can be selected with the SAM
+/// flags which must be set (`-f`) or unset (`-F`), like `samtools view`. With `--split-mates`, a
+/// single file with both mates of each pair is split into the first and last mates, which are then
+/// handled as two paired-end files.
+///
+/// ```sh
+/// $ umgap fastq2fasta --sam --split-mates -m reads.bam
+/// ```
+///
+/// The headers are normalised to the read name, followed by `/` and the number of the input file
+/// if more than one file is given, so `umgap uniq -d /` can join the mates later on. Any comments
+/// are dropped. Use `-k` to keep the headers as they are.
 ///
 /// ```sh
 /// $ cat input_1.fq
@@ -52,33 +79,323 @@ use crate::utils;
 /// >record2/2
 /// GGACACGCTCTCAGGACGATGGCGCGATTGCAGGACTTGCTGGATCTCCTCCGTCGCCAAGGGGACGCGCTCGGAGTGGCTCATGGAGCAGACGAGTTCT
 /// ```
+///
+/// The reads can be cleaned up before they are written, using their quality scores. Their encoding
+/// (Phred+33 or Phred+64) is detected from the first records of each FASTQ file, or can be set with
+/// `--phred33` or `--phred64`. A later record contradicting a detected Phred+64 encoding is an
+/// error, as the records before it were scored with it. In order:
+///
+/// * `-a adapters.fa` clips the adapters in the given FASTA file from the reads, including
+///   partial adapters of at least 8 bases (`--adapter-overlap`) at the end of a read, with 10% of
+///   mismatches allowed (`--adapter-mismatches`).
+/// * `--leading 3` and `--trailing 3` remove bases with a quality below 3 from the start and the
+///   end of the reads.
+/// * `-q 15` cuts reads once the average quality in a sliding window of 4 bases (`-w`) drops
+///   below 15.
+/// * `-d 20` masks low-complexity regions, with a DUST score above 20, with N's.
+/// * `-n 0.1` drops reads with more than 10% N's.
+/// * `-l 50` drops reads shorter than 50 bases after trimming.
+///
+/// If a read of a pair is dropped, its mates are dropped as well.
+///
+/// ```sh
+/// $ umgap fastq2fasta -a adapters.fa -q 15 -l 50 input_1.fq input_2.fq
+/// ```
+///
+/// With `-m`, the reads of two paired-end files are merged into the fragment they were sequenced
+/// from, if read 1 and the reverse complement of read 2 overlap by at least 10 bases
+/// (`--min-overlap`) with at most 10% of mismatches (`--max-mismatches`). Mismatches are resolved
+/// using the quality scores. The merged fragment is written under the read name, without a mate
+/// number. Pairs which can't be merged are interleaved as usual. Statistics on the merged pairs are
+/// reported on *standard error*.
+///
+/// ```sh
+/// $ umgap fastq2fasta -m input_1.fq input_2.fq
+/// ```
 pub struct FastqToFasta {
     /// The input files
     #[structopt(parse(from_os_str))]
     pub input: Vec<PathBuf>,
+
+    /// Keep the FASTQ headers as they are, instead of normalising them
+    #[structopt(short = "k", long = "keep-headers")]
+    pub keep_headers: bool,
+
+    /// The input files are SAM or BAM files
+    #[structopt(long = "sam")]
+    pub sam: bool,
+
+    /// Only read SAM records with all of these flags set
+    #[structopt(short = "f", long = "require-flags", default_value = "0",
+                parse(try_from_str = sam::parse_flags))]
+    pub require_flags: u16,
+
+    /// Skip SAM records with any of these flags set
+    #[structopt(short = "F", long = "exclude-flags", default_value = "0x900",
+                parse(try_from_str = sam::parse_flags))]
+    pub exclude_flags: u16,
+
+    /// Read the first and last mates from a single SAM or BAM file as paired-end inputs
+    #[structopt(long = "split-mates")]
+    pub split_mates: bool,
+
+    /// Quality scores are encoded as Phred+33, instead of detecting the encoding (FASTQ only)
+    #[structopt(long = "phred33", conflicts_with = "phred64")]
+    pub phred33: bool,
+
+    /// Quality scores are encoded as Phred+64 instead of Phred+33 (FASTQ only)
+    #[structopt(long = "phred64")]
+    pub phred64: bool,
+
+    /// A FASTA file of adapter sequences to clip from the reads
+    #[structopt(short = "a", long = "adapters", parse(from_os_str))]
+    pub adapters: Option<PathBuf>,
+
+    /// The minimal overlap of a partial adapter at the end of a read
+    #[structopt(long = "adapter-overlap", default_value = "8")]
+    pub adapter_overlap: usize,
+
+    /// The fraction of mismatches allowed in an adapter match
+    #[structopt(long = "adapter-mismatches", default_value = "0.1")]
+    pub adapter_mismatches: f32,
+
+    /// Remove leading bases with a quality below this score
+    #[structopt(long = "leading")]
+    pub leading: Option<u8>,
+
+    /// Remove trailing bases with a quality below this score
+    #[structopt(long = "trailing")]
+    pub trailing: Option<u8>,
+
+    /// Cut reads once the average quality in the sliding window drops below this score
+    #[structopt(short = "q", long = "window-quality")]
+    pub window_quality: Option<u8>,
+
+    /// The size of the sliding window
+    #[structopt(short = "w", long = "window-size", default_value = "4")]
+    pub window_size: usize,
+
+    /// Mask low-complexity regions with a DUST score above this threshold
+    #[structopt(short = "d", long = "dust")]
+    pub dust: Option<f32>,
+
+    /// Drop reads with a larger fraction of N's
+    #[structopt(short = "n", long = "max-n")]
+    pub max_n: Option<f32>,
+
+    /// Drop reads shorter than this after trimming
+    #[structopt(short = "l", long = "min-length", default_value = "0")]
+    pub min_length: usize,
+
+    /// Merge overlapping paired-end reads
+    #[structopt(short = "m", long = "merge")]
+    pub merge: bool,
+
+    /// The minimal overlap between merged reads
+    #[structopt(long = "min-overlap", default_value = "10")]
+    pub min_overlap: usize,
+
+    /// The fraction of mismatches allowed in the overlap between merged reads
+    #[structopt(long = "max-mismatches", default_value = "0.1")]
+    pub max_mismatches: f32,
 }
 
 /// Implements the fastq2fasta command.
 pub fn fastq2fasta(args: FastqToFasta) -> errors::Result<()> {
-    let handles = args
-        .input
-        .iter()
-        .map(fs::File::open)
-        .collect::<io::Result<Vec<fs::File>>>()?;
-    let readers = handles
-        .iter()
-        .map(fastq::Reader::new)
-        .map(fastq::Reader::records)
-        .collect();
+    let mut adapters = Vec::new();
+    if let Some(path) = &args.adapters {
+        for record in fasta::Reader::new(compression::open(path)?, true).records() {
+            let adapter = record?.sequence.concat().into_bytes();
+            if !adapter.is_empty() {
+                adapters.push(adapter);
+            }
+        }
+    }
+    let trimmer = Trimmer {
+        leading: args.leading,
+        trailing: args.trailing,
+        window: args.window_quality.map(|q| (args.window_size, q)),
+        adapters,
+        adapter_overlap: args.adapter_overlap,
+        adapter_mismatches: args.adapter_mismatches,
+        dust: args.dust,
+        max_n: args.max_n,
+        min_length: args.min_length,
+    };
+
+    let merger = Merger {
+        min_overlap: args.min_overlap,
+        max_mismatches: args.max_mismatches,
+    };
+    if args.split_mates && !(args.sam && args.input.len() == 1) {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "Splitting mates requires a single SAM or BAM input file.".into(),
+        )
+        .into());
+    }
+    if args.phred64 && args.sam {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "SAM and BAM files have no Phred+64 encoding, --phred64 is for FASTQ only.".into(),
+        )
+        .into());
+    }
+
+    let mut readers: Vec<Reads> = Vec::new();
+    if args.sam {
+        let flags = sam::Flags {
+            required: args.require_flags,
+            excluded: args.exclude_flags,
+        };
+        for path in &args.input {
+            let reader = sam::Reader::new(compression::open(path)?, flags)?;
+            if args.split_mates {
+                let (first, last) = reader.split_mates();
+                readers.push(sam_reads(first));
+                readers.push(sam_reads(last));
+            } else {
+                readers.push(sam_reads(reader.records()));
+            }
+        }
+    } else {
+        for path in &args.input {
+            let mut reader = if args.phred64 {
+                fastq::Reader::with_encoding(compression::open(path)?, QualityEncoding::Phred64)
+            } else if args.phred33 {
+                fastq::Reader::with_encoding(compression::open(path)?, QualityEncoding::Phred33)
+            } else {
+                fastq::Reader::new(compression::open(path)?)
+            };
+            readers.push(Box::new(iter::from_fn(move || {
+                let record = reader.read_record().transpose()?;
+                let encoding = reader.encoding().unwrap_or(QualityEncoding::Phred33);
+                Some(record.map(|record| (record, encoding)).map_err(Into::into))
+            })));
+        }
+    }
+
+    if args.merge && readers.len() != 2 {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "Merging requires exactly two paired-end inputs.".into(),
+        )
+        .into());
+    }
     let mut writer = fasta::Writer::new(io::stdout(), "", false);
-    for recordzip in utils::Zip::new(readers) {
-        for record in recordzip {
-            let record = record?;
+    let mut stats = MergeStats::default();
+    let mut zip = utils::Zip::new(readers);
+    for recordzip in zip.by_ref() {
+        let records = recordzip.into_iter().collect::<errors::Result<Vec<_>>>()?;
+        let paired = records.len() > 1;
+        if paired {
+            fastq::check_mates(
+                &records
+                    .iter()
+                    .map(|(record, _)| record.header.as_str())
+                    .collect::<Vec<_>>(),
+            )?;
+        }
+
+        let mates = records.len();
+        let mut reads = Vec::with_capacity(mates);
+        for (i, (record, encoding)) in records.into_iter().enumerate() {
+            let header = if args.keep_headers {
+                record.header
+            } else {
+                MateName::parse(&record.header).normalise(if paired { Some(i + 1) } else { None })
+            };
+            let mut scores = record
+                .quality
+                .bytes()
+                .map(|q| encoding.score(q))
+                .collect::<Vec<u8>>();
+            let mut sequence = record.sequence.into_bytes();
+            if !trimmer.trim(&mut sequence, &mut scores) {
+                break;
+            }
+            reads.push((header, sequence, scores));
+        }
+        // Drop the whole pair if any of its reads is dropped, so no mates are orphaned
+        if reads.len() < mates {
+            continue;
+        }
+
+        if args.merge {
+            stats.pairs += 1;
+            if let [(header, seq1, scores1), (_, seq2, scores2)] = &reads[..] {
+                if let Some(merged) = merger.merge(seq1, scores1, seq2, scores2) {
+                    if merged.sequence.len() >= args.min_length {
+                        stats.add(merged.sequence.len(), merged.overlap, merged.mismatches);
+                        let header = if args.keep_headers {
+                            header.clone()
+                        } else {
+                            MateName::parse(header).name.to_string()
+                        };
+                        writer.write_record(fasta::Record {
+                            header,
+                            sequence: vec![String::from_utf8_lossy(&merged.sequence).into_owned()],
+                        })?;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        for (header, sequence, _) in reads {
             writer.write_record(fasta::Record {
-                header: record.header,
-                sequence: vec![record.sequence],
+                header,
+                sequence: vec![String::from_utf8_lossy(&sequence).into_owned()],
             })?;
         }
     }
+
+    if zip.uneven() {
+        return Err(fastq::Error::from(fastq::ErrorKind::UnevenPairs).into());
+    }
+
+    if args.merge {
+        stats.report();
+    }
     Ok(())
 }
+
+// The reads of an input, each with the quality encoding to score it with.
+type Reads = Box<dyn Iterator<Item = errors::Result<(fastq::Record, QualityEncoding)>>>;
+
+// The quality scores of SAM and BAM records are always converted to Phred+33.
+fn sam_reads<I: Iterator<Item = sam::Result<fastq::Record>> + 'static>(records: I) -> Reads {
+    Box::new(records.map(|r| {
+        r.map(|record| (record, QualityEncoding::Phred33))
+            .map_err(Into::into)
+    }))
+}
+
+/// Statistics on the merged read pairs.
+#[derive(Debug, Default)]
+struct MergeStats {
+    pairs: usize,
+    merged: usize,
+    length: usize,
+    overlap: usize,
+    mismatches: usize,
+}
+
+impl MergeStats {
+    fn add(&mut self, length: usize, overlap: usize, mismatches: usize) {
+        self.merged += 1;
+        self.length += length;
+        self.overlap += overlap;
+        self.mismatches += mismatches;
+    }
+
+    fn report(&self) {
+        let mean = |total: usize| total as f64 / self.merged.max(1) as f64;
+        eprintln!(
+            "Merged {} of {} pairs ({:.2}%)",
+            self.merged,
+            self.pairs,
+            100.0 * self.merged as f64 / self.pairs.max(1) as f64
+        );
+        eprintln!("Mean fragment length: {:.1}", mean(self.length));
+        eprintln!("Mean overlap: {:.1}", mean(self.overlap));
+        eprintln!("Mean mismatches in overlap: {:.2}", mean(self.mismatches));
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashSet;
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -70,7 +71,7 @@ pub fn filter(args: Filter) -> errors::Result<()> {
     let lacks = args.lacks.chars().collect::<HashSet<char>>();
 
     let mut writer = fasta::Writer::new(io::stdout(), "\n", false);
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         let fasta::Record { header, sequence } = record?;
 
         writer.write_record(fasta::Record {
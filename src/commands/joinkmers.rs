@@ -7,6 +7,7 @@ use std::path::PathBuf;
 use crate::agg;
 use crate::agg::Aggregator;
 use crate::errors;
+use crate::io::compression;
 use crate::taxon;
 use crate::taxon::TaxonId;
 use crate::tree;
@@ -54,16 +55,15 @@ pub fn joinkmers(args: JoinKmers) -> errors::Result<()> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
         .delimiter(b'\t')
-        .from_reader(io::stdin());
+        .from_reader(compression::stdin()?);
 
     let stdout = io::stdout();
     let mut handle = stdout.lock();
 
-    let taxons = taxon::read_taxa_file(args.taxon_file)?;
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
 
     // Parsing the Taxa file
-    let tree = taxon::TaxonTree::new(&taxons);
-    let by_id = taxon::TaxonList::new(taxons);
+    let (tree, by_id) = taxon::read_taxonomy(args.taxon_file)?;
     let ranksnapping = tree.snapping(&by_id, true);
     let validsnapping = tree.snapping(&by_id, false);
     let aggregator = tree::mix::MixCalculator::new(tree.root, &by_id, 0.95);
@@ -98,7 +98,7 @@ pub fn joinkmers(args: JoinKmers) -> errors::Result<()> {
         }
 
         current_kmer = Some(kmer);
-        if let Some(validancestor) = validsnapping[tid] {
+        if let Some(validancestor) = validsnapping[remapping.remap(tid)] {
             current_tids.push((validancestor, 1.0));
         }
         current_uids.push(uid);
@@ -107,5 +107,6 @@ pub fn joinkmers(args: JoinKmers) -> errors::Result<()> {
         emit(&c, current_tids, current_uids)?;
     }
 
+    remapping.report();
     Ok(())
 }
@@ -0,0 +1,79 @@
+//! The `umgap maptaxa` command.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::io::gtdb;
+use crate::taxon::TaxonId;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Maps taxon IDs onto the taxa of another taxonomy
+///
+/// The `umgap maptaxa` command translates taxon IDs of the NCBI taxonomy into taxon IDs of the GTDB
+/// taxonomy, using the mapping written by `umgap buildgtdb -m`. This way, the results of an index
+/// built on the NCBI taxonomy can be reported in GTDB terms. Taxon IDs without a mapping are
+/// replaced by 0, with a warning on *standard error* that lists them.
+///
+/// The input is given on *standard input* and may be any sequence of FASTA headers and/or lines
+/// starting with a taxon ID, optionally followed by other fields separated by tabs. The FASTA
+/// headers and the other fields (if any) are just copied over to *standard output*.
+///
+/// ```sh
+/// $ umgap buildgtdb -m ncbi2gtdb.tsv -n taxons.tsv bac120_metadata.tsv > gtdb-taxons.tsv
+/// $ umgap maptaxa ncbi2gtdb.tsv < input.fa | umgap taxonomy gtdb-taxons.tsv
+/// ```
+pub struct MapTaxa {
+    /// The mapping of taxon IDs, with an old and new taxon ID on each line
+    #[structopt(parse(from_os_str))]
+    pub mapping_file: PathBuf,
+}
+
+/// Implements the maptaxa command.
+pub fn maptaxa(args: MapTaxa) -> errors::Result<()> {
+    let mapping = gtdb::read_mapping(BufReader::new(compression::open(&args.mapping_file)?))?;
+
+    let mut unmapped = BTreeMap::new();
+    let stdin = BufReader::new(compression::stdin()?);
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for line in stdin.lines() {
+        let line = line?;
+        if line.starts_with('>') {
+            writeln!(handle, "{}", line)?;
+        } else {
+            // The taxon ID may be followed by other fields, separated by a tab.
+            let mut fields = line.splitn(2, '\t');
+            let taxon = fields.next().unwrap_or("").trim().parse::<TaxonId>()?;
+            let mapped = match mapping.get(&taxon) {
+                Some(&mapped) => mapped,
+                None => {
+                    if taxon != 0 {
+                        *unmapped.entry(taxon).or_insert(0) += 1;
+                    }
+                    0
+                }
+            };
+            write!(handle, "{}", mapped)?;
+            if let Some(rest) = fields.next() {
+                write!(handle, "\t{}", rest)?;
+            }
+            writeln!(handle)?;
+        }
+    }
+    handle.flush()?;
+
+    for (taxon, count) in unmapped {
+        eprintln!(
+            "Warning: unmapped taxon {} ({} times) became 0",
+            taxon, count
+        );
+    }
+    Ok(())
+}
@@ -1,22 +1,34 @@
 //! The subcommands of the umgap command.
 
 pub mod bestof;
+pub mod buildgtdb;
 pub mod buildindex;
+pub mod buildtaxcache;
+pub mod buildtaxonomy;
+pub mod checktaxonomy;
+pub mod compare;
 pub mod fastq2fasta;
 pub mod filter;
 pub mod joinkmers;
+pub mod maptaxa;
 pub mod pept2lca;
 pub mod printindex;
 pub mod prot2kmer;
 pub mod prot2kmer2lca;
 pub mod prot2tryp;
 pub mod prot2tryp2lca;
+pub mod remaptaxa;
 pub mod seedextend;
 pub mod snaptaxon;
 pub mod splitkmers;
+pub mod subtaxonomy;
 pub mod taxa2agg;
 pub mod taxa2freq;
+pub mod taxa2krona;
+pub mod taxa2report;
 pub mod taxa2tree;
 pub mod taxonomy;
+pub mod taxonomydiff;
+pub mod taxonsearch;
 pub mod translate;
 pub mod uniq;
@@ -1,12 +1,12 @@
 //! The `umgap pept2lca` command.
 
 use std::fs;
-use std::io;
 use std::path::PathBuf;
 
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -80,7 +80,7 @@ pub fn pept2lca(args: PeptToLca) -> errors::Result<()> {
 
     let default = if args.one_on_one { Some(0) } else { None };
 
-    fasta::Reader::new(io::stdin(), false)
+    fasta::Reader::new(compression::stdin()?, false)
         .records()
         .chunked(args.chunk_size)
         .par_bridge()
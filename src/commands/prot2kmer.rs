@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -42,7 +43,7 @@ pub struct ProtToKmer {
 /// Implements the prot2kmer command.
 pub fn prot2kmer(args: ProtToKmer) -> errors::Result<()> {
     let mut writer = fasta::Writer::new(io::stdout(), "\n", false);
-    for record in fasta::Reader::new(io::stdin(), true).records() {
+    for record in fasta::Reader::new(compression::stdin()?, true).records() {
         let fasta::Record { header, sequence } = record?;
         if sequence[0].len() < args.length {
             continue;
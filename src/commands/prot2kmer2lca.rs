@@ -17,6 +17,7 @@ use hash_index::index::conflict_table::ConflictTable;
 use hash_index::kmer::Kmer;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -152,7 +153,7 @@ pub fn prot2kmer2lca(args: ProtToKmerToLca) -> errors::Result<()> {
         Ok(())
     } else {
         stream_prot2kmer2lca(
-            io::stdin(),
+            compression::stdin()?,
             io::stdout(),
             &conflict_table,
             &lca_table,
@@ -203,7 +204,8 @@ where
                                     .join(";");
 
                                 return format!("{}\t{}", lca_table.get(fpointer as usize).to_string(), functions);
-                            }))
+                            })
+                            .or_else(|| default.map(|taxon| format!("{}\t", taxon))))
                         //.map(|lca| lca.to_string())
                         .collect::<Vec<_>>()
                         .join("\n");
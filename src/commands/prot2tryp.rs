@@ -3,6 +3,7 @@
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -51,7 +52,7 @@ pub fn prot2tryp(args: ProtToTryp) -> errors::Result<()> {
     let pattern = regex::Regex::new(&args.pattern)?;
 
     let mut writer = fasta::Writer::new(io::stdout(), "\n", false);
-    for record in fasta::Reader::new(io::stdin(), true).records() {
+    for record in fasta::Reader::new(compression::stdin()?, true).records() {
         let fasta::Record { header, sequence } = record?;
 
         // We will run the regex replacement twice, since a letter can be
@@ -2,7 +2,6 @@
 
 use std::collections::HashSet;
 use std::fs;
-use std::io;
 use std::path::PathBuf;
 
 use fst;
@@ -12,6 +11,7 @@ use regex;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -97,7 +97,7 @@ pub fn prot2tryp2lca(args: ProtToTrypToLca) -> errors::Result<()> {
     let contains = args.contains.chars().collect::<HashSet<char>>();
     let lacks = args.lacks.chars().collect::<HashSet<char>>();
 
-    fasta::Reader::new(io::stdin(), false)
+    fasta::Reader::new(compression::stdin()?, false)
         .records()
         .chunked(args.chunk_size)
         .par_bridge()
@@ -0,0 +1,98 @@
+//! The `umgap remaptaxa` command.
+
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::taxon;
+use crate::taxon::TaxonId;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Replaces merged and deleted taxon IDs by their current ones
+///
+/// The `umgap remaptaxa` command upgrades results produced with an older release of the taxonomy,
+/// so they can be used with a newer one. Each taxon ID that was merged into another taxon is
+/// replaced by the ID of that taxon, and each deleted taxon ID by 0. A warning is written to
+/// *standard error* for each replaced ID, with the number of times it occurred.
+///
+/// The input is given on *standard input* and may be any sequence of FASTA headers and/or lines
+/// starting with a taxon ID, optionally followed by other fields separated by tabs. The FASTA
+/// headers and the other fields (if any) are just copied over to *standard output*.
+///
+/// The taxonomy to be used is passed as an argument to this command. The merged and deleted IDs are
+/// read from the file with the same name and `.remap` appended, as written by `umgap
+/// buildtaxonomy -m`. Another file can be given with the `-m` option.
+///
+/// ```sh
+/// $ cat taxons.tsv.remap
+/// 12	74109
+/// 3417211	0
+/// $ cat input.fa
+/// >header1
+/// 12
+/// 562
+/// 3417211
+/// $ umgap remaptaxa taxons.tsv < input.fa
+/// >header1
+/// 74109
+/// 562
+/// 0
+/// Warning: merged taxon 12 (1 times) became 74109
+/// Warning: deleted taxon 3417211 (1 times) became 0
+/// ```
+#[rustfmt::skip]
+#[allow(clippy::tabs_in_doc_comments)]
+pub struct RemapTaxa {
+    /// The file with the merged and deleted taxon IDs, instead of the one belonging to the
+    /// taxonomy
+    #[structopt(short = "m", long = "remap", parse(from_os_str))]
+    pub remap_file: Option<PathBuf>,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+}
+
+/// Implements the remaptaxa command.
+pub fn remaptaxa(args: RemapTaxa) -> errors::Result<()> {
+    let remap_file = match args.remap_file {
+        Some(remap_file) => remap_file,
+        None => taxon::Remapping::path_for(&args.taxon_file),
+    };
+    if !remap_file.exists() {
+        return Err(errors::ErrorKind::InvalidInvocation(format!(
+            "No remapping file {} found, build one with `umgap buildtaxonomy -m`.",
+            remap_file.display()
+        ))
+        .into());
+    }
+    let remapping = taxon::read_remap_file(&remap_file)?;
+
+    let stdin = BufReader::new(compression::stdin()?);
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for line in stdin.lines() {
+        let line = line?;
+        if line.starts_with('>') {
+            writeln!(handle, "{}", line)?;
+        } else {
+            // The taxon ID may be followed by other fields, separated by a tab.
+            let mut fields = line.splitn(2, '\t');
+            let taxon = fields.next().unwrap_or("").trim().parse::<TaxonId>()?;
+            write!(handle, "{}", remapping.remap(taxon))?;
+            if let Some(rest) = fields.next() {
+                write!(handle, "\t{}", rest)?;
+            }
+            writeln!(handle)?;
+        }
+    }
+    handle.flush()?;
+
+    remapping.report();
+    Ok(())
+}
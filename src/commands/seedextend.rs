@@ -5,6 +5,7 @@ use std::io;
 use std::path::PathBuf;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 use crate::taxon;
 use crate::taxon::TaxonId;
@@ -59,7 +60,8 @@ use crate::taxon::TaxonId;
 /// The command can be altered to print only the extended seed with the highest score among all
 /// extended seeds. Pass a taxonomy using the `-r taxon.tsv` option to activate this. In this scored
 /// mode, extended seeds with gaps are given a penalty of 5, which can be made more or less severe
-/// (higher or lower) with the `-p` option.
+/// (higher or lower) with the `-p` option. A remapping of merged and deleted taxa (see `umgap
+/// buildtaxonomy -m`) is applied to the taxon IDs to score them, but not to the output.
 pub struct SeedExtend {
     /// The minimum length of equal taxa to count as seed
     #[structopt(short = "s", long = "min-seed-size", default_value = "2")]
@@ -82,14 +84,17 @@ pub struct SeedExtend {
 pub fn seedextend(args: SeedExtend) -> errors::Result<()> {
     let mut writer = fasta::Writer::new(io::stdout(), "\n", false);
 
-    let by_id = if let Some(ref tf) = args.ranked {
+    let (by_id, remapping) = if let Some(ref tf) = args.ranked {
         let taxa = taxon::read_taxa_file(tf)?;
-        Some(taxon::TaxonList::new_with_unknown(taxa, true))
+        (
+            Some(taxon::TaxonList::new_with_unknown(taxa, true)?),
+            taxon::Remapping::for_taxonomy(tf)?,
+        )
     } else {
-        None
+        (None, taxon::Remapping::default())
     };
 
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         let record = record?;
         let mut taxons = record
             .sequence
@@ -156,7 +161,7 @@ pub fn seedextend(args: SeedExtend) -> errors::Result<()> {
                         .iter()
                         .skip(*s)
                         .take(e - s)
-                        .map(|t| by_id.score(*t).unwrap_or(args.penalty))
+                        .map(|t| by_id.score(remapping.remap(*t)).unwrap_or(args.penalty))
                         .sum::<usize>()
                 })
                 .into_iter()
@@ -175,5 +180,6 @@ pub fn seedextend(args: SeedExtend) -> errors::Result<()> {
             })
             .map_err(|err| err.to_string())?;
     }
+    remapping.report();
     Ok(())
 }
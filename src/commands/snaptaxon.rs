@@ -2,14 +2,16 @@
 
 use std::io;
 use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Write;
 use std::path::PathBuf;
 
 use crate::errors;
+use crate::io::compression;
+use crate::names;
 use crate::rank;
 use crate::rank::Rank;
 use crate::taxon;
-use crate::taxon::TaxonId;
 
 #[derive(Debug, StructOpt)]
 #[structopt(verbatim_doc_comment)]
@@ -47,6 +49,9 @@ use crate::taxon::TaxonId;
 /// 1239
 /// 2
 /// ```
+///
+/// The taxa to snap towards can also be given by name, such as `-t Bacillota`. Use `umgap
+/// taxonsearch` to look up names that aren't unique.
 pub struct SnapTaxon {
     /// The rank to snap towards.
     #[structopt(
@@ -56,9 +61,9 @@ pub struct SnapTaxon {
     )]
     pub rank: Option<Rank>,
 
-    /// A taxon to snap towards (allow multiple times).
+    /// A taxon to snap towards, by ID or name (allow multiple times).
     #[structopt(short = "t", long = "taxons")]
-    pub taxons: Vec<TaxonId>,
+    pub taxons: Vec<String>,
 
     /// Include the invalidated taxa from the taxonomy
     #[structopt(short = "i", long = "invalid")]
@@ -71,16 +76,20 @@ pub struct SnapTaxon {
 
 /// Implements the snaptaxon command.
 pub fn snaptaxon(args: SnapTaxon) -> errors::Result<()> {
-    let taxons = taxon::read_taxa_file(&args.taxon_file)?;
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
     if args.rank.map(|r| r == rank::Rank::NoRank).unwrap_or(false) {
         return Err(errors::ErrorKind::InvalidInvocation("Snap to an actual rank.".into()).into());
     }
 
     // Parsing the taxons
-    let tree = taxon::TaxonTree::new(&taxons);
-    let by_id = taxon::TaxonList::new(taxons);
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
+    let mut targets = names::resolve_all(&args.taxons, &args.taxon_file, &by_id)?;
+    for id in targets.iter_mut() {
+        *id = remapping.remap(*id);
+        by_id.get_or_unknown(*id)?;
+    }
     let snapping = tree.filter_ancestors(|tid| {
-        args.taxons.contains(&tid)
+        targets.contains(&tid)
             || by_id
                 .get(tid)
                 .map(|t| {
@@ -90,19 +99,20 @@ pub fn snaptaxon(args: SnapTaxon) -> errors::Result<()> {
     });
 
     // Read and count taxon ranks
-    let stdin = io::stdin();
+    let stdin = BufReader::new(compression::stdin()?);
     let stdout = io::stdout();
     let mut handle = stdout.lock();
-    for line in stdin.lock().lines() {
+    for line in stdin.lines() {
         let line = line?;
         if line.starts_with('>') {
             writeln!(handle, "{}", line)?;
         } else {
-            let taxon = line.parse::<taxon::TaxonId>()?;
+            let taxon = remapping.remap(line.parse::<taxon::TaxonId>()?);
             let snapped = snapping[taxon].unwrap_or(0);
             writeln!(handle, "{}", snapped)?;
         }
     }
 
+    remapping.report();
     Ok(())
 }
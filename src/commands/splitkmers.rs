@@ -3,6 +3,7 @@
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::taxon::TaxonId;
 
 #[rustfmt::skip]
@@ -56,7 +57,7 @@ pub fn splitkmers(args: SplitKmers) -> errors::Result<()> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
         .delimiter(b'\t')
-        .from_reader(io::stdin());
+        .from_reader(compression::stdin()?);
 
     let mut writer = csv::WriterBuilder::new()
         .delimiter(b'\t')
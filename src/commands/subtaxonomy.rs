@@ -0,0 +1,134 @@
+//! The `umgap subtaxonomy` command.
+
+use std::collections::HashSet;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::names;
+use crate::rank::Rank;
+use crate::taxon;
+use crate::taxon::TaxonId;
+
+#[rustfmt::skip]
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
+/// Extracts a smaller taxonomy from a taxonomy TSV-file
+///
+/// The `umgap subtaxonomy` command restricts a taxonomy to the taxa relevant to a study, so smaller
+/// taxonomies and indexes can be built. The result is written to *standard output* in the same
+/// format as the taxonomy itself, so it can be used by the other commands.
+///
+/// With the `-t` option, the taxa below the given taxa (by ID or name) are kept. With the `-i`
+/// option, the taxa listed in the given file and the taxa above them are kept. This file contains
+/// a taxon ID on each line, such as the output of the other commands. FASTA headers and any fields
+/// following the taxon ID are ignored. The ancestors of the kept taxa are always kept too, so their
+/// lineages are unchanged.
+///
+/// ```sh
+/// $ umgap subtaxonomy -t Viroids taxons.tsv | cut -f1-4
+/// 1	root	no rank	1
+/// 12884	Viroids	superkingdom	1
+/// 185751	Pospiviroidae	family	12884
+/// 185752	Avsunviroidae	family	12884
+/// $ umgap pept2lca tryptic-peptides.index < input.fa > found.fa
+/// $ umgap subtaxonomy -i found.fa taxons.tsv > found-taxons.tsv
+/// ```
+///
+/// With the `-r` option, only the taxa of the given ranks are kept. Each of them is attached to
+/// its closest ancestor that is kept as well. The root of the taxonomy is always kept.
+///
+/// ```sh
+/// $ umgap subtaxonomy -r superkingdom -r phylum -r class -r order -r family -r genus -r species \
+///       taxons.tsv > taxons-ranked.tsv
+/// $ umgap taxa2agg taxons-ranked.tsv < input.fa
+/// ```
+///
+/// A remapping of merged and deleted taxa (see `umgap buildtaxonomy -m`) is applied to the given
+/// taxon IDs, but it isn't copied along.
+pub struct SubTaxonomy {
+    /// Keep the taxa below this taxon, by ID or name (allow multiple times)
+    #[structopt(short = "t", long = "taxa")]
+    pub taxa: Vec<String>,
+
+    /// Keep the taxa listed in this file and their ancestors
+    #[structopt(short = "i", long = "ids", parse(from_os_str))]
+    pub ids_file: Option<PathBuf>,
+
+    /// Keep only the taxa of this rank (allow multiple times)
+    #[structopt(
+        short = "r",
+        long = "ranks",
+        possible_values = &Rank::variants()
+    )]
+    pub ranks: Vec<Rank>,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+}
+
+/// Implements the subtaxonomy command.
+pub fn subtaxonomy(args: SubTaxonomy) -> errors::Result<()> {
+    if args.taxa.is_empty() && args.ids_file.is_none() && args.ranks.is_empty() {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "Select the taxa to keep with -t, -i or -r.".into(),
+        )
+        .into());
+    }
+
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
+
+    // Collect the selected taxa, if any were given
+    let mut ids = Vec::new();
+    if let Some(ids_file) = &args.ids_file {
+        for line in BufReader::new(compression::open(ids_file)?).lines() {
+            let line = line?;
+            if line.starts_with('>') || line.trim().is_empty() {
+                continue;
+            }
+            let id = line.split('\t').next().unwrap_or("").trim();
+            ids.push(remapping.remap(id.parse::<TaxonId>()?));
+        }
+    }
+    let mut subtrees = names::resolve_all(&args.taxa, &args.taxon_file, &by_id)?;
+    for id in subtrees.iter_mut() {
+        *id = remapping.remap(*id);
+        by_id.get_or_unknown(*id)?;
+    }
+    let selected = if args.taxa.is_empty() && args.ids_file.is_none() {
+        None
+    } else {
+        let mut selected = HashSet::new();
+        for &id in ids.iter().chain(subtrees.iter()) {
+            selected.extend(by_id.ancestors(id));
+        }
+        for &id in subtrees.iter() {
+            selected.extend(tree.descendants(id));
+        }
+        Some(selected)
+    };
+
+    let pruned = tree.prune(&by_id, |taxon| {
+        selected
+            .as_ref()
+            .map(|selected| selected.contains(&taxon.id))
+            .unwrap_or(true)
+            && (args.ranks.is_empty() || args.ranks.contains(&taxon.rank))
+    });
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for taxon in pruned.iter() {
+        writeln!(handle, "{}", taxon)?;
+    }
+    handle.flush()?;
+    remapping.report();
+    Ok(())
+}
@@ -8,14 +8,18 @@ use itertools::Itertools;
 
 use crate::agg;
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
+use crate::io::kraken;
 use crate::rmq;
 use crate::taxon;
 use crate::taxon::TaxonId;
 use crate::tree;
 
+#[rustfmt::skip]
 #[derive(Debug, StructOpt)]
 #[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
 /// Aggregates taxon IDs in a FASTA stream
 ///
 /// The `umgap taxa2agg` command takes one or more lists of taxon IDs and aggregates them into a
@@ -59,6 +63,20 @@ use crate::tree;
 ///   not have the highest frequency of ancestors in the list, but would have less contradicting
 ///   taxa. Use the `-f` option to select a hybrid close to the MRTL (`-f 0.0`) or to the LCA (`-f
 ///   1.0`).
+///
+/// With the `--kraken` option, the output follows the per-read classification format of Kraken
+/// instead, so it can be used by tools built around Kraken. Each input record results in a line
+/// with the tab-separated fields `C` (classified) or `U` (unclassified), the FASTA header, the
+/// consensus taxon ID (0 if unclassified), the number of taxon IDs in the record and the trace of
+/// these taxon IDs, in which consecutive equal IDs are grouped as `taxon:count`. Unlike Kraken,
+/// the fourth field is this number of taxon IDs (k-mers or peptides), not the length of the read
+/// in bases. Use the `-o` option of `umgap prot2kmer2lca` to include the k-mers which weren't
+/// found (as taxon 0).
+///
+/// ```sh
+/// $ umgap taxa2agg --kraken taxons.tsv < input.fa
+/// C	header1	571525	6	571525:2 6920:2 1:1 6920:1
+/// ```
 pub struct TaxaToAgg {
     /// Each taxon is followed by a score between 0 and 1
     #[structopt(short = "s", long = "scored")]
@@ -94,6 +112,10 @@ pub struct TaxaToAgg {
     #[structopt(short = "l", long = "lower-bound", default_value = "0")]
     pub lower_bound: f32,
 
+    /// Write per-read classifications in the Kraken output format
+    #[structopt(long = "kraken")]
+    pub kraken: bool,
+
     /// An NCBI taxonomy TSV-file as processed by Unipept
     #[structopt(parse(from_os_str))]
     pub taxon_file: PathBuf,
@@ -101,12 +123,10 @@ pub struct TaxaToAgg {
 
 /// Implements the taxa2agg command.
 pub fn taxa2agg(args: TaxaToAgg) -> errors::Result<()> {
-    // Parsing the Taxa file
-    let taxons = taxon::read_taxa_file(args.taxon_file)?;
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
 
-    // Parsing the taxons
-    let tree = taxon::TaxonTree::new(&taxons);
-    let by_id = taxon::TaxonList::new(taxons);
+    // Parsing the Taxa file
+    let (tree, by_id) = taxon::read_taxonomy(args.taxon_file)?;
     let snapping = tree.snapping(&by_id, args.ranked_only);
 
     let aggregator: errors::Result<Box<dyn agg::Aggregator>> = match (args.method, args.strategy) {
@@ -155,17 +175,37 @@ pub fn taxa2agg(args: TaxaToAgg) -> errors::Result<()> {
     let parser = if args.scored { with_score } else { not_scored };
 
     let mut writer = fasta::Writer::new(io::stdout(), "\n", false);
+    let mut kraken = io::BufWriter::new(io::stdout());
 
     // Iterate over each read
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         // Parse the sequence of LCA's
         let record = record?;
         let taxons = record
             .sequence
             .iter()
             .map(|s| parser(s.split('\t').collect::<Vec<&str>>()[0]))
+            .map(|parsed| parsed.map(|(tid, score)| (remapping.remap(tid), score)))
             .collect::<errors::Result<Vec<(TaxonId, f32)>>>()?;
 
+        if args.kraken {
+            let taxa = taxons.iter().map(|&(tid, _)| tid).collect::<Vec<_>>();
+            let counts = agg::count(taxons.into_iter().filter(|&(tid, _)| tid != 0));
+            let counts = agg::filter(counts, args.lower_bound);
+            let taxon = if counts.is_empty() {
+                None
+            } else {
+                snapping[aggregator.aggregate(&counts)?]
+            };
+            let classification = kraken::Classification {
+                read: &record.header,
+                taxon,
+                taxa: &taxa,
+            };
+            writeln!(kraken, "{}", classification)?;
+            continue;
+        }
+
         let functions = itertools::flatten(record
             .sequence
             .iter()
@@ -187,6 +227,7 @@ pub fn taxa2agg(args: TaxaToAgg) -> errors::Result<()> {
             },
         })?;
     }
+    remapping.report();
     Ok(())
 }
 
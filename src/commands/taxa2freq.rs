@@ -1,15 +1,15 @@
 //! The `umgap taxa2freq` command.
 
 use std::collections::HashMap;
-use std::fs::File;
 use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use crate::errors;
-use crate::rank;
+use crate::io::biom;
+use crate::io::compression;
 use crate::rank::Rank;
 use crate::taxon;
 
@@ -22,9 +22,11 @@ use crate::taxon;
 ///
 /// The input is given on *standard input*, or in multiple file arguments, a single taxon ID on each
 /// line. Each taxon that is more specific than the target rank is counted towards its ancestor on
-/// the target rank. Each taxon less specific than the target rank is counted towards root. The
-/// command outputs a CSV table of taxon IDs, their names, and counts for each input, ordered by
-/// descending sum of counts.
+/// the target rank. Taxa that can't be placed on the target rank, as they have no ancestor on it,
+/// are counted as `unassigned`, and taxon ID 0 is counted as `unclassified`. The command outputs a
+/// CSV table of taxon IDs, their names, and counts for each input, ordered by descending sum of
+/// counts. Rows with a sum of counts up to the minimum frequency (`-f`, 1 by default) are left
+/// out.
 ///
 /// The taxonomy to be used is passed as first argument to this command. This is a preprocessed
 /// version of the NCBI taxonomy.
@@ -44,35 +46,81 @@ use crate::taxon;
 /// 8287
 /// $ umgap taxa2freq taxons.tsv < input.txt
 /// taxon id,taxon name,stdin
-/// 1,root,2
 /// 9606,Homo sapiens,9
+/// ,unassigned,2
 /// $ umgap taxa2freq taxons.tsv input.txt input.txt
 /// taxon id,taxon name,input.txt,input.txt
-/// 1,root,2,2
 /// 9606,Homo sapiens,9,9
+/// ,unassigned,2,2
 /// ```
 ///
-/// With the `-r` option, the default species rank can be set to any named rank.
+/// With the `-r` option, the default species rank can be set to any named rank. When given
+/// multiple times, a block of rows is written for each rank, with the rank in an extra first
+/// column. The `-a` option adds the names of the lineage of each taxon as columns, from realm
+/// down to the most specific rank requested.
 ///
 /// ```sh
 /// $ umgap taxa2freq -r phylum taxons.tsv < input.txt
 /// taxon id,taxon name,stdin
 /// 7711,Chordata,10
+/// $ umgap taxa2freq -a -r superkingdom -r phylum taxons.tsv < input.txt
+/// rank,taxon id,taxon name,realm,domain,superkingdom,kingdom,subkingdom,superphylum,phylum,stdin
+/// superkingdom,2759,Eukaryota,,,Eukaryota,,,,,11
+/// phylum,7711,Chordata,,,Eukaryota,Metazoa,,,Chordata,10
+/// ```
+///
+/// With `-l`, a long-format table is written instead, with a row for each rank, taxon and input
+/// with a non-zero count.
+///
+/// ```sh
+/// $ umgap taxa2freq -l -f 0 -r phylum taxons.tsv input.txt input.txt
+/// rank,taxon id,taxon name,sample,count
+/// phylum,7711,Chordata,input.txt,10
+/// phylum,7711,Chordata,input.txt,10
+/// phylum,,unassigned,input.txt,1
+/// phylum,,unassigned,input.txt,1
+/// ```
+///
+/// With `--format biom`, the table is written in the JSON-based BIOM 1.0 format instead, with the
+/// lineage of each taxon on the main ranks (`k__Eukaryota`, `p__Chordata`, ...) as its taxonomy
+/// metadata, so it can be loaded directly in QIIME or phyloseq. By default, a sparse matrix is
+/// written. Use `--format biom-dense` for a dense matrix. A BIOM table holds a single rank. The
+/// unassigned and unclassified rows get `unassigned` and `unclassified` as ID, and `Unassigned`
+/// and `Unclassified` as taxonomy.
+///
+/// ```sh
+/// $ umgap taxa2freq --format biom taxons.tsv input1.txt input2.txt > table.biom
 /// ```
 pub struct TaxaToFreq {
-    /// The rank to show
+    /// The rank to show (allow multiple times, species by default)
     #[structopt(
         short = "r",
         long = "rank",
-        default_value = "species",
+        number_of_values = 1,
         possible_values = &Rank::variants()
     )]
-    pub rank: Rank,
+    pub ranks: Vec<Rank>,
 
     /// The minimum frequency to be reported
     #[structopt(short = "f", long = "frequency", default_value = "1")]
     pub min_frequency: usize,
 
+    /// Include the names of the lineage of each taxon
+    #[structopt(short = "a", long = "lineage")]
+    pub lineage: bool,
+
+    /// Write a long-format table, with a count on each row
+    #[structopt(short = "l", long = "long")]
+    pub long: bool,
+
+    /// The output format
+    #[structopt(
+        long = "format",
+        default_value = "csv",
+        possible_values = &Format::variants()
+    )]
+    pub format: Format,
+
     /// An NCBI taxonomy TSV-file as processed by Unipept
     #[structopt(parse(from_os_str))]
     pub taxon_file: PathBuf,
@@ -84,86 +132,274 @@ pub struct TaxaToFreq {
 
 /// Implements the taxa2freq command.
 pub fn taxa2freq(args: TaxaToFreq) -> errors::Result<()> {
-    let taxons = taxon::read_taxa_file(&args.taxon_file)?;
-    if args.rank == rank::Rank::NoRank {
+    let ranks = if args.ranks.is_empty() {
+        vec![Rank::Species]
+    } else {
+        args.ranks.clone()
+    };
+    if ranks.contains(&Rank::NoRank) {
         return Err(errors::ErrorKind::InvalidInvocation("Snap to an actual rank.".into()).into());
     }
+    if args.format != Format::Csv && (ranks.len() > 1 || args.long) {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "A BIOM table holds a single rank and can't be written in long format.".into(),
+        )
+        .into());
+    }
+
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
     let numfiles = args.input_files.len();
 
     // Parsing the taxons
-    let tree = taxon::TaxonTree::new(&taxons);
-    let by_id = taxon::TaxonList::new(taxons);
-    let snapping =
-        tree.filter_ancestors(|tid| by_id.get(tid).map(|t| t.rank == args.rank).unwrap_or(false));
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
 
-    // Grab stdout
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
-
-    // Writing headers
-    write!(stdout, "taxon id,taxon name")?;
-    if numfiles == 0 {
-        write!(stdout, ",stdin")?;
-    } else {
-        for filename in args.input_files.iter() {
-            write!(stdout, ",{}", filename.to_string_lossy())?;
-        }
-    }
-    writeln!(stdout)?;
-
-    // Read and count taxon ranks
+    // Read and count taxa
     let mut counts = HashMap::new();
     if numfiles == 0 {
-        let stdin = io::stdin();
-        count_file(&snapping, &mut counts, 0, 1, Box::new(stdin.lock()))?;
+        count_file(
+            &by_id,
+            &remapping,
+            &mut counts,
+            0,
+            1,
+            BufReader::new(compression::stdin()?),
+        )?;
     } else {
         for (i, file) in args.input_files.iter().enumerate() {
             count_file(
-                &snapping,
+                &by_id,
+                &remapping,
                 &mut counts,
                 i,
                 numfiles,
-                Box::new(BufReader::new(File::open(file)?)),
+                BufReader::new(compression::open(file)?),
             )?;
         }
     }
 
-    // Sort rows by total sum
-    let mut sorted_counts = counts
-        .into_iter()
-        .collect::<Vec<(taxon::TaxonId, Vec<usize>)>>();
-    sorted_counts.sort_by_key(|p| p.1.iter().sum::<usize>());
-
-    // Print rows
-    for (tid, row) in sorted_counts.into_iter().rev() {
-        let taxon = by_id
-            .get(tid)
-            .ok_or("LCA taxon id not in taxon list. Check compatibility with index.")?;
-        if row.iter().sum::<usize>() > args.min_frequency {
-            write!(stdout, "{},{}", taxon.id, taxon.name)?;
-            for count in row {
-                write!(stdout, ",{}", count)?;
+    let columns = if numfiles == 0 {
+        vec!["stdin".to_string()]
+    } else {
+        args.input_files
+            .iter()
+            .map(|filename| filename.to_string_lossy().into_owned())
+            .collect()
+    };
+
+    // Snap the taxa to each rank, and sort the rows of each rank by total sum
+    let mut tables = Vec::with_capacity(ranks.len());
+    for &rank in ranks.iter() {
+        let is_ranked = |tid| by_id.get(tid).map(|t| t.rank == rank).unwrap_or(false);
+        let snapping = tree.filter_ancestors(is_ranked);
+        let mut snapped = HashMap::new();
+        for (&taxon, row) in counts.iter() {
+            // Taxa without an ancestor on the rank are snapped to the root, so check the rank.
+            let key = match snapping[taxon] {
+                Some(ancestor) if taxon != 0 && is_ranked(ancestor) => Row::Taxon(ancestor),
+                _ if taxon != 0 => Row::Unassigned,
+                _ => Row::Unclassified,
+            };
+            let totals = snapped.entry(key).or_insert_with(|| vec![0; columns.len()]);
+            for (total, count) in totals.iter_mut().zip(row) {
+                *total += count;
             }
-            writeln!(stdout)?;
         }
+        let mut rows = snapped
+            .into_iter()
+            .filter(|(_, row)| row.iter().sum::<usize>() > args.min_frequency)
+            .collect::<Vec<(Row, Vec<usize>)>>();
+        rows.sort_by(|(a, x), (b, y)| {
+            let sum = |row: &Vec<usize>| row.iter().sum::<usize>();
+            sum(y).cmp(&sum(x)).then(a.cmp(b))
+        });
+        tables.push((rank, rows));
     }
 
+    let lineage_ranks = if args.lineage {
+        let last = *ranks.iter().max().unwrap();
+        Rank::ranks().take_while(|&r| r <= last).collect()
+    } else {
+        Vec::new()
+    };
+
+    match args.format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+
+            // Writing headers
+            let mut header = Vec::new();
+            if ranks.len() > 1 || args.long {
+                header.push("rank".to_string());
+            }
+            header.push("taxon id".to_string());
+            header.push("taxon name".to_string());
+            for rank in lineage_ranks.iter() {
+                header.push(rank.to_string().replace(" ", "_"));
+            }
+            if args.long {
+                header.push("sample".to_string());
+                header.push("count".to_string());
+            } else {
+                header.extend(columns.iter().cloned());
+            }
+            writer.write_record(&header)?;
+
+            // Print rows
+            for (rank, rows) in tables {
+                for (key, row) in rows {
+                    let mut record = Vec::new();
+                    if ranks.len() > 1 || args.long {
+                        record.push(rank.to_string());
+                    }
+                    record.push(key.id());
+                    record.push(key.name(&by_id)?);
+                    record.extend(key.lineage(&by_id, &lineage_ranks)?);
+                    if args.long {
+                        for (column, count) in columns.iter().zip(row) {
+                            if count > 0 {
+                                let mut record = record.clone();
+                                record.push(column.clone());
+                                record.push(count.to_string());
+                                writer.write_record(&record)?;
+                            }
+                        }
+                    } else {
+                        record.extend(row.iter().map(|count| count.to_string()));
+                        writer.write_record(&record)?;
+                    }
+                }
+            }
+            writer.flush()?;
+        }
+        Format::Biom | Format::BiomDense => {
+            let (_, rows) = tables.pop().unwrap();
+            let mut observations = Vec::with_capacity(rows.len());
+            let mut counts = Vec::with_capacity(rows.len());
+            for (key, row) in rows {
+                let (id, taxonomy) = match key {
+                    Row::Taxon(taxon) => (key.id(), by_id.lineage(taxon)?.qiime()),
+                    Row::Unassigned => ("unassigned".to_string(), vec!["Unassigned".to_string()]),
+                    Row::Unclassified => {
+                        ("unclassified".to_string(), vec!["Unclassified".to_string()])
+                    }
+                };
+                observations.push(biom::Observation { id, taxonomy });
+                counts.push(row);
+            }
+            let table = biom::Table {
+                observations,
+                samples: columns,
+                counts,
+            };
+            println!("{}", table.to_json(args.format == Format::Biom));
+        }
+    }
+
+    remapping.report();
     Ok(())
 }
 
 fn count_file<T: BufRead>(
-    snapping: &[Option<taxon::TaxonId>],
+    by_id: &taxon::TaxonList,
+    remapping: &taxon::Remapping,
     counts: &mut HashMap<taxon::TaxonId, Vec<usize>>,
     index: usize,
     numfiles: usize,
     file: T,
 ) -> errors::Result<()> {
     for line in file.lines() {
-        if let Ok(taxon) = line?.parse::<taxon::TaxonId>() {
-            counts
-                .entry(snapping[taxon].unwrap_or(0))
-                .or_insert_with(|| vec![0; numfiles])[index] += 1
+        // The taxon ID may be followed by the functional annotations, separated by a tab.
+        if let Ok(taxon) = line?
+            .split('\t')
+            .next()
+            .unwrap_or("")
+            .parse::<taxon::TaxonId>()
+        {
+            let taxon = remapping.remap(taxon);
+            if taxon != 0 {
+                by_id.get_or_unknown(taxon)?;
+            }
+            counts.entry(taxon).or_insert_with(|| vec![0; numfiles])[index] += 1
         }
     }
     Ok(())
 }
+
+// A row of the table: a taxon on the requested rank, the taxa that can't be placed on that rank,
+// or the unclassified taxa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Row {
+    Taxon(taxon::TaxonId),
+    Unassigned,
+    Unclassified,
+}
+
+impl Row {
+    fn id(self) -> String {
+        match self {
+            Row::Taxon(taxon) => taxon.to_string(),
+            Row::Unassigned => String::new(),
+            Row::Unclassified => "0".to_string(),
+        }
+    }
+
+    fn name(self, by_id: &taxon::TaxonList) -> errors::Result<String> {
+        Ok(match self {
+            Row::Taxon(taxon) => by_id.get_or_unknown(taxon)?.name.to_string(),
+            Row::Unassigned => "unassigned".to_string(),
+            Row::Unclassified => "unclassified".to_string(),
+        })
+    }
+
+    fn lineage(self, by_id: &taxon::TaxonList, ranks: &[Rank]) -> errors::Result<Vec<String>> {
+        if let Row::Taxon(taxon) = self {
+            let lineage = by_id.lineage(taxon)?;
+            Ok(ranks
+                .iter()
+                .map(|&rank| lineage[rank].as_ref().map(|t| t.name.clone()))
+                .map(Option::unwrap_or_default)
+                .collect())
+        } else {
+            Ok(vec![String::new(); ranks.len()])
+        }
+    }
+}
+
+/// An output format
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Biom,
+    BiomDense,
+}
+
+static FORMATS: &[&str] = &["csv", "biom", "biom-dense"];
+impl Format {
+    fn variants() -> &'static [&'static str] {
+        FORMATS
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "biom" => Ok(Format::Biom),
+            "biom-dense" => Ok(Format::BiomDense),
+            _ => Err(ErrorKind::ParseFormatError(s.to_string()).into()),
+        }
+    }
+}
+
+error_chain! {
+    errors {
+        /// Unparseable Format
+        ParseFormatError(format: String) {
+            description("Unparseable format")
+            display("Unparseable format: {}", format)
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! The `umgap taxa2krona` command.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::errors;
+use crate::io::compression;
+use crate::io::fasta;
+use crate::rank;
+use crate::rank::Rank;
+use crate::taxon;
+use crate::taxon::Taxon;
+use crate::taxon::TaxonId;
+use crate::taxon::TaxonRef;
+
+// The page of a taxon in the NCBI Taxonomy Browser, without its ID.
+const NCBI_TAXON_URL: &str = "https://www.ncbi.nlm.nih.gov/Taxonomy/Browser/wwwtax.cgi?id=";
+
+#[rustfmt::skip]
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
+/// Writes a Krona chart of a stream of taxon IDs
+///
+/// The `umgap taxa2krona` command summarizes the taxa assigned to a set of reads as input for
+/// [Krona](https://github.com/marbl/Krona), so the results can be visualized where the Unipept
+/// visualizations aren't available.
+///
+/// The input is given in a FASTA format on *standard input*. Each FASTA record contains the taxon
+/// ID assigned to a single read, as output by `umgap taxa2agg`. Reads with taxon ID 0 are counted
+/// as unclassified.
+///
+/// Each taxon is placed in the chart by its lineage on the named ranks, followed by the taxon
+/// itself if it has no rank. By default, a Krona XML file is written to *standard output*, which can
+/// be converted into a chart with `ktImportXML`. The counts, ranks and taxon IDs of all nodes are
+/// included.
+///
+/// The taxonomy to be used is passed as an argument to this command. This is a preprocessed
+/// version of the NCBI taxonomy.
+///
+/// ```sh
+/// $ umgap taxa2krona taxons.tsv < input.fa > chart.xml
+/// $ ktImportXML chart.xml -o chart.html
+/// ```
+///
+/// With `--format text`, the Krona text format is written instead, suitable for `ktImportText`:
+/// a line for each taxon with reads assigned to it, containing the number of reads and the names in
+/// its lineage, separated by tabs.
+///
+/// ```sh
+/// $ umgap taxa2krona --format text taxons.tsv < input.fa
+/// 3	Eukaryota	Metazoa	Chordata	Mammalia	Primates	Hominidae	Homo	Homo sapiens
+/// 1	Eukaryota
+/// 1	unclassified
+/// ```
+///
+/// With the `-r` option, all taxa are first snapped to their ancestor on the given rank. Taxa
+/// without such an ancestor are counted towards the root.
+///
+/// ```sh
+/// $ umgap taxa2krona -r phylum --format text taxons.tsv < input.fa
+/// 3	Eukaryota	Metazoa	Chordata
+/// 1
+/// 1	unclassified
+/// ```
+pub struct TaxaToKrona {
+    /// The rank to snap the taxa to
+    #[structopt(
+        short = "r",
+        long = "rank",
+        possible_values = &Rank::variants()
+    )]
+    pub rank: Option<Rank>,
+
+    /// The output format
+    #[structopt(
+        long = "format",
+        default_value = "xml",
+        possible_values = &Format::variants()
+    )]
+    pub format: Format,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+}
+
+/// Implements the taxa2krona command.
+pub fn taxa2krona(args: TaxaToKrona) -> errors::Result<()> {
+    if args.rank == Some(rank::Rank::NoRank) {
+        return Err(errors::ErrorKind::InvalidInvocation("Snap to an actual rank.".into()).into());
+    }
+
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
+    let snapping = args.rank.map(|rank| {
+        tree.filter_ancestors(|tid| by_id.get(tid).map(|t| t.rank == rank).unwrap_or(false))
+    });
+
+    let mut counts = HashMap::new();
+    let mut unclassified = 0;
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
+        let record = record?;
+        // The taxon ID may be followed by the functional annotations, separated by a tab.
+        let line = record.sequence.first().map(String::as_str).unwrap_or("");
+        let taxon = line
+            .split('\t')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .parse::<TaxonId>()?;
+        let taxon = remapping.remap(taxon);
+        if taxon == 0 {
+            unclassified += 1;
+        } else {
+            by_id.get_or_unknown(taxon)?;
+            let taxon = match &snapping {
+                Some(snapping) => snapping[taxon].unwrap_or(tree.root),
+                None => taxon,
+            };
+            *counts.entry(taxon).or_insert(0) += 1;
+        }
+    }
+
+    let unknown = Taxon::from_static(0, "unclassified", Rank::NoRank, 0, false);
+    let mut chart = Node::new(by_id.get_or_unknown(tree.root)?);
+    for (&id, &count) in counts.iter() {
+        let taxon = by_id.get_or_unknown(id)?;
+        let lineage = by_id.lineage(id)?;
+        let mut path = rank::Rank::ranks()
+            .filter_map(|rank| lineage[rank].as_ref().and_then(|t| by_id.get(t.id)))
+            .collect::<Vec<_>>();
+        if !taxon.rank.is_ranked() && taxon.id != tree.root {
+            path.push(taxon);
+        }
+        chart.insert(&path, count);
+    }
+    if unclassified > 0 {
+        chart.insert(&[TaxonRef::from(&unknown)], unclassified);
+    }
+    chart.sort();
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match args.format {
+        Format::Xml => {
+            writeln!(stdout, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(stdout, "<krona>")?;
+            writeln!(stdout, r#"<attributes magnitude="count">"#)?;
+            writeln!(stdout, r#"<attribute display="Count">count</attribute>"#)?;
+            writeln!(
+                stdout,
+                r#"<attribute display="Rank" mono="true">rank</attribute>"#
+            )?;
+            writeln!(
+                stdout,
+                r#"<attribute display="Taxon ID" mono="true" hrefBase="{}">taxid</attribute>"#,
+                NCBI_TAXON_URL
+            )?;
+            writeln!(stdout, "</attributes>")?;
+            writeln!(stdout, "<datasets><dataset>stdin</dataset></datasets>")?;
+            chart.write_xml(&mut stdout, 0)?;
+            writeln!(stdout, "</krona>")?;
+        }
+        Format::Text => chart.write_text(&mut stdout, &mut Vec::new())?,
+    }
+    remapping.report();
+    Ok(())
+}
+
+// A node in the chart, with the number of reads assigned directly to it and in its whole clade.
+struct Node<'a> {
+    taxon: TaxonRef<'a>,
+    direct: usize,
+    clade: usize,
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn new(taxon: TaxonRef<'a>) -> Self {
+        Node {
+            taxon,
+            direct: 0,
+            clade: 0,
+            children: Vec::new(),
+        }
+    }
+
+    // Adds a count to the node at the end of given path below this node.
+    fn insert(&mut self, path: &[TaxonRef<'a>], count: usize) {
+        self.clade += count;
+        match path.split_first() {
+            None => self.direct += count,
+            Some((first, rest)) => {
+                let position = self.children.iter().position(|c| c.taxon.id == first.id);
+                let index = position.unwrap_or_else(|| {
+                    self.children.push(Node::new(*first));
+                    self.children.len() - 1
+                });
+                self.children[index].insert(rest, count);
+            }
+        }
+    }
+
+    // Orders the children on descending clade count, and then on taxon ID.
+    fn sort(&mut self) {
+        self.children
+            .sort_by(|a, b| b.clade.cmp(&a.clade).then(a.taxon.id.cmp(&b.taxon.id)));
+        for child in self.children.iter_mut() {
+            child.sort();
+        }
+    }
+
+    fn write_xml<W: Write>(&self, out: &mut W, depth: usize) -> errors::Result<()> {
+        let indent = "  ".repeat(depth);
+        writeln!(
+            out,
+            r#"{}<node name="{}">"#,
+            indent,
+            escape(self.taxon.name)
+        )?;
+        writeln!(out, "{}  <count><val>{}</val></count>", indent, self.clade)?;
+        writeln!(
+            out,
+            "{}  <rank><val>{}</val></rank>",
+            indent, self.taxon.rank
+        )?;
+        if self.taxon.id != 0 {
+            writeln!(
+                out,
+                "{}  <taxid><val>{}</val></taxid>",
+                indent, self.taxon.id
+            )?;
+        }
+        for child in self.children.iter() {
+            child.write_xml(out, depth + 1)?;
+        }
+        writeln!(out, "{}</node>", indent)?;
+        Ok(())
+    }
+
+    // Writes the nodes with directly assigned reads, with the names of their lineage below the
+    // root of the chart.
+    fn write_text<W: Write>(&self, out: &mut W, path: &mut Vec<&'a str>) -> errors::Result<()> {
+        if self.direct > 0 {
+            write!(out, "{}", self.direct)?;
+            for name in path.iter() {
+                write!(out, "\t{}", name)?;
+            }
+            writeln!(out)?;
+        }
+        for child in self.children.iter() {
+            path.push(child.taxon.name);
+            child.write_text(out, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+// Escapes the special characters in XML attribute values and text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The Krona input formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The XML format, for `ktImportXML`
+    Xml,
+    /// The text format, for `ktImportText`
+    Text,
+}
+
+static FORMATS: &[&str] = &["xml", "text"];
+
+impl Format {
+    fn variants() -> &'static [&'static str] {
+        FORMATS
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "xml" => Ok(Format::Xml),
+            "text" => Ok(Format::Text),
+            _ => Err(ErrorKind::ParseFormatError(s.to_string()).into()),
+        }
+    }
+}
+
+error_chain! {
+    errors {
+        /// Unparseable format
+        ParseFormatError(format: String) {
+            description("Unparseable format")
+            display("Unparseable format: {}", format)
+        }
+    }
+}
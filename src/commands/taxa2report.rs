@@ -0,0 +1,163 @@
+//! The `umgap taxa2report` command.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::io::fasta;
+use crate::taxon;
+use crate::taxon::TaxonId;
+use crate::tree::Tree;
+
+#[rustfmt::skip]
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
+/// Writes a Kraken-style report of a stream of taxon IDs
+///
+/// The `umgap taxa2report` command summarizes the taxa assigned to a set of reads in the report
+/// format of Kraken, so the results can be used by tools built around Kraken, such as Pavian.
+///
+/// The input is given in a FASTA format on *standard input*. Each FASTA record contains the taxon
+/// ID assigned to a single read, as output by `umgap taxa2agg`. Reads with taxon ID 0 are
+/// unclassified.
+///
+/// The report is written to *standard output*, with a line for each taxon with reads assigned to it
+/// or to one of its descendants, in depth-first order with the largest clades first. Each line
+/// contains the tab-separated fields:
+///
+/// * the percentage of reads in the clade of the taxon,
+/// * the number of reads in the clade of the taxon,
+/// * the number of reads assigned directly to the taxon,
+/// * the rank code of the taxon: `U` (unclassified), `R` (root), `D` (superkingdom), `K`, `P`,
+///   `C`, `O`, `F`, `G` or `S`, or the code of its closest ancestor with such a rank followed by
+///   the number of levels below that ancestor,
+/// * the taxon ID, and
+/// * the name of the taxon, indented with two spaces for each level below root.
+///
+/// The taxonomy to be used is passed as an argument to this command. This is a preprocessed
+/// version of the NCBI taxonomy.
+///
+/// ```sh
+/// $ cat input.fa
+/// >read1
+/// 9606
+/// >read2
+/// 2759
+/// >read3
+/// 0
+/// >read4
+/// 9606
+/// $ umgap taxa2report taxons.tsv < input.fa
+///  25.00	1	1	U	0	unclassified
+///  75.00	3	0	R	1	root
+///  75.00	3	0	R1	131567	  cellular organisms
+///  75.00	3	1	D	2759	    Eukaryota
+///  50.00	2	0	D1	33154	      Opisthokonta
+///  50.00	2	0	K	33208	        Metazoa
+/// ...
+/// ```
+pub struct TaxaToReport {
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+}
+
+/// Implements the taxa2report command.
+pub fn taxa2report(args: TaxaToReport) -> errors::Result<()> {
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
+    let (tree, by_id) = taxon::read_taxonomy(&args.taxon_file)?;
+
+    let mut counts = HashMap::new();
+    let mut unclassified = 0;
+    let mut total = 0;
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
+        let record = record?;
+        // The taxon ID may be followed by the functional annotations, separated by a tab.
+        let line = record.sequence.first().map(String::as_str).unwrap_or("");
+        let taxon = line
+            .split('\t')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .parse::<TaxonId>()?;
+        let taxon = remapping.remap(taxon);
+        total += 1;
+        if taxon == 0 {
+            unclassified += 1;
+        } else {
+            by_id.get_or_unknown(taxon)?;
+            *counts.entry(taxon).or_insert(0) += 1;
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if unclassified > 0 {
+        writeln!(
+            stdout,
+            "{:6.2}\t{}\t{}\tU\t0\tunclassified",
+            percentage(unclassified, total),
+            unclassified,
+            unclassified
+        )?;
+    }
+    if total > unclassified {
+        let direct = Tree::new(tree.root, &by_id.ancestry(), &counts)?;
+        let clade = direct.aggregate(&|a, b| a + b);
+        report(&mut stdout, &by_id, &direct, &clade, ('R', 0), 0, total)?;
+    }
+    remapping.report();
+    Ok(())
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    100.0 * count as f64 / total as f64
+}
+
+// Writes the report line of a taxon and recurses into its children, given the direct and clade
+// counts of its subtree and the rank code of its parent.
+fn report<W: Write>(
+    out: &mut W,
+    taxons: &taxon::TaxonList,
+    direct: &Tree<usize>,
+    clade: &Tree<usize>,
+    parent: (char, usize),
+    depth: usize,
+    total: usize,
+) -> errors::Result<()> {
+    let taxon = taxons.get_or_unknown(direct.root)?;
+    let code = if depth == 0 {
+        ('R', 0)
+    } else {
+        taxon
+            .rank
+            .kraken_code()
+            .map(|code| (code, 0))
+            .unwrap_or((parent.0, parent.1 + 1))
+    };
+    let rank = if code.1 == 0 {
+        code.0.to_string()
+    } else {
+        format!("{}{}", code.0, code.1)
+    };
+    writeln!(
+        out,
+        "{:6.2}\t{}\t{}\t{}\t{}\t{}{}",
+        percentage(clade.value, total),
+        clade.value,
+        direct.value,
+        rank,
+        taxon.id,
+        "  ".repeat(depth),
+        taxon.name
+    )?;
+
+    for (direct, clade) in direct.sorted_children(clade) {
+        report(out, taxons, direct, clade, code, depth + 1, total)?;
+    }
+    Ok(())
+}
@@ -1,25 +1,35 @@
 //! The `umgap taxa2tree` command.
 
 use std::collections::HashMap;
-use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use serde_json::json;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
+use crate::taxon;
 use crate::taxon::TaxonId;
+use crate::tree::Tree;
+
+/// The page in which the tree is embedded.
+const TEMPLATE: &str = include_str!("taxa2tree.html");
 
 #[derive(Debug, StructOpt)]
 #[structopt(verbatim_doc_comment)]
-/// Visualizes a stream of taxon IDs using the Unipept API
+/// Visualizes a stream of taxon IDs as an interactive taxonomic tree
 ///
 /// The `umgap taxa2tree` command, similar to the `unipept taxa2tree` command, takes one or more
-/// taxon IDs and returns a visualized taxonomic tree of these taxa using the Unipept API server.
+/// taxon IDs and returns a visualized taxonomic tree of these taxa.
 ///
 /// The input is given in a FASTA format on *standard input*. Each FASTA record contains one taxon
-/// ID. A HTML file is written to *standard output* containing a visualization of the taxonomic
-/// tree containing all these taxa. With `-u`, a URL to the visualization hosted online is printed
-/// instead.
+/// ID. A HTML file is written to *standard output* containing an interactive visualization of the
+/// taxonomic tree containing all these taxa: a sunburst to zoom into, and a collapsible tree view.
+///
+/// The tree is built locally from the taxonomy passed as an argument to this command, which is a
+/// preprocessed version of the NCBI taxonomy. The resulting file is self-contained and can be
+/// viewed offline.
 ///
 /// ```sh
 /// $ cat input.txt
@@ -27,28 +37,120 @@ use crate::taxon::TaxonId;
 /// 817
 /// 329854
 /// 1099853
-/// $ umgap taxa2tree < input.txt > output.html
+/// $ umgap taxa2tree taxons.tsv < input.txt > output.html
+/// ```
+///
+/// With `--format json` or `--format newick`, the tree itself is written instead, e.g. for
+/// plotting in R. The JSON format nests an object for each taxon, with its `id`, `name`, `rank`,
+/// the number of reads assigned directly to it (`self_count`), the number of reads in its clade
+/// (`count`) and its `children`. In the Newick format, each node is labeled with the name of the
+/// taxon and the number of reads in its clade, separated by a `|`.
+///
+/// ```sh
+/// $ umgap taxa2tree --format newick taxons.tsv < input.txt
+/// ((((...('Homo sapiens|2')'Homo|2'...)'Eukaryota|3')'cellular organisms|3')'root|3';
+/// ```
+///
+/// With `-o`, the tree is built and visualized by the Unipept API server instead, which doesn't
+/// require a taxonomy. With `-u`, a URL to the visualization hosted online is printed instead.
+/// Note that these options send the taxon counts to a third party.
+///
+/// ```sh
+/// $ umgap taxa2tree --online < input.txt > output.html
 /// $ umgap taxa2tree --url < input.txt
 /// https://bl.ocks.org/a686a37e1dcd43dd4ec7d467487bd6a1
 /// ```
 pub struct TaxaToTree {
-    /// Host the result online and return the URL
+    /// The output format
+    #[structopt(
+        short = "f",
+        long = "format",
+        default_value = "html",
+        possible_values = &Format::variants()
+    )]
+    pub format: Format,
+
+    /// Use the Unipept API to build the visualization
+    #[structopt(short = "o", long = "online")]
+    pub online: bool,
+
+    /// Host the result online and return the URL (implies --online)
     #[structopt(short = "u", long = "url")]
     pub url: bool,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: Option<PathBuf>,
 }
 
 /// Implements the taxa2tree command.
 pub fn taxa2tree(args: TaxaToTree) -> errors::Result<()> {
+    let remapping = match &args.taxon_file {
+        Some(taxon_file) => taxon::Remapping::for_taxonomy(taxon_file)?,
+        None => taxon::Remapping::default(),
+    };
     let mut taxa = HashMap::new();
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         let record = record?;
-        let taxon = record.sequence[0].parse::<TaxonId>()?;
-        *taxa.entry(taxon).or_insert(0) += 1;
+        // The taxon ID may be followed by the functional annotations, separated by a tab.
+        let line = record.sequence.first().map(String::as_str).unwrap_or("");
+        let taxon = line
+            .split('\t')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .parse::<TaxonId>()?;
+        *taxa.entry(remapping.remap(taxon)).or_insert(0) += 1;
+    }
+    remapping.report();
+
+    if (args.online || args.url) && args.format != Format::Html {
+        Err(errors::ErrorKind::InvalidInvocation(
+            "Only the HTML visualization can be built online.".into(),
+        )
+        .into())
+    } else if args.online || args.url {
+        online(taxa, args.url)
+    } else if let Some(taxon_file) = args.taxon_file {
+        offline(taxa, taxon_file, args.format)
+    } else {
+        Err(errors::ErrorKind::InvalidInvocation(
+            "A taxonomy is required, unless the visualization is built online.".into(),
+        )
+        .into())
+    }
+}
+
+fn offline(
+    mut taxa: HashMap<TaxonId, usize>,
+    taxon_file: PathBuf,
+    format: Format,
+) -> errors::Result<()> {
+    let (tree, by_id) = taxon::read_taxonomy(taxon_file)?;
+
+    // Unclassified reads aren't part of the tree.
+    taxa.remove(&0);
+    for &taxon in taxa.keys() {
+        by_id.get_or_unknown(taxon)?;
+    }
+
+    let tree = Tree::new(tree.root, &by_id.ancestry(), &taxa)?;
+    match format {
+        Format::Html => {
+            // Escape closing tags, as the JSON is embedded in a script element.
+            let data = tree.to_json(&by_id)?.to_string().replace("</", "<\\/");
+            print!("{}", TEMPLATE.replace("{{DATA}}", &data));
+        }
+        Format::Json => println!("{}", tree.to_json(&by_id)?),
+        Format::Newick => println!("{}", tree.to_newick(&by_id)?),
     }
+    Ok(())
+}
 
+fn online(taxa: HashMap<TaxonId, usize>, url: bool) -> errors::Result<()> {
     let json = json!({
         "counts": taxa,
-        "link": args.url.to_string(),
+        "link": url.to_string(),
     });
 
     let res = attohttpc::post("http://api.unipept.ugent.be/api/v1/taxa2tree")
@@ -57,7 +159,7 @@ pub fn taxa2tree(args: TaxaToTree) -> errors::Result<()> {
         .send()
         .map_err(|err| err.to_string())?;
 
-    if args.url {
+    if url {
         let jsonres: serde_json::Value = res.json().map_err(|err| err.to_string())?;
         let gist = jsonres
             .get("gist")
@@ -74,3 +176,42 @@ pub fn taxa2tree(args: TaxaToTree) -> errors::Result<()> {
 
     Ok(())
 }
+
+/// An output format
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Json,
+    Newick,
+}
+
+static FORMATS: &[&str] = &["html", "json", "newick"];
+impl Format {
+    fn variants() -> &'static [&'static str] {
+        FORMATS
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "html" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            "newick" => Ok(Format::Newick),
+            _ => Err(ErrorKind::ParseFormatError(s.to_string()).into()),
+        }
+    }
+}
+
+error_chain! {
+    errors {
+        /// Unparseable Format
+        ParseFormatError(format: String) {
+            description("Unparseable format")
+            display("Unparseable format: {}", format)
+        }
+    }
+}
@@ -2,11 +2,16 @@
 
 use std::io;
 use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde_json::json;
 
 use crate::errors;
-use crate::rank;
+use crate::io::compression;
+use crate::rank::Rank;
 use crate::taxon;
 
 #[rustfmt::skip]
@@ -61,14 +66,38 @@ use crate::taxon;
 /// 1883	Streptomyces	genus
 /// ```
 ///     
-/// The `-a` flag can be used to request a complete ranked lineage.
+/// The `-a` flag can be used to request a complete ranked lineage. Taxa without a rank and clades
+/// are left out of it.
 ///
 /// ```sh
 /// $ cat input3.fa
 /// 888268
 /// $ umgap taxonomy -a taxons.tsv < input3.fa
-/// taxon_id	taxon_name	taxon_rank	superkingdom_id	superkingdom_name	kingdom_id	kingdom_name	subkingdom_id	subkingdom_name	superphylum_id	superphylum_name	phylum_id	phylum_name	subphylum_id	subphylum_name	superclass_id	superclass_name	class_id	class_name	subclass_id	subclass_name	infraclass_id	infraclass_name	superorder_id	superorder_name	order_id	order_name	suborder_id	suborder_name	infraorder_id	infraorder_name	parvorder_id	parvorder_name	superfamily_id	superfamily_name	family_id	family_name	subfamily_id	subfamily_name	tribe_id	tribe_name	subtribe_id	subtribe_name	genus_id	genus_name	subgenus_id	subgenus_name	species_group_id	species_group_name	species_subgroup_id	species_subgroup_name	species_id	species_name	subspecies_id	subspecies_name	varietas_id	varietas_name	forma_id	forma_name
-/// 888268	Dichanthelium oligosanthes	species	2759	Eukaryota	33090	Viridiplantae					35493	Streptophyta	131221	Streptophytina			3398	Magnoliopsida	1437197	Petrosaviidae					38820	Poales									4479	Poaceae	147369	Panicoideae	147428	Paniceae	1648011	Dichantheliinae	161620	Dichanthelium							888268	Dichanthelium oligosanthes						
+/// taxon_id	taxon_name	taxon_rank	realm_id	realm_name	domain_id	domain_name	superkingdom_id	superkingdom_name	kingdom_id	kingdom_name	subkingdom_id	subkingdom_name	superphylum_id	superphylum_name	phylum_id	phylum_name	subphylum_id	subphylum_name	superclass_id	superclass_name	class_id	class_name	subclass_id	subclass_name	infraclass_id	infraclass_name	cohort_id	cohort_name	subcohort_id	subcohort_name	superorder_id	superorder_name	order_id	order_name	suborder_id	suborder_name	infraorder_id	infraorder_name	parvorder_id	parvorder_name	superfamily_id	superfamily_name	family_id	family_name	subfamily_id	subfamily_name	tribe_id	tribe_name	subtribe_id	subtribe_name	genus_id	genus_name	subgenus_id	subgenus_name	section_id	section_name	subsection_id	subsection_name	series_id	series_name	species_group_id	species_group_name	species_subgroup_id	species_subgroup_name	species_id	species_name	subspecies_id	subspecies_name	varietas_id	varietas_name	subvariety_id	subvariety_name	forma_id	forma_name	serogroup_id	serogroup_name	serotype_id	serotype_name	biotype_id	biotype_name	pathogroup_id	pathogroup_name	genotype_id	genotype_name	morph_id	morph_name	strain_id	strain_name	isolate_id	isolate_name
+/// 888268	Dichanthelium oligosanthes	species					2759	Eukaryota	33090	Viridiplantae					35493	Streptophyta	131221	Streptophytina			3398	Magnoliopsida	1437197	Petrosaviidae									38820	Poales									4479	Poaceae	147369	Panicoideae	147428	Paniceae	1648011	Dichantheliinae	161620	Dichanthelium													888268	Dichanthelium oligosanthes																								
+/// ```
+///
+/// The `-l` option writes the lineage in a more compact format, in a single `lineage` column. With
+/// `-l qiime`, the names on the main ranks are prefixed with `k__`, `p__`, ... up to `s__`, as in
+/// QIIME and MetaPhlAn. With `-l names` or `-l ids`, the names or IDs of the ranked taxa in the
+/// lineage are joined by semicolons. The `-a` flag is the same as `-l columns`.
+///
+/// ```sh
+/// $ umgap taxonomy -H -l qiime taxons.tsv < input3.fa
+/// 888268	Dichanthelium oligosanthes	species	k__Eukaryota;p__Streptophyta;c__Magnoliopsida;o__Poales;f__Poaceae;g__Dichanthelium;s__Dichanthelium oligosanthes
+/// $ umgap taxonomy -H -l ids taxons.tsv < input3.fa
+/// 888268	Dichanthelium oligosanthes	species	2759;33090;35493;131221;3398;1437197;38820;4479;147369;147428;1648011;161620;888268
+/// ```
+///
+/// The ranks to include in the lineage can be chosen with `-r`, except for `-l qiime`. With `-l
+/// json`, each taxon is written as a JSON object on a separate line, without a TSV header.
+///
+/// ```sh
+/// $ umgap taxonomy -l names -r superkingdom -r phylum -r family taxons.tsv < input3.fa
+/// taxon_id	taxon_name	taxon_rank	lineage
+/// 888268	Dichanthelium oligosanthes	species	Eukaryota;Streptophyta;Poaceae
+/// $ umgap taxonomy -l json -r superkingdom -r genus taxons.tsv < input3.fa
+/// {"lineage":[{"id":2759,"name":"Eukaryota","rank":"superkingdom"},{"id":161620,"name":"Dichanthelium","rank":"genus"}],"taxon_id":888268,"taxon_name":"Dichanthelium oligosanthes","taxon_rank":"species"}
 /// ```
 pub struct Taxonomy {
     /// An NCBI taxonomy TSV-file as processed by Unipept
@@ -80,6 +109,22 @@ pub struct Taxonomy {
     #[structopt(short = "a", long = "all")]
     pub all_ranks: bool,
 
+    /// Show the lineage of a taxon in this format
+    #[structopt(
+        short = "l",
+        long = "lineage",
+        possible_values = &LineageFormat::variants()
+    )]
+    pub lineage: Option<LineageFormat>,
+
+    /// Show only these ranks in the lineage (allow multiple times)
+    #[structopt(
+        short = "r",
+        long = "ranks",
+        possible_values = &Rank::variants()
+    )]
+    pub ranks: Vec<Rank>,
+
     /// Do not output the TSV header
     #[structopt(short = "H", long = "no-header")]
     pub no_header: bool,
@@ -87,45 +132,156 @@ pub struct Taxonomy {
 
 /// Implements the taxonomy command.
 pub fn taxonomy(args: Taxonomy) -> errors::Result<()> {
+    let format = match (args.all_ranks, args.lineage) {
+        (true, Some(format)) if format != LineageFormat::Columns => {
+            return Err(errors::ErrorKind::InvalidInvocation(
+                "The -a flag can't be combined with another lineage format.".into(),
+            )
+            .into());
+        }
+        (true, _) => Some(LineageFormat::Columns),
+        (false, format) => format,
+    };
+    if !args.ranks.is_empty() && !matches!(format, Some(f) if f != LineageFormat::Qiime) {
+        return Err(errors::ErrorKind::InvalidInvocation(
+            "Ranks can only be chosen for a lineage in another format than qiime.".into(),
+        )
+        .into());
+    }
+    let ranks = if args.ranks.is_empty() {
+        Rank::ranks().collect::<Vec<_>>()
+    } else {
+        let mut ranks = args.ranks.clone();
+        ranks.sort();
+        ranks.dedup();
+        ranks
+    };
+
     let taxons = taxon::read_taxa_file(&args.taxon_file)?;
-    let by_id = taxon::TaxonList::new(taxons);
+    let remapping = taxon::Remapping::for_taxonomy(&args.taxon_file)?;
+    let by_id = taxon::TaxonList::new(taxons)?;
 
-    let stdin = io::stdin();
+    let stdin = BufReader::new(compression::stdin()?);
     let stdout = io::stdout();
     let mut handle = stdout.lock();
 
-    if !args.no_header {
+    if !args.no_header && format != Some(LineageFormat::Json) {
         write!(handle, "taxon_id	taxon_name	taxon_rank")?;
-        if args.all_ranks {
-            for rank in rank::Rank::ranks() {
-                let rank_name = rank.to_string().replace(" ", "_");
-                write!(handle, "	{}_id	{}_name", rank_name, rank_name)?;
+        match format {
+            Some(LineageFormat::Columns) => {
+                for rank in ranks.iter() {
+                    let rank_name = rank.to_string().replace(" ", "_");
+                    write!(handle, "	{}_id	{}_name", rank_name, rank_name)?;
+                }
             }
+            Some(_) => write!(handle, "	lineage")?,
+            None => {}
         }
         writeln!(handle)?;
     }
 
-    for line in stdin.lock().lines() {
+    for line in stdin.lines() {
         let line = line?;
         if line.starts_with('>') {
             writeln!(handle, "{}", line)?;
-        } else {
-            let id = line.parse::<taxon::TaxonId>()?;
-            // Map to root if id not found
-            let taxon = by_id.get_or_unknown(id)?;
-            write!(handle, "{}	{}	{}", taxon.id, taxon.name, taxon.rank)?;
-            if args.all_ranks {
-                let lineage = by_id.lineage(id)?;
-                for rank in rank::Rank::ranks() {
-                    if let Some(l_taxon) = &lineage[rank] {
-                        write!(handle, "	{}	{}", l_taxon.id, l_taxon.name)?;
-                    } else {
-                        write!(handle, "		")?;
-                    }
-                }
+            continue;
+        }
+        let id = remapping.remap(line.parse::<taxon::TaxonId>()?);
+        // Map to root if id not found
+        let taxon = by_id.get_or_unknown(id)?;
+        let format = match format {
+            Some(format) => format,
+            None => {
+                writeln!(handle, "{}	{}	{}", taxon.id, taxon.name, taxon.rank)?;
+                continue;
+            }
+        };
+
+        let lineage = by_id.lineage(id)?;
+        let ranked = ranks
+            .iter()
+            .filter_map(|&rank| lineage[rank].as_ref())
+            .collect::<Vec<_>>();
+        if format == LineageFormat::Json {
+            let ranked = ranked
+                .iter()
+                .map(|t| json!({"rank": t.rank.to_string(), "id": t.id, "name": t.name}))
+                .collect::<Vec<_>>();
+            let object = json!({
+                "taxon_id": taxon.id,
+                "taxon_name": taxon.name,
+                "taxon_rank": taxon.rank.to_string(),
+                "lineage": ranked,
+            });
+            writeln!(handle, "{}", object)?;
+            continue;
+        }
+
+        write!(handle, "{}	{}	{}	", taxon.id, taxon.name, taxon.rank)?;
+        match format {
+            LineageFormat::Columns => {
+                let columns = ranks.iter().map(|&rank| match &lineage[rank] {
+                    Some(l_taxon) => format!("{}	{}", l_taxon.id, l_taxon.name),
+                    None => "	".to_string(),
+                });
+                write!(handle, "{}", columns.collect::<Vec<_>>().join("	"))?;
+            }
+            LineageFormat::Qiime => write!(handle, "{}", lineage.qiime().join(";"))?,
+            LineageFormat::Names => {
+                let names = ranked.iter().map(|t| t.name.as_str());
+                write!(handle, "{}", names.collect::<Vec<_>>().join(";"))?;
             }
-            writeln!(handle)?;
+            LineageFormat::Ids => {
+                let ids = ranked.iter().map(|t| t.id.to_string());
+                write!(handle, "{}", ids.collect::<Vec<_>>().join(";"))?;
+            }
+            LineageFormat::Json => {}
         }
+        writeln!(handle)?;
     }
+    remapping.report();
     Ok(())
 }
+
+/// A format to show a lineage in
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineageFormat {
+    Columns,
+    Qiime,
+    Names,
+    Ids,
+    Json,
+}
+
+static LINEAGE_FORMATS: &[&str] = &["columns", "qiime", "names", "ids", "json"];
+impl LineageFormat {
+    fn variants() -> &'static [&'static str] {
+        LINEAGE_FORMATS
+    }
+}
+
+impl FromStr for LineageFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "columns" => Ok(LineageFormat::Columns),
+            "qiime" => Ok(LineageFormat::Qiime),
+            "names" => Ok(LineageFormat::Names),
+            "ids" => Ok(LineageFormat::Ids),
+            "json" => Ok(LineageFormat::Json),
+            _ => Err(ErrorKind::ParseLineageFormatError(s.to_string()).into()),
+        }
+    }
+}
+
+error_chain! {
+    errors {
+        /// Unparseable LineageFormat
+        ParseLineageFormatError(format: String) {
+            description("Unparseable lineage format")
+            display("Unparseable lineage format: {}", format)
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! The `umgap taxonomydiff` command.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::taxon;
+use crate::taxon::Change;
+
+#[rustfmt::skip]
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+#[allow(clippy::tabs_in_doc_comments)]
+/// Lists the differences between two releases of a taxonomy
+///
+/// The `umgap taxonomydiff` command compares an old and a new taxonomy, to check what changes before
+/// rolling out the new one. The taxa are matched on their ID. A taxon can be added or removed, and
+/// the name, rank, parent and validity of the taxa present in both are compared.
+///
+/// The two taxonomies are passed as arguments to this command. Each change is written to *standard
+/// output* on a separate line, with the taxon ID, the kind of change and the old and new value,
+/// separated by tabs. The old name of an added taxon and the new name of a removed taxon are
+/// empty. A summary with the number of changes of each kind is written to *standard error*.
+///
+/// ```sh
+/// $ umgap taxonomydiff 2020-04-taxons.tsv 2025-04-taxons.tsv
+/// taxon_id	change	old	new
+/// 2	reranked	superkingdom	domain
+/// 1224	renamed	Proteobacteria	Pseudomonadota
+/// 1239	renamed	Firmicutes	Bacillota
+/// 2759	reranked	superkingdom	domain
+/// 2732396	added		Orthornavirae
+/// ...
+///    2381 added
+///     742 removed
+///     518 renamed
+///       3 reranked
+///     307 reparented
+///      12 validity
+/// ```
+///
+/// The `-H` flag can be used to suppress the TSV header.
+pub struct TaxonomyDiff {
+    /// Do not output the TSV header
+    #[structopt(short = "H", long = "no-header")]
+    pub no_header: bool,
+
+    /// The old taxonomy TSV-file
+    #[structopt(parse(from_os_str))]
+    pub old_file: PathBuf,
+
+    /// The new taxonomy TSV-file
+    #[structopt(parse(from_os_str))]
+    pub new_file: PathBuf,
+}
+
+/// Implements the taxonomydiff command.
+pub fn taxonomydiff(args: TaxonomyDiff) -> errors::Result<()> {
+    let old = taxon::TaxonList::new(taxon::read_taxa_file(&args.old_file)?)?;
+    let new = taxon::TaxonList::new(taxon::read_taxa_file(&args.new_file)?)?;
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    if !args.no_header {
+        writeln!(handle, "taxon_id\tchange\told\tnew")?;
+    }
+    let mut counts = HashMap::new();
+    for (id, change) in old.diff(&new) {
+        *counts.entry(change.kind()).or_insert(0) += 1;
+        writeln!(handle, "{}\t{}", id, change)?;
+    }
+    handle.flush()?;
+
+    for kind in Change::KINDS {
+        eprintln!("{:>7} {}", counts.get(kind).unwrap_or(&0), kind);
+    }
+    Ok(())
+}
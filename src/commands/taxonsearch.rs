@@ -0,0 +1,97 @@
+//! The `umgap taxonsearch` command.
+
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::io::compression;
+use crate::names::Matching;
+use crate::names::NameIndex;
+use crate::taxon;
+
+#[derive(Debug, StructOpt)]
+#[structopt(verbatim_doc_comment)]
+/// Looks up taxa by their name
+///
+/// The `umgap taxonsearch` command searches the taxa of which the name matches a query. By default,
+/// the names are matched ignoring case. With the `-m` option, the names can be matched exactly, on
+/// a prefix of the name, or fuzzily, allowing up to a number of characters to differ (see `-d`).
+///
+/// The queries are given as arguments, or else on *standard input*, one on each line. For each
+/// matching name, the query, the taxon ID, its name and rank, and the matching name are written to
+/// *standard output*, separated by tabs. The matches are ordered from the closest to the farthest
+/// one, with the scientific names first.
+///
+/// The taxonomy to be used is passed as an argument to this command. If the taxonomy was built with
+/// `umgap buildtaxonomy -s`, the synonyms and common names of the taxa are searched as well.
+///
+/// ```sh
+/// $ umgap taxonsearch taxons.tsv "bacteroides fragilis"
+/// bacteroides fragilis	817	Bacteroides fragilis	species	Bacteroides fragilis
+/// $ umgap taxonsearch -m fuzzy taxons.tsv "Bacterodes fragilis"
+/// Bacterodes fragilis	817	Bacteroides fragilis	species	Bacteroides fragilis
+/// $ umgap taxonsearch -m prefix -n 2 taxons.tsv "Escherichia col"
+/// Escherichia col	562	Escherichia coli	species	Escherichia coli
+/// Escherichia col	1048689	Escherichia coli 0.1288	no rank	Escherichia coli 0.1288
+/// ```
+#[rustfmt::skip]
+#[allow(clippy::tabs_in_doc_comments)]
+pub struct TaxonSearch {
+    /// How to match the names
+    #[structopt(
+        short = "m",
+        long = "matching",
+        default_value = "insensitive",
+        possible_values = &Matching::variants()
+    )]
+    pub matching: Matching,
+
+    /// The maximum number of differing characters for fuzzy matching
+    #[structopt(short = "d", long = "distance", default_value = "2")]
+    pub distance: usize,
+
+    /// Report at most this many matches for each query
+    #[structopt(short = "n", long = "number")]
+    pub number: Option<usize>,
+
+    /// An NCBI taxonomy TSV-file as processed by Unipept
+    #[structopt(parse(from_os_str))]
+    pub taxon_file: PathBuf,
+
+    /// The names to search for
+    pub queries: Vec<String>,
+}
+
+/// Implements the taxonsearch command.
+pub fn taxonsearch(args: TaxonSearch) -> errors::Result<()> {
+    let by_id = taxon::TaxonList::new(taxon::read_taxa_file(&args.taxon_file)?)?;
+    let index = NameIndex::for_taxonomy(&args.taxon_file, &by_id)?;
+
+    let queries = if args.queries.is_empty() {
+        BufReader::new(compression::stdin()?)
+            .lines()
+            .collect::<io::Result<Vec<_>>>()?
+    } else {
+        args.queries
+    };
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    for query in queries.iter().filter(|query| !query.trim().is_empty()) {
+        let matches = index.search(query, args.matching, args.distance);
+        let number = args.number.unwrap_or(matches.len());
+        for found in matches.into_iter().take(number) {
+            let taxon = by_id.get_or_unknown(found.taxon)?;
+            writeln!(
+                handle,
+                "{}\t{}\t{}\t{}\t{}",
+                query, taxon.id, taxon.name, taxon.rank, found.name
+            )?;
+        }
+    }
+    handle.flush()?;
+    Ok(())
+}
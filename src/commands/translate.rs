@@ -7,6 +7,7 @@ use std::str::FromStr;
 use crate::dna::translation::TranslationTable;
 use crate::dna::Strand;
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -111,7 +112,7 @@ pub fn translate(args: Translate) -> errors::Result<()> {
             })
             .collect::<Vec<(Frame, usize, bool)>>();
 
-        for record in fasta::Reader::new(io::stdin(), true).records() {
+        for record in fasta::Reader::new(compression::stdin()?, true).records() {
             let fasta::Record { header, sequence } = record?;
 
             let forward = Strand::from(&sequence);
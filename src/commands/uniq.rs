@@ -3,6 +3,7 @@
 use std::io;
 
 use crate::errors;
+use crate::io::compression;
 use crate::io::fasta;
 
 #[derive(Debug, StructOpt)]
@@ -56,7 +57,7 @@ pub struct Uniq {
 pub fn uniq(args: Uniq) -> errors::Result<()> {
     let mut last = None::<fasta::Record>;
     let mut writer = fasta::Writer::new(io::stdout(), &args.separator, args.wrap);
-    for record in fasta::Reader::new(io::stdin(), false).records() {
+    for record in fasta::Reader::new(compression::stdin()?, false).records() {
         let mut record = record?;
         if let Some(ref delimiter) = args.delimiter {
             record.header.truncate(
@@ -0,0 +1,298 @@
+//! Defines normalisations and diversity measures to compare the taxon counts of samples.
+//!
+//! The abundances of a sample are given as a slice, with a value for each taxon of a list shared by
+//! all samples that are compared.
+
+use std::collections::HashMap;
+
+use crate::taxon;
+use crate::taxon::TaxonId;
+use crate::taxon::TaxonList;
+use crate::taxon::TaxonTree;
+use crate::tree::Tree;
+
+/// Converts counts into relative abundances, which sum to 1.
+pub fn relative(counts: &[f64]) -> Vec<f64> {
+    let total = counts.iter().sum::<f64>();
+    counts
+        .iter()
+        .map(|&count| if total > 0.0 { count / total } else { 0.0 })
+        .collect()
+}
+
+/// Applies the centred log-ratio transformation to counts: the logarithm of each count (plus the
+/// pseudocount, to avoid the logarithm of zero), minus the mean of these logarithms.
+pub fn clr(counts: &[f64], pseudocount: f64) -> Vec<f64> {
+    let logs = counts
+        .iter()
+        .map(|&count| (count + pseudocount).ln())
+        .collect::<Vec<_>>();
+    let mean = logs.iter().sum::<f64>() / logs.len() as f64;
+    logs.into_iter().map(|log| log - mean).collect()
+}
+
+/// Subsamples counts without replacement to the given depth, which should be at most their total.
+pub fn rarefy(counts: &[usize], depth: usize, random: &mut Random) -> Vec<usize> {
+    // Selection sampling (Knuth's algorithm S): each of the remaining items is selected with the
+    // probability that it's one of the items still needed.
+    let mut remaining = counts.iter().sum::<usize>();
+    let mut needed = depth;
+    counts
+        .iter()
+        .map(|&count| {
+            let mut selected = 0;
+            for _ in 0..count {
+                if random.below(remaining as u64) < needed as u64 {
+                    selected += 1;
+                    needed -= 1;
+                }
+                remaining -= 1;
+            }
+            selected
+        })
+        .collect()
+}
+
+/// The number of taxa present in a sample.
+pub fn richness(abundances: &[f64]) -> usize {
+    abundances.iter().filter(|&&a| a > 0.0).count()
+}
+
+/// The Shannon index of a sample (using the natural logarithm).
+pub fn shannon(abundances: &[f64]) -> f64 {
+    -relative(abundances)
+        .into_iter()
+        .filter(|&p| p > 0.0)
+        .map(|p| p * p.ln())
+        .sum::<f64>()
+}
+
+/// The Gini-Simpson index of a sample: the probability that two randomly chosen individuals
+/// (with replacement) belong to different taxa.
+pub fn simpson(abundances: &[f64]) -> f64 {
+    if abundances.iter().all(|&a| a <= 0.0) {
+        return 0.0;
+    }
+    1.0 - relative(abundances).into_iter().map(|p| p * p).sum::<f64>()
+}
+
+/// The Bray-Curtis dissimilarity between two samples.
+pub fn bray_curtis(a: &[f64], b: &[f64]) -> f64 {
+    let difference = a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f64>();
+    let total = a.iter().zip(b).map(|(x, y)| x + y).sum::<f64>();
+    if total > 0.0 {
+        difference / total
+    } else {
+        0.0
+    }
+}
+
+/// The Jaccard distance between the sets of taxa present in two samples.
+pub fn jaccard(a: &[f64], b: &[f64]) -> f64 {
+    let union = a
+        .iter()
+        .zip(b)
+        .filter(|&(&x, &y)| x > 0.0 || y > 0.0)
+        .count();
+    let intersection = a
+        .iter()
+        .zip(b)
+        .filter(|&(&x, &y)| x > 0.0 && y > 0.0)
+        .count();
+    if union > 0 {
+        1.0 - intersection as f64 / union as f64
+    } else {
+        0.0
+    }
+}
+
+/// The Euclidean distance between two samples. On CLR-transformed counts, this is the Aitchison
+/// distance.
+pub fn euclidean(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Calculates UniFrac distances between samples over a taxonomy, in which every taxon is a branch
+/// of length 1 to its parent.
+pub struct UniFrac {
+    root: TaxonId,
+    parents: Vec<Option<TaxonId>>,
+}
+
+impl UniFrac {
+    /// Constructs a UniFrac calculator for a given taxon tree.
+    pub fn new(tree: &TaxonTree, taxonomy: &TaxonList) -> Self {
+        UniFrac {
+            root: tree.root,
+            parents: taxonomy.ancestry(),
+        }
+    }
+
+    /// The unweighted UniFrac distance: the fraction of the branches leading to the taxa of
+    /// either sample which lead to the taxa of only one of them.
+    pub fn unweighted(&self, taxa: &[TaxonId], a: &[f64], b: &[f64]) -> taxon::Result<f64> {
+        let clade = self.tree(taxa, a, b)?.aggregate(&sum);
+        let (mut total, mut unique) = (0, 0);
+        branches(&clade, &mut total, &mut unique);
+        Ok(if total > 0 {
+            unique as f64 / total as f64
+        } else {
+            0.0
+        })
+    }
+
+    /// The normalised weighted UniFrac distance: the differences in relative abundance below each
+    /// branch, normalised to values between 0 and 1.
+    pub fn weighted(&self, taxa: &[TaxonId], a: &[f64], b: &[f64]) -> taxon::Result<f64> {
+        let direct = self.tree(taxa, a, b)?;
+        let clade = direct.aggregate(&sum);
+        let (mut difference, mut total) = (0.0, 0.0);
+        weights(&direct, &clade, 0, &mut difference, &mut total);
+        Ok(if total > 0.0 { difference / total } else { 0.0 })
+    }
+
+    // Builds the tree of the relative abundances of both samples.
+    fn tree(&self, taxa: &[TaxonId], a: &[f64], b: &[f64]) -> taxon::Result<Tree<(f64, f64)>> {
+        let abundances: HashMap<TaxonId, (f64, f64)> = taxa
+            .iter()
+            .zip(relative(a).into_iter().zip(relative(b)))
+            .filter(|&(_, (x, y))| x > 0.0 || y > 0.0)
+            .map(|(&taxon, abundances)| (taxon, abundances))
+            .collect();
+        Tree::new(self.root, &self.parents, &abundances)
+    }
+}
+
+fn sum((a, b): (f64, f64), (x, y): (f64, f64)) -> (f64, f64) {
+    (a + x, b + y)
+}
+
+// Counts the branches below the root of an aggregated tree, and those present in only one sample.
+fn branches(clade: &Tree<(f64, f64)>, total: &mut usize, unique: &mut usize) {
+    for child in clade.children.iter() {
+        let (a, b) = child.value;
+        *total += 1;
+        if (a > 0.0) != (b > 0.0) {
+            *unique += 1;
+        }
+        branches(child, total, unique);
+    }
+}
+
+// Sums the differences in abundance over all branches, and the abundances of both samples weighted
+// by their distance to the root.
+fn weights(
+    direct: &Tree<(f64, f64)>,
+    clade: &Tree<(f64, f64)>,
+    depth: usize,
+    difference: &mut f64,
+    total: &mut f64,
+) {
+    let (a, b) = direct.value;
+    *total += depth as f64 * (a + b);
+    for (direct, clade) in direct.children.iter().zip(clade.children.iter()) {
+        let (a, b) = clade.value;
+        *difference += (a - b).abs();
+        weights(direct, clade, depth + 1, difference, total);
+    }
+}
+
+/// A small pseudorandom number generator (SplitMix64), so subsamples can be reproduced with the
+/// same seed.
+pub struct Random(u64);
+
+impl Random {
+    /// Creates a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    /// Returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in `[0, bound)`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(bound)) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn close(expected: f64, actual: f64) -> bool {
+        (expected - actual).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_normalisations() {
+        assert_eq!(vec![0.25, 0.75, 0.0], relative(&[1.0, 3.0, 0.0]));
+        assert_eq!(vec![0.0, 0.0], relative(&[0.0, 0.0]));
+        let transformed = clr(&[1.0, 3.0], 1.0);
+        assert!(close(-0.5 * 2f64.ln(), transformed[0]));
+        assert!(close(0.5 * 2f64.ln(), transformed[1]));
+    }
+
+    #[test]
+    fn test_rarefy() {
+        let counts = vec![10, 0, 5, 20];
+        let mut random = Random::new(42);
+        for depth in 0..=35 {
+            let rarefied = rarefy(&counts, depth, &mut random);
+            assert_eq!(depth, rarefied.iter().sum::<usize>());
+            assert!(rarefied.iter().zip(&counts).all(|(r, c)| r <= c));
+        }
+        assert_eq!(counts, rarefy(&counts, 35, &mut random));
+        assert_eq!(
+            rarefy(&counts, 12, &mut Random::new(7)),
+            rarefy(&counts, 12, &mut Random::new(7))
+        );
+    }
+
+    #[test]
+    fn test_alpha() {
+        assert_eq!(2, richness(&[1.0, 0.0, 3.0]));
+        assert!(close(2f64.ln(), shannon(&[2.0, 2.0, 0.0])));
+        assert!(close(0.0, shannon(&[5.0])));
+        assert!(close(0.5, simpson(&[2.0, 2.0])));
+        assert!(close(0.0, simpson(&[0.0, 0.0])));
+    }
+
+    #[test]
+    fn test_beta() {
+        let a = [1.0, 2.0, 0.0];
+        let b = [0.0, 2.0, 2.0];
+        assert!(close(3.0 / 7.0, bray_curtis(&a, &b)));
+        assert!(close(2.0 / 3.0, jaccard(&a, &b)));
+        assert!(close(5f64.sqrt(), euclidean(&a, &b)));
+        assert!(close(0.0, bray_curtis(&a, &a)));
+        assert!(close(0.0, jaccard(&a, &a)));
+    }
+
+    #[test]
+    fn test_unifrac() {
+        let unifrac = UniFrac::new(&fixtures::tree(), &fixtures::by_id());
+        let taxa = [2, 10239, 185751, 185752];
+        let a = [1.0, 0.0, 0.0, 0.0];
+        let b = [0.0, 1.0, 0.0, 0.0];
+        assert!(close(1.0, unifrac.unweighted(&taxa, &a, &b).unwrap()));
+        assert!(close(1.0, unifrac.weighted(&taxa, &a, &b).unwrap()));
+        let a = [0.0, 0.0, 1.0, 0.0];
+        let b = [0.0, 0.0, 0.0, 1.0];
+        assert!(close(2.0 / 3.0, unifrac.unweighted(&taxa, &a, &b).unwrap()));
+        assert!(close(0.5, unifrac.weighted(&taxa, &a, &b).unwrap()));
+        assert!(close(0.0, unifrac.unweighted(&taxa, &a, &a).unwrap()));
+        assert!(close(0.0, unifrac.weighted(&taxa, &a, &a).unwrap()));
+    }
+}
@@ -0,0 +1,196 @@
+//! Overlap-based merging of paired-end reads.
+
+use crate::dna::Nucleotide;
+
+/// Merges the two reads of a pair into the fragment they were sequenced from, by overlapping read 1
+/// with the reverse complement of read 2.
+#[derive(Debug, Clone)]
+pub struct Merger {
+    /// The shortest overlap between both reads to consider.
+    pub min_overlap: usize,
+    /// The maximal fraction of mismatching bases in the overlap.
+    pub max_mismatches: f32,
+}
+
+/// A fragment merged from a pair of reads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged {
+    /// The sequence of the fragment.
+    pub sequence: Vec<u8>,
+    /// The Phred scores of the fragment.
+    pub scores: Vec<u8>,
+    /// The length of the overlap between both reads.
+    pub overlap: usize,
+    /// The number of mismatching bases in the overlap.
+    pub mismatches: usize,
+}
+
+impl Merger {
+    /// Merges read 1 with read 2 (as sequenced, so not yet reverse complemented), given the Phred
+    /// scores of their bases. Returns `None` if the reads don't overlap.
+    ///
+    /// The best overlap is the one with the most matching minus mismatching bases. Read 1 is taken
+    /// as the start of the fragment and read 2 as its end, so parts of a read extending beyond
+    /// its mate (reading through into the adapter) are dropped. Mismatches are resolved by taking
+    /// the base with the highest score, with the difference of both scores as its new score.
+    pub fn merge(
+        &self,
+        seq1: &[u8],
+        scores1: &[u8],
+        seq2: &[u8],
+        scores2: &[u8],
+    ) -> Option<Merged> {
+        let seq2 = reverse_complement(seq2);
+        let scores2 = scores2.iter().rev().cloned().collect::<Vec<u8>>();
+        let n = seq1.len().min(scores1.len()) as isize;
+        let m = seq2.len().min(scores2.len()) as isize;
+        let min_overlap = self.min_overlap.max(1) as isize;
+
+        // The offset of the reverse complement of read 2 relative to read 1.
+        let mut best: Option<(isize, usize, usize)> = None;
+        for offset in (min_overlap - m)..=(n - min_overlap) {
+            let start = offset.max(0);
+            let end = n.min(offset + m);
+            let overlap = (end - start) as usize;
+            // A mate shorter than the minimal overlap, or an empty one, overlaps less at any offset
+            if (overlap as isize) < min_overlap {
+                continue;
+            }
+            let mismatches = (start..end)
+                .filter(|&i| !same(seq1[i as usize], seq2[(i - offset) as usize]))
+                .count();
+            if mismatches as f32 > overlap as f32 * self.max_mismatches {
+                continue;
+            }
+            let score = overlap as isize - 2 * mismatches as isize;
+            let better = match best {
+                None => true,
+                Some((_, o, mm)) => score > o as isize - 2 * mm as isize,
+            };
+            if better {
+                best = Some((offset, overlap, mismatches));
+            }
+        }
+
+        let (offset, overlap, mismatches) = best?;
+        let length = (offset + m) as usize;
+        let mut sequence = Vec::with_capacity(length);
+        let mut scores = Vec::with_capacity(length);
+        for i in 0..offset + m {
+            let first = if i < n { Some(i as usize) } else { None };
+            let second = if i >= offset {
+                Some((i - offset) as usize)
+            } else {
+                None
+            };
+            let (base, score) = match (first, second) {
+                (Some(j), None) => (seq1[j], scores1[j]),
+                (None, Some(k)) => (seq2[k], scores2[k]),
+                (Some(j), Some(k)) if same(seq1[j], seq2[k]) => {
+                    (seq1[j], scores1[j].max(scores2[k]))
+                }
+                (Some(j), Some(k)) if scores1[j] >= scores2[k] => {
+                    (seq1[j], scores1[j] - scores2[k])
+                }
+                (Some(j), Some(k)) => (seq2[k], scores2[k] - scores1[j]),
+                (None, None) => unreachable!(),
+            };
+            sequence.push(base);
+            scores.push(score);
+        }
+        Some(Merged {
+            sequence,
+            scores,
+            overlap,
+            mismatches,
+        })
+    }
+}
+
+fn same(a: u8, b: u8) -> bool {
+    a.eq_ignore_ascii_case(&b)
+}
+
+/// The reverse complement of a DNA sequence. Unknown bases become N's.
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence
+        .iter()
+        .rev()
+        .map(|b| u8::from(Nucleotide::from(b.to_ascii_uppercase()).complement()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAGMENT: &[u8] = b"GATTACAGGCTTAACCGGTTAGCAATGCCGTATCGA";
+
+    fn merger() -> Merger {
+        Merger {
+            min_overlap: 10,
+            max_mismatches: 0.1,
+        }
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(b"TGTAATCN".to_vec(), reverse_complement(b"xGATTaca"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let read2 = reverse_complement(&FRAGMENT[12..]);
+        let merged = merger()
+            .merge(&FRAGMENT[..24], &[30; 24], &read2, &[20; 24])
+            .unwrap();
+        assert_eq!(FRAGMENT.to_vec(), merged.sequence);
+        assert_eq!(12, merged.overlap);
+        assert_eq!(0, merged.mismatches);
+        assert_eq!(vec![30; 24], merged.scores[..24].to_vec());
+        assert_eq!(vec![20; 12], merged.scores[24..].to_vec());
+    }
+
+    #[test]
+    fn test_merge_mismatch() {
+        let mut read1 = FRAGMENT[..24].to_vec();
+        read1[20] = b'C';
+        let mut scores1 = vec![30; 24];
+        scores1[20] = 5;
+        let read2 = reverse_complement(&FRAGMENT[4..]);
+        let merged = merger().merge(&read1, &scores1, &read2, &[20; 32]).unwrap();
+        assert_eq!(FRAGMENT.to_vec(), merged.sequence);
+        assert_eq!(1, merged.mismatches);
+        assert_eq!(15, merged.scores[20]);
+    }
+
+    #[test]
+    fn test_merge_read_through() {
+        let mut read1 = FRAGMENT[..20].to_vec();
+        read1.extend_from_slice(b"AGATCGGAAG");
+        let mut read2 = reverse_complement(&FRAGMENT[..20]);
+        read2.extend_from_slice(b"AGATCGGAAG");
+        let merged = merger()
+            .merge(&read1, &[30; 30], &read2, &[30; 30])
+            .unwrap();
+        assert_eq!(FRAGMENT[..20].to_vec(), merged.sequence);
+    }
+
+    #[test]
+    fn test_short_mate() {
+        assert_eq!(
+            None,
+            merger().merge(&FRAGMENT[..20], &[30; 20], b"CGT", &[30; 3])
+        );
+        assert_eq!(None, merger().merge(&FRAGMENT[..20], &[30; 20], b"", &[]));
+    }
+
+    #[test]
+    fn test_no_overlap() {
+        let read2 = reverse_complement(&FRAGMENT[18..]);
+        assert_eq!(
+            None,
+            merger().merge(&FRAGMENT[..18], &[30; 18], &read2, &[30; 18])
+        );
+    }
+}
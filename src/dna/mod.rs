@@ -1,6 +1,8 @@
 //! Module for DNA related code.
 
+pub mod merge;
 pub mod translation;
+pub mod trim;
 
 /// A Deoxyribose nucleotide.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -0,0 +1,253 @@
+//! Quality trimming and filtering of DNA reads.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Trims and filters reads, based on their Phred quality scores and sequence content.
+///
+/// The steps are applied in the following order: adapter clipping, leading and trailing quality
+/// trimming, sliding window trimming, low-complexity masking, and finally filtering on N-content
+/// and length.
+#[derive(Debug, Clone, Default)]
+pub struct Trimmer {
+    /// Remove leading bases with a quality below this score.
+    pub leading: Option<u8>,
+    /// Remove trailing bases with a quality below this score.
+    pub trailing: Option<u8>,
+    /// Cut the read once the average quality in a window of this size drops below this score.
+    pub window: Option<(usize, u8)>,
+    /// Adapter sequences to clip from the 3' end.
+    pub adapters: Vec<Vec<u8>>,
+    /// The shortest partial adapter (at the very end of a read) to clip.
+    pub adapter_overlap: usize,
+    /// The maximal fraction of mismatches in an adapter match.
+    pub adapter_mismatches: f32,
+    /// Mask windows of 64 bases with a DUST score above this threshold with N's.
+    pub dust: Option<f32>,
+    /// Drop reads with a larger fraction of N's (after masking).
+    pub max_n: Option<f32>,
+    /// Drop reads shorter than this length after trimming.
+    pub min_length: usize,
+}
+
+/// The window size for the DUST low-complexity score.
+pub const DUST_WINDOW: usize = 64;
+
+impl Trimmer {
+    /// Trims a read and the Phred scores of its bases in place. Returns whether the read passes
+    /// the filters.
+    pub fn trim(&self, sequence: &mut Vec<u8>, scores: &mut Vec<u8>) -> bool {
+        let mut end = sequence.len().min(scores.len());
+        for adapter in self.adapters.iter() {
+            end = end.min(adapter_start(
+                &sequence[..end],
+                adapter,
+                self.adapter_overlap,
+                self.adapter_mismatches,
+            ));
+        }
+        let mut range = 0..end;
+        if let Some(threshold) = self.leading {
+            range.start += leading(&scores[range.clone()], threshold);
+        }
+        if let Some(threshold) = self.trailing {
+            range.end = range.start + trailing(&scores[range.clone()], threshold);
+        }
+        if let Some((size, threshold)) = self.window {
+            range.end = range.start + sliding_window(&scores[range.clone()], size, threshold);
+        }
+        sequence.truncate(range.end);
+        sequence.drain(..range.start);
+        scores.truncate(range.end);
+        scores.drain(..range.start);
+
+        if let Some(threshold) = self.dust {
+            dust(sequence, DUST_WINDOW, threshold);
+        }
+        if let Some(fraction) = self.max_n {
+            if n_fraction(sequence) > fraction {
+                return false;
+            }
+        }
+        sequence.len() >= self.min_length
+    }
+}
+
+/// Returns the number of leading bases with a score below the threshold.
+pub fn leading(scores: &[u8], threshold: u8) -> usize {
+    scores
+        .iter()
+        .position(|&s| s >= threshold)
+        .unwrap_or(scores.len())
+}
+
+/// Returns the length of the read without the trailing bases with a score below the threshold.
+pub fn trailing(scores: &[u8], threshold: u8) -> usize {
+    scores
+        .iter()
+        .rposition(|&s| s >= threshold)
+        .map(|p| p + 1)
+        .unwrap_or(0)
+}
+
+/// Scans the read from the 5' end and cuts it in the first window of the given size with an
+/// average score below the threshold. The bases of this window up to the first one below the
+/// threshold are kept. Returns the length of the trimmed read.
+pub fn sliding_window(scores: &[u8], size: usize, threshold: u8) -> usize {
+    if size == 0 || scores.len() < size {
+        return scores.len();
+    }
+    let minimum = threshold as usize * size;
+    let mut sum = scores[..size].iter().map(|&s| s as usize).sum::<usize>();
+    for start in 0..=scores.len() - size {
+        if start > 0 {
+            sum = sum + scores[start + size - 1] as usize - scores[start - 1] as usize;
+        }
+        if sum < minimum {
+            let window = &scores[start..start + size];
+            return start + window.iter().position(|&s| s < threshold).unwrap_or(size);
+        }
+    }
+    scores.len()
+}
+
+/// The fraction of N's (or any other non-ACGT character) in a sequence.
+pub fn n_fraction(sequence: &[u8]) -> f32 {
+    if sequence.is_empty() {
+        return 0.0;
+    }
+    let unknown = sequence
+        .iter()
+        .filter(|&&b| !matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T'))
+        .count();
+    unknown as f32 / sequence.len() as f32
+}
+
+/// The DUST score of a sequence: the sum over all triplets of `c * (c - 1) / 2`, with `c` the
+/// number of occurrences of that triplet, divided by the number of triplets minus one.
+/// Low-complexity sequences, such as repeats, have high scores.
+pub fn dust_score(sequence: &[u8]) -> f32 {
+    if sequence.len() < 4 {
+        return 0.0;
+    }
+    let mut counts = HashMap::new();
+    for triplet in sequence.windows(3) {
+        *counts.entry(triplet).or_insert(0_usize) += 1;
+    }
+    let score = counts.values().map(|&c| c * (c - 1) / 2).sum::<usize>();
+    score as f32 / (sequence.len() - 3) as f32
+}
+
+/// Masks each window of the given size with a DUST score above the threshold with N's.
+pub fn dust(sequence: &mut [u8], window: usize, threshold: f32) {
+    let length = sequence.len();
+    let window = window.min(length);
+    let mut masked: Vec<Range<usize>> = Vec::new();
+    for start in 0..=length - window {
+        if dust_score(&sequence[start..start + window]) > threshold {
+            match masked.last_mut() {
+                Some(last) if last.end >= start => last.end = start + window,
+                _ => masked.push(start..start + window),
+            }
+        }
+    }
+    for range in masked {
+        for base in &mut sequence[range] {
+            *base = b'N';
+        }
+    }
+}
+
+/// Finds where an adapter starts in the read: either completely contained in it, or partially
+/// overhanging its 3' end with an overlap of at least `min_overlap` bases. At most a fraction
+/// `mismatches` of the overlapping bases may differ. Returns the length of the read if the
+/// adapter isn't found or is empty.
+pub fn adapter_start(read: &[u8], adapter: &[u8], min_overlap: usize, mismatches: f32) -> usize {
+    if adapter.is_empty() {
+        return read.len();
+    }
+    let min_overlap = min_overlap.max(1);
+    for start in 0..read.len() {
+        let overlap = adapter.len().min(read.len() - start);
+        if overlap < min_overlap.min(adapter.len()) {
+            break;
+        }
+        let allowed = (overlap as f32 * mismatches) as usize;
+        let differences = read[start..start + overlap]
+            .iter()
+            .zip(adapter)
+            .filter(|(r, a)| !r.eq_ignore_ascii_case(a))
+            .count();
+        if differences <= allowed {
+            return start;
+        }
+    }
+    read.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_trailing() {
+        assert_eq!(2, leading(&[2, 10, 30, 30, 2], 20));
+        assert_eq!(4, trailing(&[2, 10, 30, 30, 2], 20));
+        assert_eq!(3, leading(&[2, 2, 2], 20));
+        assert_eq!(0, trailing(&[2, 2, 2], 20));
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        assert_eq!(6, sliding_window(&[30, 30, 30, 30, 30, 30], 4, 20));
+        assert_eq!(
+            5,
+            sliding_window(&[30, 30, 30, 30, 30, 10, 2, 2, 30], 4, 20)
+        );
+        assert_eq!(2, sliding_window(&[30, 30], 4, 20));
+    }
+
+    #[test]
+    fn test_dust() {
+        assert!(dust_score(b"ACACACACACACACACACAC") > dust_score(b"ACGTTGCAAGCTTCGATCGG"));
+        let mut sequence = b"GATTACAACGTTAGCACACACACACACACACACACACACAC".to_vec();
+        dust(&mut sequence, 20, 2.0);
+        assert!(sequence.starts_with(b"GATTACAAC"));
+        assert!(sequence.ends_with(b"NNNNNNNNNN"));
+    }
+
+    #[test]
+    fn test_adapter_start() {
+        assert_eq!(6, adapter_start(b"GATTACAGATCGGAAG", b"AGATCGGAAG", 3, 0.0));
+        assert_eq!(
+            13,
+            adapter_start(b"GATTACAGATTCAAGATC", b"AGATCGGAAG", 3, 0.0)
+        );
+        assert_eq!(
+            18,
+            adapter_start(b"GATTACAGATTCAAGATC", b"AGATCGGAAG", 6, 0.0)
+        );
+        assert_eq!(6, adapter_start(b"GATTACAGATCGGTAG", b"AGATCGGAAG", 3, 0.1));
+        assert_eq!(7, adapter_start(b"GATTACA", b"", 0, 0.0));
+    }
+
+    #[test]
+    fn test_trimmer() {
+        let trimmer = Trimmer {
+            leading: Some(3),
+            trailing: Some(3),
+            min_length: 4,
+            max_n: Some(0.25),
+            ..Trimmer::default()
+        };
+        let mut sequence = b"NGATTACAN".to_vec();
+        let mut scores = vec![2, 30, 30, 30, 30, 30, 30, 31, 2];
+        assert!(trimmer.trim(&mut sequence, &mut scores));
+        assert_eq!(b"GATTACA".to_vec(), sequence);
+        assert_eq!(vec![30, 30, 30, 30, 30, 30, 31], scores);
+        let mut sequence = b"GANNNCA".to_vec();
+        assert!(!trimmer.trim(&mut sequence, &mut vec![30; 7]));
+        let mut sequence = b"GATTACA".to_vec();
+        assert!(!trimmer.trim(&mut sequence, &mut vec![2, 2, 2, 30, 30, 30, 2]));
+    }
+}
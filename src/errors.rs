@@ -11,6 +11,12 @@ use regex;
 
 use crate::agg;
 use crate::dna::translation;
+use crate::io::fastq;
+use crate::io::gtdb;
+use crate::io::sam;
+use crate::io::taxcache;
+use crate::io::taxdump;
+use crate::names;
 use crate::taxon;
 
 error_chain! {
@@ -18,6 +24,12 @@ error_chain! {
         Taxon(taxon::Error, taxon::ErrorKind) #[doc = "Error propagated from Taxon"];
         Translation(translation::Error, translation::ErrorKind) #[doc = "Error propagated from Translation"];
         Aggregation(agg::Error, agg::ErrorKind) #[doc = "Error propagated from Aggregation"];
+        Fastq(fastq::Error, fastq::ErrorKind) #[doc = "Error propagated from FASTQ parsing"];
+        Sam(sam::Error, sam::ErrorKind) #[doc = "Error propagated from SAM/BAM parsing"];
+        Taxdump(taxdump::Error, taxdump::ErrorKind) #[doc = "Error propagated from taxonomy dump parsing"];
+        Gtdb(gtdb::Error, gtdb::ErrorKind) #[doc = "Error propagated from GTDB taxonomy parsing"];
+        TaxonomyCache(taxcache::Error, taxcache::ErrorKind) #[doc = "Error propagated from taxonomy caches"];
+        Names(names::Error, names::ErrorKind) #[doc = "Error propagated from looking up taxon names"];
     }
     foreign_links {
         Csv(csv::Error) #[doc = "CSV"];
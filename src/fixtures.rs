@@ -14,8 +14,8 @@ pub fn taxon_list() -> Vec<Taxon> {
 }
 
 pub fn tree() -> TaxonTree {
-    TaxonTree::new(&taxon_list())
+    TaxonTree::new(&taxon_list()).unwrap()
 }
 pub fn by_id() -> TaxonList {
-    TaxonList::new(taxon_list())
+    TaxonList::new(taxon_list()).unwrap()
 }
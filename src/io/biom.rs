@@ -0,0 +1,161 @@
+//! Writes tables in the [BIOM 1.0](http://biom-format.org/documentation/format_versions/biom-1.0.html)
+//! JSON format, as used by QIIME and phyloseq.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use serde_json::json;
+use serde_json::Value;
+
+const FORMAT: &str = "Biological Observation Matrix 1.0.0";
+const FORMAT_URL: &str = "http://biom-format.org";
+
+/// An observation (a row) of a BIOM table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// The identifier of the observation, e.g. a taxon ID
+    pub id: String,
+
+    /// The lineage of the observation, stored as its `taxonomy` metadata
+    pub taxonomy: Vec<String>,
+}
+
+/// A table of counts, with an observation for each row and a sample for each column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// The observations, one for each row
+    pub observations: Vec<Observation>,
+
+    /// The sample names, one for each column
+    pub samples: Vec<String>,
+
+    /// The counts, row by row
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl Table {
+    /// Converts this table into BIOM JSON, with either a sparse matrix (a list of `[row, column,
+    /// count]` entries for the non-zero counts) or a dense matrix (a list of rows).
+    pub fn to_json(&self, sparse: bool) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        self.to_json_at(sparse, &iso8601(now))
+    }
+
+    fn to_json_at(&self, sparse: bool, date: &str) -> Value {
+        let rows = self
+            .observations
+            .iter()
+            .map(|o| json!({"id": o.id, "metadata": {"taxonomy": o.taxonomy}}))
+            .collect::<Vec<_>>();
+        let columns = self
+            .samples
+            .iter()
+            .map(|sample| json!({"id": sample, "metadata": null}))
+            .collect::<Vec<_>>();
+        let data = if sparse {
+            let mut entries = Vec::new();
+            for (i, row) in self.counts.iter().enumerate() {
+                for (j, &count) in row.iter().enumerate() {
+                    if count != 0 {
+                        entries.push(json!([i, j, count]));
+                    }
+                }
+            }
+            Value::Array(entries)
+        } else {
+            json!(self.counts)
+        };
+        json!({
+            "id": null,
+            "format": FORMAT,
+            "format_url": FORMAT_URL,
+            "type": "Taxon table",
+            "generated_by": format!("umgap {}", env!("CARGO_PKG_VERSION")),
+            "date": date,
+            "rows": rows,
+            "columns": columns,
+            "matrix_type": if sparse { "sparse" } else { "dense" },
+            "matrix_element_type": "int",
+            "shape": [self.observations.len(), self.samples.len()],
+            "data": data,
+        })
+    }
+}
+
+// Formats seconds since the UNIX epoch as an ISO 8601 date and time (in UTC).
+fn iso8601(seconds: u64) -> String {
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = seconds / 86400 + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let time = seconds % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table {
+            observations: vec![
+                Observation {
+                    id: "2".to_string(),
+                    taxonomy: vec!["k__Bacteria".to_string(), "p__".to_string()],
+                },
+                Observation {
+                    id: "10239".to_string(),
+                    taxonomy: vec!["k__Viruses".to_string(), "p__".to_string()],
+                },
+            ],
+            samples: vec!["A".to_string(), "B".to_string()],
+            counts: vec![vec![5, 0], vec![0, 3]],
+        }
+    }
+
+    #[test]
+    fn test_iso8601() {
+        assert_eq!("1970-01-01T00:00:00", iso8601(0));
+        assert_eq!("2000-02-29T01:02:03", iso8601(951_782_400 + 3723));
+    }
+
+    #[test]
+    fn test_sparse() {
+        let json = table().to_json_at(true, "2020-01-01T00:00:00");
+        assert_eq!(json["format"], FORMAT);
+        assert_eq!(json["matrix_type"], "sparse");
+        assert_eq!(json["shape"], json!([2, 2]));
+        assert_eq!(json["data"], json!([[0, 0, 5], [1, 1, 3]]));
+        assert_eq!(json["rows"][1]["id"], "10239");
+        assert_eq!(
+            json["rows"][0]["metadata"]["taxonomy"],
+            json!(["k__Bacteria", "p__"])
+        );
+        assert_eq!(json["columns"][1], json!({"id": "B", "metadata": null}));
+    }
+
+    #[test]
+    fn test_dense() {
+        let json = table().to_json_at(false, "2020-01-01T00:00:00");
+        assert_eq!(json["matrix_type"], "dense");
+        assert_eq!(json["data"], json!([[5, 0], [0, 3]]));
+    }
+}
@@ -0,0 +1,126 @@
+//! Transparent decompression of input streams.
+//!
+//! Input streams are decompressed based on the magic bytes they start with, so plain text, gzip,
+//! bzip2, xz and zstd input can be used interchangeably wherever a file or *standard input* is
+//! read.
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const MAGIC_LENGTH: usize = 6;
+
+/// A compression format.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression format from the first bytes of a stream.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if magic.starts_with(BZIP2_MAGIC) {
+            Compression::Bzip2
+        } else if magic.starts_with(XZ_MAGIC) {
+            Compression::Xz
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wraps a readable source in the decoder matching its magic bytes. Sources which don't start
+/// with a known magic number are passed through unchanged.
+pub fn decompress<'a, R: Read + Send + 'a>(mut reader: R) -> io::Result<Box<dyn Read + Send + 'a>> {
+    // Read the magic bytes, even if the underlying reader hands them over in pieces.
+    let mut magic = [0; MAGIC_LENGTH];
+    let mut length = 0;
+    while length < MAGIC_LENGTH {
+        match reader.read(&mut magic[length..]) {
+            Ok(0) => break,
+            Ok(n) => length += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let compression = Compression::detect(&magic[..length]);
+    let source = io::Cursor::new(magic[..length].to_vec()).chain(reader);
+    Ok(match compression {
+        Compression::None => Box::new(source),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(source)),
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(source)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(source)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(source)?),
+    })
+}
+
+/// Opens a file for reading, decompressing it if needed.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read + Send>> {
+    decompress(File::open(path)?)
+}
+
+/// Returns *standard input*, decompressed if needed.
+pub fn stdin() -> io::Result<Box<dyn Read + Send>> {
+    decompress(io::stdin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fastq;
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn headers(path: &str) -> Vec<String> {
+        fastq::Reader::new(open(path).unwrap())
+            .records()
+            .map(|record| record.unwrap().header)
+            .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::None, Compression::detect(b"@header"));
+        assert_eq!(Compression::None, Compression::detect(b""));
+        assert_eq!(Compression::Gzip, Compression::detect(&[0x1f, 0x8b, 0x08]));
+        assert_eq!(Compression::Bzip2, Compression::detect(b"BZh9"));
+        assert_eq!(Compression::Xz, Compression::detect(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]));
+        assert_eq!(Compression::Zstd, Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]));
+    }
+
+    #[test]
+    fn test_compressed_fixtures() {
+        let plain = headers(&testdata("A1.fq"));
+        assert!(!plain.is_empty());
+        for extension in &["gz", "bz2", "xz", "zst"] {
+            assert_eq!(plain, headers(&testdata(&format!("A1.fq.{}", extension))));
+        }
+    }
+
+    #[test]
+    fn test_short_input() {
+        let mut output = String::new();
+        decompress(&b">"[..])
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(">", output);
+    }
+}
@@ -3,10 +3,10 @@
 use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Lines;
 use std::io::Read;
 use std::io::Write;
-use std::iter::Peekable;
+use std::ops::Range;
+use std::str;
 
 use crate::errors;
 use crate::errors::Result;
@@ -15,8 +15,14 @@ const BUFFER_SIZE: usize = 10_000_000; // 10MB
 const FASTA_WIDTH: usize = 70;
 
 /// Reads a FASTA-formatted source (e.g. a file).
+///
+/// The reader works on bytes: each record is read into a buffer which is reused for the next
+/// record, and handed out as a [RecordRef](struct.RecordRef.html) borrowing from this buffer.
 pub struct Reader<R: Read> {
-    lines: Peekable<Lines<BufReader<R>>>,
+    reader: BufReader<R>,
+    buffer: Vec<u8>,
+    header: Range<usize>,
+    lines: Vec<Range<usize>>,
     unwrap: bool,
 }
 
@@ -28,42 +34,77 @@ impl<R: Read> Reader<R> {
     /// When unwrap is `false`, each record line will be a new item in
     /// the [Record.sequence](struct.Record.html) vec.
     pub fn new(reader: R, unwrap: bool) -> Self {
-        let lines = BufReader::with_capacity(BUFFER_SIZE, reader)
-            .lines()
-            .peekable();
-        Reader { lines, unwrap }
+        Reader {
+            reader: BufReader::with_capacity(BUFFER_SIZE, reader),
+            buffer: Vec::new(),
+            header: 0..0,
+            lines: Vec::new(),
+            unwrap,
+        }
     }
 
-    /// Reads the next record from the FASTA file.
-    pub fn read_record(&mut self) -> Result<Option<Record>> {
-        let mut header = match self.lines.next() {
-            None => return Ok(None),
-            Some(header) => header?,
-        };
+    // Appends the next line to the buffer, without its line ending. Returns false at the end of
+    // the input.
+    fn read_line(&mut self) -> Result<bool> {
+        if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+            return Ok(false);
+        }
+        if self.buffer.last() == Some(&b'\n') {
+            self.buffer.pop();
+            if self.buffer.last() == Some(&b'\r') {
+                self.buffer.pop();
+            }
+        }
+        Ok(true)
+    }
 
-        if !header.starts_with('>') {
+    /// Reads the next record from the FASTA file, borrowing it from the internal buffer.
+    pub fn read_record_ref(&mut self) -> Result<Option<RecordRef<'_>>> {
+        self.buffer.clear();
+        self.lines.clear();
+
+        if !self.read_line()? {
+            return Ok(None);
+        }
+        if self.buffer.first() != Some(&b'>') {
             bail!(errors::ErrorKind::Io(io::Error::new(
                 io::ErrorKind::Other,
                 "Expected > at beginning of fasta header."
             )));
         }
-        let _ = header.remove(0);
+        self.header = 1..self.buffer.len();
 
-        let mut sequence = Vec::new();
+        let mut start = self.buffer.len();
         while self
-            .lines
-            .peek()
-            .and_then(|line| line.as_ref().ok())
-            .map(|line| !line.starts_with('>'))
+            .reader
+            .fill_buf()?
+            .first()
+            .map(|&byte| byte != b'>')
             .unwrap_or(false)
         {
-            sequence.push(self.lines.next().unwrap()?);
+            self.read_line()?;
+            if !self.unwrap {
+                self.lines.push(start..self.buffer.len());
+                start = self.buffer.len();
+            }
         }
         if self.unwrap {
-            sequence = vec![sequence.concat()];
+            self.lines.push(start..self.buffer.len());
         }
 
-        Ok(Some(Record { header, sequence }))
+        let buffer = &self.buffer;
+        Ok(Some(RecordRef {
+            header: &buffer[self.header.clone()],
+            sequence: self.lines.iter().map(|r| &buffer[r.clone()]).collect(),
+        }))
+    }
+
+    /// Reads the next record from the FASTA file.
+    pub fn read_record(&mut self) -> Result<Option<Record>> {
+        match self.read_record_ref()? {
+            None => Ok(None),
+            Some(record) => record.to_record().map(Some),
+        }
     }
 
     /// Returns a Records struct with itself as its reader.
@@ -83,6 +124,38 @@ pub struct Record {
     pub sequence: Vec<String>,
 }
 
+/// A [Record](struct.Record.html) borrowed from the buffer of a [Reader](struct.Reader.html). It
+/// is only valid until the next record is read.
+#[derive(Debug)]
+pub struct RecordRef<'a> {
+    /// The record header (without the preceding '>')
+    pub header: &'a [u8],
+
+    /// The sequence lines (or a single unwrapped sequence)
+    pub sequence: Vec<&'a [u8]>,
+}
+
+impl<'a> RecordRef<'a> {
+    /// Copies this record into an owned [Record](struct.Record.html), failing if it isn't valid
+    /// UTF-8.
+    pub fn to_record(&self) -> Result<Record> {
+        Ok(Record {
+            header: utf8(self.header)?.to_string(),
+            sequence: self
+                .sequence
+                .iter()
+                .map(|line| utf8(line).map(str::to_string))
+                .collect::<io::Result<Vec<String>>>()?,
+        })
+    }
+}
+
+/// Interprets bytes read by one of the readers as UTF-8, with the error the standard library's
+/// line readers would give.
+pub(crate) fn utf8(bytes: &[u8]) -> io::Result<&str> {
+    str::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
 /// Convenience struct which allows for iteration (e.g. using for..in).
 pub struct Records<R: Read> {
     reader: Reader<R>,
@@ -179,3 +252,35 @@ impl<'a, W: Write> Writer<'a, W> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_records() {
+        let input = &b">header1\nAC\nGT\n>header2\r\nTT\r\n>header3\n"[..];
+        let mut reader = Reader::new(input, false);
+        let record = reader.read_record_ref().unwrap().unwrap();
+        assert_eq!(&b"header1"[..], record.header);
+        assert_eq!(vec![&b"AC"[..], &b"GT"[..]], record.sequence);
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!("header2", record.header);
+        assert_eq!(vec!["TT".to_string()], record.sequence);
+        let record = reader.read_record_ref().unwrap().unwrap();
+        assert!(record.sequence.is_empty());
+        assert!(reader.read_record_ref().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unwrap() {
+        let mut reader = Reader::new(&b">header1\nAC\nGT\n>header2\n"[..], true);
+        assert_eq!(vec![&b"ACGT"[..]], reader.read_record_ref().unwrap().unwrap().sequence);
+        assert_eq!(vec![&b""[..]], reader.read_record_ref().unwrap().unwrap().sequence);
+    }
+
+    #[test]
+    fn test_missing_header() {
+        assert!(Reader::new(&b"ACGT\n"[..], false).read_record().is_err());
+    }
+}
@@ -1,89 +1,336 @@
 //! Allows operations over the [FASTQ format](https://en.wikipedia.org/wiki/FASTQ_format).
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::io;
 use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Read;
-use std::iter::Peekable;
+use std::mem;
+use std::ops::Range;
 
-use crate::errors;
-use crate::errors::Result;
+use crate::io::fasta::utf8;
+
+/// Lowest quality character allowed by any encoding (`!`).
+const MIN_QUALITY: u8 = 33;
+/// Highest quality character allowed by any encoding (`~`).
+const MAX_QUALITY: u8 = 126;
+
+/// The maximal number of records read ahead to detect the quality encoding.
+const DETECTION_RECORDS: usize = 1000;
+/// The maximal number of quality characters read ahead to detect the quality encoding.
+const DETECTION_BYTES: usize = 1 << 20;
+
+/// The encoding of the quality scores in a FASTQ file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityEncoding {
+    /// Sanger and Illumina 1.8+: the Phred score plus 33 (`!` is 0).
+    Phred33,
+    /// Illumina 1.3 to 1.7: the Phred score plus 64 (`@` is 0).
+    Phred64,
+}
+
+impl QualityEncoding {
+    /// The value added to the Phred score to get the quality character.
+    pub fn offset(self) -> u8 {
+        match self {
+            QualityEncoding::Phred33 => 33,
+            QualityEncoding::Phred64 => 64,
+        }
+    }
+
+    /// The Phred score of a quality character in this encoding.
+    pub fn score(self, quality: u8) -> u8 {
+        quality.saturating_sub(self.offset())
+    }
+
+    /// Guesses the encoding from the range of quality characters in a record. Returns `None` if
+    /// the characters fit both encodings.
+    ///
+    /// Characters below `;` (the lowest Solexa score) only appear in Phred+33. Phred+33 data
+    /// normally doesn't go above `K` (a score of 42), so a range entirely above `@` and reaching
+    /// higher than that is taken to be Phred+64.
+    pub fn detect(quality: &[u8]) -> Option<Self> {
+        QualityEncoding::detect_range(*quality.iter().min()?, *quality.iter().max()?)
+    }
+
+    // Guesses the encoding from the lowest and highest quality character, as in detect.
+    fn detect_range(min: u8, max: u8) -> Option<Self> {
+        if min < b';' {
+            Some(QualityEncoding::Phred33)
+        } else if min >= b'@' && max > b'K' {
+            Some(QualityEncoding::Phred64)
+        } else {
+            None
+        }
+    }
+}
 
 /// Reads a FASTQ-formatted source (e.g. a file).
+///
+/// Like the FASTA [Reader](../fasta/struct.Reader.html), this reader works on bytes and reuses a
+/// single buffer for all records.
+///
+/// Sequences and quality strings may be wrapped over multiple lines. The sequence ends at the
+/// first line starting with a `+`, and the quality lines are read until they are as long as the
+/// sequence, so quality lines starting with `@` or `+` are handled correctly.
+///
+/// Unless an encoding is given, the quality encoding is detected from the range of quality
+/// characters in the first records (up to 1000 records or 1 MiB of quality), which are read ahead
+/// before returning any. If these records fit both encodings, Phred+33 is assumed until a later
+/// record settles it. As the records returned before were scored with the detected encoding, a
+/// later record that isn't Phred+64 after detecting Phred+64 is an error, like it is for a given
+/// Phred+64 encoding.
 pub struct Reader<R: Read> {
-    lines: Peekable<io::Lines<io::BufReader<R>>>,
+    reader: BufReader<R>,
+    buffer: Vec<u8>,
+    encoding: Option<QualityEncoding>,
+    given: bool,
+    detected: bool,
+    pending: VecDeque<Pending>,
+    failed: Option<Error>,
+    record: usize,
+    line: usize,
+}
+
+// A record read ahead while detecting the quality encoding, with its position in the input.
+struct Pending {
+    buffer: Vec<u8>,
+    header: Range<usize>,
+    sequence: Range<usize>,
+    quality: Range<usize>,
+    record: usize,
+    line: usize,
 }
 
 impl<R: Read> Reader<R> {
     /// Creates a Reader from the given Read (e.g. a file)
     pub fn new(readable: R) -> Self {
         Reader {
-            lines: io::BufReader::new(readable).lines().peekable(),
+            reader: BufReader::new(readable),
+            buffer: Vec::new(),
+            encoding: None,
+            given: false,
+            detected: false,
+            pending: VecDeque::new(),
+            failed: None,
+            record: 0,
+            line: 0,
         }
     }
 
-    /// Reads the next record from the FASTQ file.
-    pub fn read_record(&mut self) -> Result<Option<Record>> {
+    /// Creates a Reader from the given Read, expecting the quality scores in the given encoding.
+    pub fn with_encoding(readable: R, encoding: QualityEncoding) -> Self {
+        let mut reader = Reader::new(readable);
+        reader.encoding = Some(encoding);
+        reader.given = true;
+        reader.detected = true;
+        reader
+    }
+
+    /// The quality encoding of this file, if it is known (or has been detected) yet.
+    pub fn encoding(&self) -> Option<QualityEncoding> {
+        self.encoding
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        ErrorKind::Parse(self.record, self.line, Box::new(kind)).into()
+    }
+
+    // Reads ahead to detect the quality encoding, keeping the records read to return them later.
+    // An error while reading ahead is kept to be returned after these records.
+    fn detect(&mut self) {
+        self.detected = true;
+        let (mut min, mut max, mut bytes) = (MAX_QUALITY, MIN_QUALITY, 0);
+        while self.pending.len() < DETECTION_RECORDS && bytes < DETECTION_BYTES && min >= b';' {
+            match self.parse_record() {
+                Ok(Some((header, sequence, quality))) => {
+                    for &byte in &self.buffer[quality.clone()] {
+                        min = min.min(byte);
+                        max = max.max(byte);
+                    }
+                    bytes += quality.len();
+                    self.pending.push_back(Pending {
+                        buffer: mem::take(&mut self.buffer),
+                        header,
+                        sequence,
+                        quality,
+                        record: self.record,
+                        line: self.line,
+                    });
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    self.failed = Some(error);
+                    break;
+                }
+            }
+        }
+        if bytes > 0 {
+            self.encoding = QualityEncoding::detect_range(min, max);
+        }
+    }
+
+    // Appends the next line to the buffer, without its line ending, and returns its range.
+    fn read_line(&mut self) -> Result<Option<Range<usize>>> {
+        let start = self.buffer.len();
+        if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        if self.buffer.last() == Some(&b'\n') {
+            self.buffer.pop();
+            if self.buffer.last() == Some(&b'\r') {
+                self.buffer.pop();
+            }
+        }
+        Ok(Some(start..self.buffer.len()))
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn at_blank_line(&mut self) -> Result<bool> {
+        Ok(self
+            .peek()?
+            .map(|byte| byte == b'\n' || byte == b'\r')
+            .unwrap_or(false))
+    }
+
+    // Checks the quality of a record against the encoding. Characters only found in Phred+33
+    // settle an undecided encoding on Phred+33, and contradict a given or detected Phred+64.
+    fn check_encoding(&mut self, quality: Range<usize>, record: usize, line: usize) -> Result<()> {
+        if self.encoding == Some(QualityEncoding::Phred33) {
+            return Ok(());
+        }
+        if let Some(&byte) = self.buffer[quality].iter().find(|&&b| b < b';') {
+            let kind = match self.encoding {
+                None => {
+                    self.encoding = Some(QualityEncoding::Phred33);
+                    return Ok(());
+                }
+                Some(_) if self.given => ErrorKind::InvalidQuality(byte as char),
+                Some(_) => ErrorKind::DetectedEncodingMismatch(byte as char),
+            };
+            return Err(ErrorKind::Parse(record, line, Box::new(kind)).into());
+        }
+        Ok(())
+    }
+
+    /// Reads the next record from the FASTQ file, borrowing it from the internal buffer.
+    pub fn read_record_ref(&mut self) -> Result<Option<RecordRef<'_>>> {
+        if !self.detected {
+            self.detect();
+        }
+        let (header, sequence, quality, record, line) = match self.pending.pop_front() {
+            Some(pending) => {
+                self.buffer = pending.buffer;
+                let Pending {
+                    header,
+                    sequence,
+                    quality,
+                    record,
+                    line,
+                    ..
+                } = pending;
+                (header, sequence, quality, record, line)
+            }
+            None => {
+                if let Some(error) = self.failed.take() {
+                    return Err(error);
+                }
+                match self.parse_record()? {
+                    None => return Ok(None),
+                    Some((header, sequence, quality)) => {
+                        (header, sequence, quality, self.record, self.line)
+                    }
+                }
+            }
+        };
+        self.check_encoding(quality.clone(), record, line)?;
+
+        Ok(Some(RecordRef {
+            header: &self.buffer[header],
+            sequence: &self.buffer[sequence],
+            quality: &self.buffer[quality],
+        }))
+    }
+
+    // Parses the next record into the buffer, returning the ranges of its header, sequence and
+    // quality.
+    #[allow(clippy::type_complexity)]
+    fn parse_record(&mut self) -> Result<Option<(Range<usize>, Range<usize>, Range<usize>)>> {
+        self.buffer.clear();
+
+        // skipping blank lines between records
+        while self.at_blank_line()? {
+            self.read_line()?;
+        }
+
         // reading the header
-        let mut header = match self.lines.next() {
+        let header = match self.read_line()? {
             None => return Ok(None),
-            Some(header) => header?,
+            Some(header) => header,
         };
-        if !header.starts_with('@') {
-            bail!(errors::ErrorKind::Io(io::Error::new(
-                io::ErrorKind::Other,
-                "Expected @ at beginning of fastq header."
-            )));
+        self.record += 1;
+        if self.buffer[header.clone()].first() != Some(&b'@') {
+            return Err(self.error(ErrorKind::MissingHeader));
         }
-        let _ = header.remove(0);
+        let header = header.start + 1..header.end;
 
         // reading the sequence
-        let mut lines = 0;
-        let mut sequence = String::new();
-        while self
-            .lines
-            .peek()
-            .and_then(|line| line.as_ref().ok())
-            .map(|line| !line.starts_with('+'))
-            .unwrap_or(false)
-        {
-            sequence.push_str(&self.lines.next().unwrap()?);
-            lines += 1;
+        let sequence_start = self.buffer.len();
+        while self.peek()?.map(|byte| byte != b'+').unwrap_or(false) {
+            self.read_line()?;
         }
+        let sequence = sequence_start..self.buffer.len();
 
-        // skipping the separator
-        if self
-            .lines
-            .next()
-            .and_then(|line| line.ok())
-            .map(|line| !line.starts_with('+'))
-            .unwrap_or(false)
-        {
-            bail!(errors::ErrorKind::Io(io::Error::new(
-                io::ErrorKind::Other,
-                "Expected a + as separator."
-            )));
-        }
-
-        // reading the quality
-        let mut quality = String::with_capacity(sequence.len());
-        for _ in 0..lines {
-            if let Some(line) = self.lines.next() {
-                quality.push_str(&line?)
-            } else {
-                bail!(errors::ErrorKind::Io(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Expected as many quality lines as \
-                                                            sequence lines."
-                )));
+        // checking the separator, which may repeat the header
+        match self.read_line()? {
+            None => return Err(self.error(ErrorKind::Truncated)),
+            Some(separator) => {
+                let title = &self.buffer[separator.start + 1..separator.end];
+                if !title.is_empty() && title != &self.buffer[header.clone()] {
+                    return Err(self.error(ErrorKind::SeparatorMismatch));
+                }
+                self.buffer.truncate(separator.start);
             }
         }
 
-        Ok(Some(Record {
-            header,
-            sequence,
-            quality,
-        }))
+        // reading the quality, until it's as long as the sequence
+        let quality_start = self.buffer.len();
+        if sequence.is_empty() {
+            // an empty sequence has an empty quality line
+            if self.at_blank_line()? {
+                self.read_line()?;
+            }
+        }
+        while self.buffer.len() - quality_start < sequence.len() {
+            if self.read_line()?.is_none() {
+                return Err(self.error(ErrorKind::Truncated));
+            }
+        }
+        let quality = quality_start..self.buffer.len();
+        if quality.len() != sequence.len() {
+            return Err(self.error(ErrorKind::LengthMismatch(sequence.len(), quality.len())));
+        }
+        if let Some(&byte) = self.buffer[quality.clone()]
+            .iter()
+            .find(|&&b| !(MIN_QUALITY..=MAX_QUALITY).contains(&b))
+        {
+            return Err(self.error(ErrorKind::InvalidQuality(byte as char)));
+        }
+
+        Ok(Some((header, sequence, quality)))
+    }
+
+    /// Reads the next record from the FASTQ file.
+    pub fn read_record(&mut self) -> Result<Option<Record>> {
+        match self.read_record_ref()? {
+            None => Ok(None),
+            Some(record) => record.to_record().map(Some),
+        }
     }
 
     /// Returns a Records struct with itself as its reader.
@@ -107,6 +354,32 @@ pub struct Record {
     pub quality: String,
 }
 
+/// A [Record](struct.Record.html) borrowed from the buffer of a [Reader](struct.Reader.html). It
+/// is only valid until the next record is read.
+#[derive(Debug)]
+pub struct RecordRef<'a> {
+    /// The FASTQ header (without the preceding '@')
+    pub header: &'a [u8],
+
+    /// The actual sequence of nucleotides, unwrapped
+    pub sequence: &'a [u8],
+
+    /// The quality line, unwrapped
+    pub quality: &'a [u8],
+}
+
+impl<'a> RecordRef<'a> {
+    /// Copies this record into an owned [Record](struct.Record.html), failing if it isn't valid
+    /// UTF-8.
+    pub fn to_record(&self) -> Result<Record> {
+        Ok(Record {
+            header: utf8(self.header)?.to_string(),
+            sequence: utf8(self.sequence)?.to_string(),
+            quality: utf8(self.quality)?.to_string(),
+        })
+    }
+}
+
 impl fmt::Display for Record {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -117,6 +390,78 @@ impl fmt::Display for Record {
     }
 }
 
+/// The name of a read and which mate of a pair it is, as parsed from its FASTQ header.
+///
+/// The mate is read from a `/1` or `/2` suffix on the read identifier (`@read/1`), or from an
+/// Illumina (Casava 1.8) comment (`@read 1:N:0:ATCACG`). Any other comment is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MateName<'a> {
+    /// The name of the read, shared by both mates
+    pub name: &'a str,
+
+    /// Which mate of the pair this read is, if known
+    pub mate: Option<u8>,
+}
+
+impl<'a> MateName<'a> {
+    /// Parses the mate name from a FASTQ header (without the preceding '@').
+    pub fn parse(header: &'a str) -> Self {
+        let mut parts = header.trim().splitn(2, char::is_whitespace);
+        let id = parts.next().unwrap_or("");
+        let comment = parts.next().unwrap_or("").trim_start().as_bytes();
+        if id.ends_with("/1") || id.ends_with("/2") {
+            MateName {
+                name: &id[..id.len() - 2],
+                mate: Some(id.as_bytes()[id.len() - 1] - b'0'),
+            }
+        } else if comment.len() >= 4
+            && matches!(comment[0], b'1' | b'2')
+            && comment[1] == b':'
+            && matches!(comment[2], b'Y' | b'N')
+            && comment[3] == b':'
+        {
+            MateName {
+                name: id,
+                mate: Some(comment[0] - b'0'),
+            }
+        } else {
+            MateName {
+                name: id,
+                mate: None,
+            }
+        }
+    }
+
+    /// The normalised header of this read: its name, followed by `/` and the given mate number if
+    /// it is part of a pair.
+    pub fn normalise(&self, mate: Option<usize>) -> String {
+        match mate {
+            Some(mate) => format!("{}/{}", self.name, mate),
+            None => self.name.to_string(),
+        }
+    }
+}
+
+/// Checks whether the given headers, one from each input file, belong to mates of the same read.
+/// Their names should be equal, and the mate numbers (if present) should match the position of
+/// each header.
+pub fn check_mates(headers: &[&str]) -> Result<()> {
+    let first = match headers.first() {
+        Some(first) => MateName::parse(first),
+        None => return Ok(()),
+    };
+    for (i, header) in headers.iter().enumerate() {
+        let mate = MateName::parse(header);
+        if mate.name != first.name || matches!(mate.mate, Some(m) if m as usize != i + 1) {
+            bail!(ErrorKind::MateMismatch(
+                headers[0].to_string(),
+                header.to_string()
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Convenience struct which allows for iteration (e.g. using for..in).
 pub struct Records<R: Read> {
     reader: Reader<R>,
@@ -133,3 +478,196 @@ impl<R: Read> Iterator for Records<R> {
         }
     }
 }
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+    }
+    errors {
+        /// A malformed record, with the record and line number where it was noticed
+        Parse(record: usize, line: usize, kind: Box<ErrorKind>) {
+            description("Malformed FASTQ record")
+            display("FASTQ record {} (line {}): {}", record, line, kind)
+        }
+        /// A record not starting with @
+        MissingHeader {
+            description("Expected @ at beginning of fastq header")
+            display("Expected @ at beginning of fastq header")
+        }
+        /// A separator line with a different title than the header
+        SeparatorMismatch {
+            description("Separator line doesn't match the header")
+            display("Separator line doesn't match the header")
+        }
+        /// The input ended in the middle of a record
+        Truncated {
+            description("Unexpected end of input")
+            display("Unexpected end of input")
+        }
+        /// A quality string of a different length than its sequence
+        LengthMismatch(sequence: usize, quality: usize) {
+            description("Quality and sequence lengths differ")
+            display("Sequence has length {}, but quality has length {}", sequence, quality)
+        }
+        /// A character not allowed in the quality encoding
+        InvalidQuality(quality: char) {
+            description("Invalid quality character")
+            display("Invalid quality character: {:?}", quality)
+        }
+        /// A quality character contradicting the Phred+64 encoding detected from the first records
+        DetectedEncodingMismatch(quality: char) {
+            description("Quality character contradicts the detected Phred+64 encoding")
+            display("Quality character {:?} contradicts the Phred+64 encoding detected from the first records", quality)
+        }
+        /// Paired records which aren't mates of each other
+        MateMismatch(first: String, second: String) {
+            description("Paired records are not mates")
+            display("Paired records are not mates: {} and {}", first, second)
+        }
+        /// Paired input files with a different number of records
+        UnevenPairs {
+            description("Paired input files have a different number of records")
+            display("Paired input files have a different number of records")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(input: &[u8]) -> (usize, usize, ErrorKind) {
+        match Reader::new(input).records().find_map(|r| r.err()) {
+            Some(Error(ErrorKind::Parse(record, line, kind), _)) => (record, line, *kind),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_records() {
+        let input = &b"@read1\nACGT\n+\nIIII\n@read2\r\nAC\r\nGT\r\n+read2\r\nII\r\n#I\r\n"[..];
+        let mut reader = Reader::new(input);
+        let record = reader.read_record_ref().unwrap().unwrap();
+        assert_eq!(&b"read1"[..], record.header);
+        assert_eq!(&b"ACGT"[..], record.sequence);
+        assert_eq!(&b"IIII"[..], record.quality);
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!("read2", record.header);
+        assert_eq!("ACGT", record.sequence);
+        assert_eq!("II#I", record.quality);
+        assert!(reader.read_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_quality_lines_like_headers() {
+        let input = &b"@read1\nACGTAC\n+\n@III\n+I\n@read2\nAC\n+\n+@\n\n"[..];
+        let records = Reader::new(input)
+            .records()
+            .collect::<Result<Vec<Record>>>()
+            .unwrap();
+        assert_eq!(2, records.len());
+        assert_eq!("@III+I", records[0].quality);
+        assert_eq!("+@", records[1].quality);
+    }
+
+    #[test]
+    fn test_empty_sequence() {
+        let records = Reader::new(&b"@read1\n\n+\n\n@read2\nA\n+\nI\n"[..])
+            .records()
+            .collect::<Result<Vec<Record>>>()
+            .unwrap();
+        assert_eq!("", records[0].sequence);
+        assert_eq!("A", records[1].sequence);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_matches!(parse_error(b"@r\nA\n+\nI\nread2\n"), (2, 5, ErrorKind::MissingHeader));
+        assert_matches!(parse_error(b"@r\nACGT\n+\nIIII\n@s\nA\n"), (2, 6, ErrorKind::Truncated));
+        assert_matches!(parse_error(b"@r\nACGT\n+\nII\n"), (1, 4, ErrorKind::Truncated));
+        assert_matches!(
+            parse_error(b"@r\nAC\n+\nIII\n"),
+            (1, 4, ErrorKind::LengthMismatch(2, 3))
+        );
+        assert_matches!(parse_error(b"@r\nAC\n+s\nII\n"), (1, 3, ErrorKind::SeparatorMismatch));
+        assert_matches!(parse_error(b"@r\nAC\n+\nI \n"), (1, 4, ErrorKind::InvalidQuality(' ')));
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut reader = Reader::new(&b"@r\nACGT\n+\nIIII\n@s\nACGT\n+\n#III\n"[..]);
+        reader.read_record().unwrap();
+        assert_eq!(Some(QualityEncoding::Phred33), reader.encoding());
+
+        let mut reader = Reader::new(&b"@r\nACGT\n+\nIIII\n"[..]);
+        reader.read_record().unwrap();
+        assert_eq!(None, reader.encoding());
+
+        let mut reader = Reader::new(&b"@r\nACGT\n+\nhhhB\n@s\nA\n+\nh\n"[..]);
+        reader.read_record().unwrap();
+        assert_eq!(Some(QualityEncoding::Phred64), reader.encoding());
+
+        let input = &b"@r\nACGT\n+\nhhhB\n@s\nA\n+\n#\n"[..];
+        let mut reader = Reader::with_encoding(input, QualityEncoding::Phred64);
+        reader.read_record().unwrap();
+        assert_matches!(
+            reader.read_record().unwrap_err().kind(),
+            ErrorKind::Parse(2, 8, _)
+        );
+
+        assert_eq!(40, QualityEncoding::Phred33.score(b'I'));
+        assert_eq!(40, QualityEncoding::Phred64.score(b'h'));
+    }
+
+    #[test]
+    fn test_high_quality_phred33() {
+        // a long read of high quality looks like Phred+64 on its own
+        let input = &b"@r\nACGTACGT\n+\nLLLLMNOI\n@s\nACGT\n+\n#III\n"[..];
+        let records = Reader::new(input)
+            .records()
+            .collect::<Result<Vec<Record>>>()
+            .unwrap();
+        assert_eq!(2, records.len());
+
+        // but only if it shows in the records the encoding is detected from
+        let mut input = b"@r\nACGT\n+\nLLLM\n".repeat(DETECTION_RECORDS);
+        input.extend_from_slice(b"@s\nACGT\n+\n#III\n");
+        let mut reader = Reader::new(&input[..]);
+        reader.read_record().unwrap();
+        assert_eq!(Some(QualityEncoding::Phred64), reader.encoding());
+        let mut records = reader.records();
+        for _ in 1..DETECTION_RECORDS {
+            records.next().unwrap().unwrap();
+        }
+        assert_eq!(
+            "FASTQ record 1001 (line 4004): Quality character '#' contradicts the Phred+64 \
+             encoding detected from the first records",
+            records.next().unwrap().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_mate_name() {
+        let mate = |name, mate| MateName { name, mate };
+        assert_eq!(mate("read", Some(1)), MateName::parse("read/1"));
+        assert_eq!(mate("read", Some(2)), MateName::parse("read/2 some comment"));
+        assert_eq!(mate("read", Some(2)), MateName::parse("read 2:N:0:ATCACG"));
+        assert_eq!(mate("read", Some(1)), MateName::parse("read\t1:Y:18:1"));
+        assert_eq!(mate("read", None), MateName::parse("read 1 length=100"));
+        assert_eq!(mate("read/3", None), MateName::parse("read/3"));
+        assert_eq!("read/2", MateName::parse("read 1:N:0").normalise(Some(2)));
+        assert_eq!("read", MateName::parse("read/1").normalise(None));
+    }
+
+    #[test]
+    fn test_check_mates() {
+        assert!(check_mates(&["read/1", "read/2"]).is_ok());
+        assert!(check_mates(&["read 1:N:0:1", "read 2:N:0:1"]).is_ok());
+        assert!(check_mates(&["read length=100", "read length=100"]).is_ok());
+        assert!(check_mates(&["read/2", "read/1"]).is_err());
+        assert_matches!(
+            check_mates(&["read1/1", "read2/2"]),
+            Err(Error(ErrorKind::MateMismatch(_, _), _))
+        );
+    }
+}
@@ -0,0 +1,526 @@
+//! Reads the [GTDB](https://gtdb.ecogenomic.org/) taxonomy.
+//!
+//! GTDB assigns a lineage on seven ranks to each genome, such as
+//! `d__Bacteria;p__Proteobacteria;c__Gammaproteobacteria;...;s__Escherichia coli`. As GTDB taxa
+//! have no numeric IDs, each taxon gets a synthetic ID derived from a hash of its name (with the
+//! rank prefix), so the taxonomy can be used wherever the NCBI taxonomy is expected. When the
+//! hashes of two names collide, one of them gets the next free ID instead, which depends on the
+//! other taxa of the release. To keep the IDs stable across GTDB releases, the taxonomy built
+//! from a previous release can be given, whose taxa keep their IDs (see
+//! [Gtdb::with_previous](struct.Gtdb.html#method.with_previous)).
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::io::BufRead;
+use std::num::ParseIntError;
+
+use crate::rank::Rank;
+use crate::taxon::Taxon;
+use crate::taxon::TaxonId;
+use crate::taxon::TaxonList;
+
+/// The ID of the root of a GTDB taxonomy.
+pub const ROOT: TaxonId = 1;
+
+/// The synthetic IDs are below this bound, which keeps the `TaxonList` of a GTDB taxonomy about as
+/// large as the one of the NCBI taxonomy.
+const ID_SPACE: TaxonId = 1 << 22;
+
+/// The rank prefixes used in GTDB lineages.
+const PREFIXES: &[(&str, Rank)] = &[
+    ("d__", Rank::Domain),
+    ("p__", Rank::Phylum),
+    ("c__", Rank::Class),
+    ("o__", Rank::Order),
+    ("f__", Rank::Family),
+    ("g__", Rank::Genus),
+    ("s__", Rank::Species),
+];
+
+/// A genome from a GTDB taxonomy or metadata file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Genome {
+    /// The accession of the genome
+    pub accession: String,
+
+    /// The GTDB lineage of the genome
+    pub lineage: String,
+
+    /// The NCBI taxon ID of the genome, if known
+    pub ncbi_taxon: Option<TaxonId>,
+}
+
+/// Reads the genomes from a GTDB taxonomy file (such as `bac120_taxonomy.tsv`), with the accession
+/// and lineage of a genome on each line, or from a GTDB metadata file (such as
+/// `bac120_metadata.tsv`), which starts with a header and also contains the NCBI taxon IDs.
+pub fn read_genomes<R: BufRead>(reader: R) -> Result<Vec<Genome>> {
+    let mut genomes = Vec::new();
+    let mut columns = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let row = line.split('\t').collect::<Vec<_>>();
+        if i == 0 && row.contains(&"gtdb_taxonomy") {
+            let column = |name: &str| {
+                row.iter()
+                    .position(|&column| column == name)
+                    .ok_or_else(|| ErrorKind::Malformed(1, format!("missing column {}", name)))
+            };
+            columns = Some((
+                column("accession")?,
+                column("gtdb_taxonomy")?,
+                column("ncbi_taxid")?,
+            ));
+            continue;
+        }
+        let malformed = |message: &str| ErrorKind::Malformed(i + 1, message.to_string());
+        let genome = match columns {
+            Some((accession, lineage, ncbi_taxon)) => {
+                let field = |index: usize| row.get(index).ok_or_else(|| malformed("missing field"));
+                let ncbi_taxon = match *field(ncbi_taxon)? {
+                    "" | "none" => None,
+                    id => Some(
+                        id.parse::<TaxonId>()
+                            .map_err(|_| malformed("invalid taxon ID"))?,
+                    ),
+                };
+                Genome {
+                    accession: field(accession)?.to_string(),
+                    lineage: field(lineage)?.to_string(),
+                    ncbi_taxon,
+                }
+            }
+            None if row.len() >= 2 => Genome {
+                accession: row[0].to_string(),
+                lineage: row[1].to_string(),
+                ncbi_taxon: None,
+            },
+            None => bail!(malformed("expected an accession and a lineage")),
+        };
+        genomes.push(genome);
+    }
+    Ok(genomes)
+}
+
+/// Reads a mapping from NCBI taxon IDs to GTDB taxon IDs, with an NCBI and a GTDB taxon ID on each
+/// line, separated by a tab.
+pub fn read_mapping<R: BufRead>(reader: R) -> Result<HashMap<TaxonId, TaxonId>> {
+    let mut mapping = HashMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        match (fields.next(), fields.next()) {
+            (Some(ncbi), Some(gtdb)) => mapping.insert(ncbi.parse()?, gtdb.trim().parse()?),
+            _ => bail!(ErrorKind::Malformed(i + 1, "expected 2 fields".into())),
+        };
+    }
+    Ok(mapping)
+}
+
+/// A GTDB taxonomy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gtdb {
+    /// The taxa, ordered by ID
+    pub taxa: Vec<Taxon>,
+
+    ids: HashMap<String, TaxonId>,
+}
+
+impl Gtdb {
+    /// Builds the taxonomy containing the lineages of the given genomes.
+    ///
+    /// The IDs are assigned in the order of the names, so a collision of the hashes of two names
+    /// is resolved the same way within a release: the first name gets the ID, and the other one the
+    /// next free ID. Another release may resolve it differently, see
+    /// [with_previous](#method.with_previous) to prevent that.
+    pub fn new(genomes: &[Genome]) -> Result<Self> {
+        Gtdb::with_previous(genomes, &[])
+    }
+
+    /// Builds the taxonomy containing the lineages of the given genomes, keeping the IDs of the
+    /// given taxonomy of a previous release. Its taxa are matched on their name and rank. The IDs
+    /// of its taxa are never given to other taxa, not even to the taxa that were removed since.
+    pub fn with_previous(genomes: &[Genome], previous: &[Taxon]) -> Result<Self> {
+        // The parent of each taxon (by its name with the rank prefix), or None for the root
+        let mut parents: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+        for genome in genomes {
+            let mut parent = None;
+            for name in genome.lineage.split(';').map(str::trim) {
+                // Skip the ranks without a name, as in "g__".
+                if split_name(name)?.1.is_empty() {
+                    continue;
+                }
+                match parents.insert(name, parent) {
+                    Some(previous) if previous != parent => {
+                        bail!(ErrorKind::Inconsistent(name.to_string()))
+                    }
+                    _ => parent = Some(name),
+                }
+            }
+        }
+
+        let mut used = HashSet::new();
+        used.insert(ROOT);
+        let mut previous_ids = HashMap::new();
+        for taxon in previous {
+            used.insert(taxon.id);
+            if let Some(prefix) = prefix(taxon.rank) {
+                previous_ids.insert(format!("{}{}", prefix, taxon.name), taxon.id);
+            }
+        }
+        if parents.len() + used.len() >= ID_SPACE {
+            bail!("Too many taxa for the synthetic IDs");
+        }
+
+        let mut ids = HashMap::new();
+        for &name in parents.keys() {
+            if let Some(&id) = previous_ids.get(name) {
+                ids.insert(name.to_string(), id);
+            }
+        }
+        for &name in parents.keys() {
+            if ids.contains_key(name) {
+                continue;
+            }
+            let mut id = synthetic_id(name);
+            while used.contains(&id) {
+                id = if id + 1 == ID_SPACE { 2 } else { id + 1 };
+            }
+            used.insert(id);
+            ids.insert(name.to_string(), id);
+        }
+
+        let mut taxa = vec![Taxon::from_static(ROOT, "root", Rank::NoRank, ROOT, true)];
+        for (&name, parent) in parents.iter() {
+            let (rank, short) = split_name(name)?;
+            let parent = parent.map(|parent| ids[parent]).unwrap_or(ROOT);
+            taxa.push(Taxon::new(ids[name], short.to_string(), rank, parent, true));
+        }
+        taxa.sort_by_key(|taxon| taxon.id);
+        Ok(Gtdb { taxa, ids })
+    }
+
+    /// The ID of a taxon, given by its name with the rank prefix, such as `g__Escherichia`.
+    pub fn id(&self, name: &str) -> Option<TaxonId> {
+        self.ids.get(name.trim()).cloned()
+    }
+
+    /// The ID of the most specific taxon in a lineage.
+    pub fn lineage_id(&self, lineage: &str) -> Option<TaxonId> {
+        lineage
+            .split(';')
+            .map(str::trim)
+            .rfind(|name| name.len() > 3)
+            .map_or(Some(ROOT), |name| self.id(name))
+    }
+
+    /// Maps NCBI taxon IDs onto GTDB taxa. The NCBI taxon of each genome is mapped onto the lowest
+    /// common ancestor of the GTDB taxa of its genomes. If the NCBI taxonomy is given, the
+    /// ancestors of these taxa are mapped as well, onto the lowest common ancestor of the GTDB
+    /// taxa of their descendants.
+    pub fn ncbi_mapping(
+        &self,
+        genomes: &[Genome],
+        ncbi: Option<&TaxonList>,
+    ) -> HashMap<TaxonId, TaxonId> {
+        let mut mapping = HashMap::new();
+        for genome in genomes {
+            if let (Some(taxon), Some(gtdb)) = (genome.ncbi_taxon, self.lineage_id(&genome.lineage))
+            {
+                let combined = match mapping.get(&taxon) {
+                    Some(&other) => self.lca(other, gtdb),
+                    None => gtdb,
+                };
+                mapping.insert(taxon, combined);
+            }
+        }
+
+        if let Some(ncbi) = ncbi {
+            let direct = mapping.keys().cloned().collect::<Vec<_>>();
+            for taxon in direct {
+                let mut current = taxon;
+                let mut combined = mapping[&taxon];
+                while let Some(parent) = ncbi.get(current).map(|t| t.parent) {
+                    if parent == current {
+                        break;
+                    }
+                    current = parent;
+                    if let Some(&other) = mapping.get(&current) {
+                        // The ancestors already include this taxon if it didn't change.
+                        if self.lca(other, combined) == other {
+                            break;
+                        }
+                        combined = self.lca(other, combined);
+                    }
+                    mapping.insert(current, combined);
+                }
+            }
+        }
+        mapping
+    }
+
+    // The lowest common ancestor of two taxa in this taxonomy.
+    fn lca(&self, left: TaxonId, right: TaxonId) -> TaxonId {
+        let ancestors = self.ancestors(left);
+        self.ancestors(right)
+            .into_iter()
+            .find(|id| ancestors.contains(id))
+            .unwrap_or(ROOT)
+    }
+
+    // The given taxon and its ancestors, up to the root.
+    fn ancestors(&self, taxon: TaxonId) -> Vec<TaxonId> {
+        let mut ancestors = vec![taxon];
+        let mut current = taxon;
+        while let Ok(index) = self.taxa.binary_search_by_key(&current, |taxon| taxon.id) {
+            let parent = self.taxa[index].parent;
+            if parent == current {
+                break;
+            }
+            ancestors.push(parent);
+            current = parent;
+        }
+        ancestors
+    }
+}
+
+// Splits the name of a taxon in a GTDB lineage into its rank and the name without prefix.
+fn split_name(name: &str) -> Result<(Rank, &str)> {
+    PREFIXES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|&(prefix, rank)| (rank, &name[prefix.len()..]))
+        .ok_or_else(|| ErrorKind::UnknownPrefix(name.to_string()).into())
+}
+
+// The prefix of a rank in GTDB lineages. Taxonomies built before domains were a rank of their own
+// have their domains as superkingdoms.
+fn prefix(rank: Rank) -> Option<&'static str> {
+    match rank {
+        Rank::Superkingdom => Some("d__"),
+        _ => PREFIXES
+            .iter()
+            .find(|&&(_, other)| other == rank)
+            .map(|&(prefix, _)| prefix),
+    }
+}
+
+// The ID of a taxon before collisions are resolved: the FNV-1a hash of its name, reduced to the
+// range of synthetic IDs (skipping 0 and the root).
+fn synthetic_id(name: &str) -> TaxonId {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    2 + (hash % (ID_SPACE - 2) as u64) as TaxonId
+}
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+        ParseInt(ParseIntError) #[doc = "Indicates failure to parse a taxon ID"];
+    }
+    errors {
+        /// A malformed line, with its line number
+        Malformed(line: usize, message: String) {
+            description("Malformed GTDB file")
+            display("Line {}: {}", line, message)
+        }
+        /// A taxon in a lineage without a known rank prefix
+        UnknownPrefix(name: String) {
+            description("Unknown rank prefix")
+            display("Unknown rank prefix in {}", name)
+        }
+        /// A taxon with different parents in different lineages
+        Inconsistent(name: String) {
+            description("Inconsistent lineages")
+            display("Taxon {} has different parents in different lineages", name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAXONOMY: &[u8] = b"RS_GCF_000005845.2\td__Bacteria;p__Proteobacteria;\
+        c__Gammaproteobacteria;o__Enterobacterales;f__Enterobacteriaceae;g__Escherichia;\
+        s__Escherichia coli\n\
+        RS_GCF_000006945.2\td__Bacteria;p__Proteobacteria;c__Gammaproteobacteria;\
+        o__Enterobacterales;f__Enterobacteriaceae;g__Salmonella;s__Salmonella enterica\n";
+
+    const METADATA: &[u8] = b"accession\tcheckm_completeness\tgtdb_taxonomy\tncbi_taxid\n\
+        RS_GCF_000005845.2\t100\td__Bacteria;p__Proteobacteria;c__Gammaproteobacteria;\
+        o__Enterobacterales;f__Enterobacteriaceae;g__Escherichia;s__Escherichia coli\t511145\n\
+        RS_GCF_000006945.2\t99.9\td__Bacteria;p__Proteobacteria;c__Gammaproteobacteria;\
+        o__Enterobacterales;f__Enterobacteriaceae;g__Salmonella;s__Salmonella enterica\t99287\n\
+        RS_GCF_000009045.1\t99.4\td__Bacteria;p__Firmicutes;c__Bacilli;o__Bacillales;\
+        f__Bacillaceae;g__Bacillus;s__Bacillus subtilis\t224308\n\
+        RS_GCF_000008865.2\t100\td__Bacteria;p__Proteobacteria;c__Gammaproteobacteria;\
+        o__Enterobacterales;f__Enterobacteriaceae;g__Escherichia;s__Escherichia coli\t386585\n";
+
+    #[test]
+    fn test_read_genomes() {
+        let genomes = read_genomes(TAXONOMY).unwrap();
+        assert_eq!(2, genomes.len());
+        assert_eq!("RS_GCF_000005845.2", genomes[0].accession);
+        assert!(genomes[0].lineage.ends_with("s__Escherichia coli"));
+        assert_eq!(None, genomes[0].ncbi_taxon);
+
+        let genomes = read_genomes(METADATA).unwrap();
+        assert_eq!(4, genomes.len());
+        assert_eq!("RS_GCF_000006945.2", genomes[1].accession);
+        assert!(genomes[1].lineage.ends_with("s__Salmonella enterica"));
+        assert_eq!(Some(99287), genomes[1].ncbi_taxon);
+
+        assert_matches!(
+            *read_genomes(&b"GCF_1\n"[..]).unwrap_err().kind(),
+            ErrorKind::Malformed(1, _)
+        );
+    }
+
+    #[test]
+    fn test_taxonomy() {
+        let gtdb = Gtdb::new(&read_genomes(TAXONOMY).unwrap()).unwrap();
+        // The root, 5 shared taxa, and 2 genera and species
+        assert_eq!(10, gtdb.taxa.len());
+        assert!(gtdb.taxa.windows(2).all(|w| w[0].id < w[1].id));
+
+        let id = |name| gtdb.id(name).unwrap();
+        assert_eq!(synthetic_id("g__Escherichia"), id("g__Escherichia"));
+        let taxon = |id| gtdb.taxa.iter().find(|t| t.id == id).unwrap();
+        let coli = taxon(id("s__Escherichia coli"));
+        assert_eq!("Escherichia coli", coli.name);
+        assert_eq!(Rank::Species, coli.rank);
+        assert_eq!(id("g__Escherichia"), coli.parent);
+        assert_eq!(ROOT, taxon(id("d__Bacteria")).parent);
+        assert_eq!(Rank::Domain, taxon(id("d__Bacteria")).rank);
+
+        assert_eq!(
+            Some(coli.id),
+            gtdb.lineage_id("d__Bacteria;g__;s__Escherichia coli")
+        );
+        assert_eq!(
+            Some(id("d__Bacteria")),
+            gtdb.lineage_id("d__Bacteria;p__;s__")
+        );
+        assert_eq!(None, gtdb.lineage_id("d__Archaea"));
+        assert_eq!(
+            id("f__Enterobacteriaceae"),
+            gtdb.lca(coli.id, id("g__Salmonella"))
+        );
+
+        // The IDs don't depend on the other taxa
+        let other = Gtdb::new(&read_genomes(TAXONOMY).unwrap()[..1]).unwrap();
+        assert_eq!(
+            gtdb.id("s__Escherichia coli"),
+            other.id("s__Escherichia coli")
+        );
+    }
+
+    #[test]
+    fn test_previous() {
+        let genomes = read_genomes(TAXONOMY).unwrap();
+        let salmonella = synthetic_id("g__Salmonella");
+        let coli = synthetic_id("s__Escherichia coli");
+        // Escherichia had the ID Salmonella hashes to, and a removed species the one of E. coli
+        let previous = vec![
+            Taxon::from_static(ROOT, "root", Rank::NoRank, ROOT, true),
+            Taxon::from_static(salmonella, "Escherichia", Rank::Genus, ROOT, true),
+            Taxon::from_static(coli, "E. albertii", Rank::Species, salmonella, true),
+            Taxon::from_static(77, "Bacteria", Rank::Superkingdom, ROOT, true),
+        ];
+        let gtdb = Gtdb::with_previous(&genomes, &previous).unwrap();
+        assert_eq!(Some(salmonella), gtdb.id("g__Escherichia"));
+        assert_eq!(Some(77), gtdb.id("d__Bacteria"));
+        assert_ne!(Some(salmonella), gtdb.id("g__Salmonella"));
+        assert_ne!(Some(coli), gtdb.id("s__Escherichia coli"));
+        assert_eq!(
+            synthetic_id("f__Enterobacteriaceae"),
+            gtdb.id("f__Enterobacteriaceae").unwrap()
+        );
+        assert_eq!(10, gtdb.taxa.len());
+    }
+
+    #[test]
+    fn test_inconsistent() {
+        let genomes = vec![
+            Genome {
+                accession: "a".into(),
+                lineage: "d__Bacteria;p__Firmicutes".into(),
+                ncbi_taxon: None,
+            },
+            Genome {
+                accession: "b".into(),
+                lineage: "d__Archaea;p__Firmicutes".into(),
+                ncbi_taxon: None,
+            },
+        ];
+        assert_matches!(
+            *Gtdb::new(&genomes).unwrap_err().kind(),
+            ErrorKind::Inconsistent(_)
+        );
+        let genomes = vec![Genome {
+            accession: "a".into(),
+            lineage: "x__Bacteria".into(),
+            ncbi_taxon: None,
+        }];
+        assert_matches!(
+            *Gtdb::new(&genomes).unwrap_err().kind(),
+            ErrorKind::UnknownPrefix(_)
+        );
+    }
+
+    #[test]
+    fn test_ncbi_mapping() {
+        let genomes = read_genomes(METADATA).unwrap();
+        let gtdb = Gtdb::new(&genomes).unwrap();
+        let id = |name| gtdb.id(name).unwrap();
+
+        let mapping = gtdb.ncbi_mapping(&genomes, None);
+        assert_eq!(4, mapping.len());
+        assert_eq!(id("s__Escherichia coli"), mapping[&511_145]);
+        assert_eq!(id("s__Bacillus subtilis"), mapping[&224_308]);
+
+        let ncbi = TaxonList::new(vec![
+            Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+            Taxon::from_static(2, "Bacteria", Rank::Superkingdom, 1, true),
+            Taxon::from_static(561, "Escherichia", Rank::Genus, 2, true),
+            Taxon::from_static(562, "Escherichia coli", Rank::Species, 561, true),
+            Taxon::from_static(1386, "Bacillus", Rank::Genus, 2, true),
+            Taxon::from_static(1423, "Bacillus subtilis", Rank::Species, 1386, true),
+            Taxon::from_static(99287, "Salmonella enterica LT2", Rank::Strain, 2, true),
+            Taxon::from_static(224_308, "Bacillus subtilis 168", Rank::Strain, 1423, true),
+            Taxon::from_static(386_585, "Escherichia coli O157:H7", Rank::Strain, 562, true),
+            Taxon::from_static(511_145, "Escherichia coli K-12", Rank::Strain, 562, true),
+        ])
+        .unwrap();
+        let mapping = gtdb.ncbi_mapping(&genomes, Some(&ncbi));
+        assert_eq!(id("s__Escherichia coli"), mapping[&562]);
+        assert_eq!(id("s__Escherichia coli"), mapping[&561]);
+        assert_eq!(id("s__Bacillus subtilis"), mapping[&1386]);
+        assert_eq!(
+            id("f__Enterobacteriaceae"),
+            gtdb.lca(mapping[&561], mapping[&99287])
+        );
+        assert_eq!(id("d__Bacteria"), mapping[&2]);
+        assert_eq!(id("d__Bacteria"), mapping[&1]);
+    }
+
+    #[test]
+    fn test_read_mapping() {
+        let mapping = read_mapping(&b"562\t4182\n2\t77\n"[..]).unwrap();
+        assert_eq!(Some(&4182), mapping.get(&562));
+        assert_eq!(Some(&77), mapping.get(&2));
+        assert_matches!(
+            *read_mapping(&b"562\n"[..]).unwrap_err().kind(),
+            ErrorKind::Malformed(1, _)
+        );
+    }
+}
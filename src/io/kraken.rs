@@ -0,0 +1,87 @@
+//! Writes per-read classifications in the output format of
+//! [Kraken](https://github.com/DerrickWood/kraken2/wiki/Manual).
+
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::taxon::TaxonId;
+
+/// The classification of a read, formatted as a line of Kraken output with the tab-separated
+/// fields `C` (classified) or `U` (unclassified), the read name, the taxon ID (0 if unclassified),
+/// the number of taxon IDs the read was classified from and their trace.
+///
+/// Kraken's fourth field is the length of the read in bases. The taxon IDs of a read don't tell
+/// its length, so the number of taxon IDs (the k-mers or peptides found in the read) is given
+/// instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification<'a> {
+    /// The name of the read
+    pub read: &'a str,
+    /// The taxon the read is assigned to, if it's classified
+    pub taxon: Option<TaxonId>,
+    /// The taxon IDs the read was classified from, in order
+    pub taxa: &'a [TaxonId],
+}
+
+impl<'a> fmt::Display for Classification<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            if self.taxon.is_some() { "C" } else { "U" },
+            self.read,
+            self.taxon.unwrap_or(0),
+            self.taxa.len(),
+            trace(self.taxa)
+        )
+    }
+}
+
+/// Groups runs of equal taxon IDs as `taxon:count`, separated by spaces, like the k-mer hits in
+/// Kraken output.
+pub fn trace(taxa: &[TaxonId]) -> String {
+    let mut runs: Vec<(TaxonId, usize)> = Vec::new();
+    for &taxon in taxa {
+        match runs.last_mut() {
+            Some((last, count)) if *last == taxon => *count += 1,
+            _ => runs.push((taxon, 1)),
+        }
+    }
+    runs.iter()
+        .map(|(taxon, count)| format!("{}:{}", taxon, count))
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAXA: &[TaxonId] = &[571525, 571525, 6920, 6920, 1, 6920];
+
+    #[test]
+    fn test_trace() {
+        assert_eq!("571525:2 6920:2 1:1 6920:1", trace(TAXA));
+        assert_eq!("0:3", trace(&[0, 0, 0]));
+        assert_eq!("", trace(&[]));
+    }
+
+    #[test]
+    fn test_classification() {
+        let classified = Classification {
+            read: "read1 sample=A",
+            taxon: Some(571525),
+            taxa: TAXA,
+        };
+        assert_eq!(
+            "C\tread1 sample=A\t571525\t6\t571525:2 6920:2 1:1 6920:1",
+            classified.to_string()
+        );
+        let unclassified = Classification {
+            read: "read2",
+            taxon: None,
+            taxa: &[0, 0],
+        };
+        assert_eq!("U\tread2\t0\t2\t0:2", unclassified.to_string());
+    }
+}
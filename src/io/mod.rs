@@ -1,4 +1,11 @@
 //! I/O operations to different formats.
 
+pub mod biom;
+pub mod compression;
 pub mod fasta;
 pub mod fastq;
+pub mod gtdb;
+pub mod kraken;
+pub mod sam;
+pub mod taxcache;
+pub mod taxdump;
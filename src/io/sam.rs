@@ -0,0 +1,588 @@
+//! Reads unaligned reads from [SAM and BAM](https://samtools.github.io/hts-specs/SAMv1.pdf) files.
+//!
+//! Sequencers increasingly deliver reads as unaligned BAM files instead of FASTQ. This module
+//! turns their records into FASTQ [Records](../fastq/struct.Record.html), so they can be used
+//! wherever FASTQ input is. BAM files are BGZF compressed, which is a series of gzip blocks, so
+//! they should be decompressed with [compression](../compression/index.html) first.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::num::ParseIntError;
+use std::rc::Rc;
+
+use crate::dna::merge::reverse_complement;
+use crate::io::fasta::utf8;
+use crate::io::fastq;
+
+/// The read is paired in sequencing.
+pub const PAIRED: u16 = 0x1;
+/// The read is unmapped.
+pub const UNMAPPED: u16 = 0x4;
+/// The sequence is reverse complemented.
+pub const REVERSE: u16 = 0x10;
+/// The read is the first mate of a pair.
+pub const FIRST: u16 = 0x40;
+/// The read is the last mate of a pair.
+pub const LAST: u16 = 0x80;
+/// The alignment is secondary.
+pub const SECONDARY: u16 = 0x100;
+/// The read fails quality checks.
+pub const QC_FAIL: u16 = 0x200;
+/// The read is a PCR or optical duplicate.
+pub const DUPLICATE: u16 = 0x400;
+/// The alignment is supplementary.
+pub const SUPPLEMENTARY: u16 = 0x800;
+
+const BAM_MAGIC: &[u8] = b"BAM\x01";
+const BAM_BASES: &[u8] = b"=ACMGRSVTWYHKDBN";
+const MISSING_QUALITY: u8 = 0xff;
+const PHRED_OFFSET: u8 = 33;
+const MAX_SCORE: u8 = 93;
+// The fixed-size fields of a BAM record, before its read name.
+const BAM_FIXED_LENGTH: usize = 32;
+// Larger records are taken to be corrupt rather than allocated.
+const MAX_BAM_RECORD: usize = 1 << 28;
+
+/// Parses SAM flags, given in decimal or hexadecimal (with a `0x` prefix).
+pub fn parse_flags(flags: &str) -> std::result::Result<u16, ParseIntError> {
+    match flags
+        .strip_prefix("0x")
+        .or_else(|| flags.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => flags.parse(),
+    }
+}
+
+/// Selects records by their flags, like the `-f` and `-F` options of `samtools view`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    /// All of these flags must be set.
+    pub required: u16,
+    /// None of these flags may be set.
+    pub excluded: u16,
+}
+
+impl Default for Flags {
+    /// Excludes secondary and supplementary alignments, which would duplicate reads.
+    fn default() -> Self {
+        Flags {
+            required: 0,
+            excluded: SECONDARY | SUPPLEMENTARY,
+        }
+    }
+}
+
+impl Flags {
+    /// Whether a record with the given flags is selected.
+    pub fn accepts(&self, flag: u16) -> bool {
+        flag & self.required == self.required && flag & self.excluded == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Sam,
+    Bam,
+}
+
+/// Reads the records of a SAM or BAM file as FASTQ records. The format is detected from the magic
+/// bytes of the (decompressed) input.
+///
+/// Mates of a pair get a `/1` or `/2` suffix on their name. Reads stored reverse complemented are
+/// turned back into the read as it was sequenced. Missing quality scores are read as score 0.
+pub struct Reader<R: Read> {
+    reader: BufReader<R>,
+    format: Format,
+    flags: Flags,
+    buffer: Vec<u8>,
+    record: usize,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a Reader from the given Read (e.g. a decompressed file), selecting records with
+    /// the given flags. The header of a BAM file is read immediately.
+    pub fn new(readable: R, flags: Flags) -> Result<Self> {
+        let mut reader = BufReader::new(readable);
+        let format = if reader.fill_buf()?.starts_with(BAM_MAGIC) {
+            Format::Bam
+        } else {
+            Format::Sam
+        };
+        let mut reader = Reader {
+            reader,
+            format,
+            flags,
+            buffer: Vec::new(),
+            record: 0,
+        };
+        if format == Format::Bam {
+            reader.read_bam_header()?;
+        }
+        Ok(reader)
+    }
+
+    fn error(&self, message: &str) -> Error {
+        ErrorKind::Malformed(self.record, message.to_string()).into()
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        read_exact(&mut self.reader, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn skip(&mut self, length: u32) -> Result<()> {
+        let skipped = io::copy(
+            &mut self.reader.by_ref().take(u64::from(length)),
+            &mut io::sink(),
+        )?;
+        if skipped < u64::from(length) {
+            bail!(ErrorKind::Truncated);
+        }
+        Ok(())
+    }
+
+    fn read_bam_header(&mut self) -> Result<()> {
+        self.skip(BAM_MAGIC.len() as u32)?;
+        let text_length = self.read_u32()?;
+        self.skip(text_length)?;
+        let references = self.read_u32()?;
+        for _ in 0..references {
+            let name_length = self.read_u32()?;
+            // The name is followed by the length of the reference.
+            self.skip(name_length)?;
+            self.skip(4)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next selected record, if any.
+    pub fn read_record(&mut self) -> Result<Option<fastq::Record>> {
+        Ok(self.read_flagged()?.map(|(_, record)| record))
+    }
+
+    // Reads the next selected record with its flags, if any.
+    fn read_flagged(&mut self) -> Result<Option<(u16, fastq::Record)>> {
+        loop {
+            let record = match self.format {
+                Format::Sam => self.read_sam_record()?,
+                Format::Bam => self.read_bam_record()?,
+            };
+            match record {
+                None => return Ok(None),
+                Some((flag, record)) if self.flags.accepts(flag) => {
+                    return Ok(Some((flag, record)))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn read_sam_record(&mut self) -> Result<Option<(u16, fastq::Record)>> {
+        loop {
+            self.buffer.clear();
+            if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+                return Ok(None);
+            }
+            while self.buffer.last() == Some(&b'\n') || self.buffer.last() == Some(&b'\r') {
+                self.buffer.pop();
+            }
+            if !self.buffer.is_empty() && self.buffer[0] != b'@' {
+                break;
+            }
+        }
+        self.record += 1;
+
+        let fields = self.buffer.split(|&b| b == b'\t').collect::<Vec<_>>();
+        if fields.len() < 11 {
+            return Err(self.error("expected at least 11 fields"));
+        }
+        let flag = utf8(fields[1])?
+            .parse::<u16>()
+            .map_err(|_| self.error("invalid flag"))?;
+        let sequence = if fields[9] == b"*" {
+            Vec::new()
+        } else {
+            fields[9].to_vec()
+        };
+        let quality = if fields[10] == b"*" {
+            vec![MISSING_QUALITY; sequence.len()]
+        } else if fields[10].len() != sequence.len() {
+            return Err(self.error("quality and sequence lengths differ"));
+        } else {
+            fields[10]
+                .iter()
+                .map(|q| q.saturating_sub(PHRED_OFFSET))
+                .collect()
+        };
+        let record = to_record(utf8(fields[0])?, flag, sequence, quality);
+        Ok(Some((flag, record)))
+    }
+
+    fn read_bam_record(&mut self) -> Result<Option<(u16, fastq::Record)>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        self.record += 1;
+        let block_size = self.read_u32()? as usize;
+        if block_size < BAM_FIXED_LENGTH {
+            return Err(self.error("record too short"));
+        }
+        if block_size > MAX_BAM_RECORD {
+            return Err(self.error("record too long"));
+        }
+        self.buffer.resize(block_size, 0);
+        read_exact(&mut self.reader, &mut self.buffer)?;
+
+        let block = &self.buffer;
+        let u16_at = |i: usize| u16::from_le_bytes([block[i], block[i + 1]]);
+        let name_length = block[8] as usize;
+        let cigar_length = u16_at(12) as usize;
+        let flag = u16_at(14);
+        let length = u32::from_le_bytes([block[16], block[17], block[18], block[19]]) as usize;
+
+        let name_start = BAM_FIXED_LENGTH;
+        let sequence_start = name_start + name_length + 4 * cigar_length;
+        // Two bases are packed in a byte.
+        let quality_start = sequence_start + length / 2 + length % 2;
+        // Checking the length first keeps the sum from overflowing.
+        if name_length == 0 || length > block.len() || quality_start + length > block.len() {
+            return Err(self.error("record too short"));
+        }
+        // The read name is terminated by a NUL character.
+        let name = utf8(&block[name_start..name_start + name_length - 1])?;
+        let sequence = (0..length)
+            .map(|i| {
+                let packed = block[sequence_start + i / 2];
+                let code = if i % 2 == 0 {
+                    packed >> 4
+                } else {
+                    packed & 0xf
+                };
+                BAM_BASES[code as usize]
+            })
+            .collect::<Vec<u8>>();
+        let quality = block[quality_start..quality_start + length].to_vec();
+        Ok(Some((flag, to_record(name, flag, sequence, quality))))
+    }
+
+    /// Returns a Records struct with itself as its reader.
+    pub fn records(self) -> Records<R> {
+        Records { reader: self }
+    }
+
+    /// Splits the selected records into the first and the last mates of the pairs, to iterate over
+    /// them side by side. The input is read only once: records are kept until the other side asks
+    /// for them, so the mates should be close together in the input. Records that are neither a
+    /// first nor a last mate are skipped.
+    pub fn split_mates(self) -> (Mates<R>, Mates<R>) {
+        let split = Rc::new(RefCell::new(Split {
+            reader: self,
+            queues: [VecDeque::new(), VecDeque::new()],
+        }));
+        (
+            Mates {
+                split: Rc::clone(&split),
+                side: 0,
+            },
+            Mates { split, side: 1 },
+        )
+    }
+}
+
+// Fills the buffer, failing with a Truncated error at the end of the input.
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<()> {
+    reader.read_exact(buffer).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            ErrorKind::Truncated.into()
+        } else {
+            err.into()
+        }
+    })
+}
+
+// Builds a FASTQ record from the fields of a SAM record, with the Phred scores of its bases.
+fn to_record(name: &str, flag: u16, mut sequence: Vec<u8>, mut scores: Vec<u8>) -> fastq::Record {
+    if flag & REVERSE != 0 {
+        sequence = reverse_complement(&sequence);
+        scores.reverse();
+    }
+    let header = if flag & PAIRED == 0 {
+        name.to_string()
+    } else if flag & FIRST != 0 {
+        format!("{}/1", name)
+    } else if flag & LAST != 0 {
+        format!("{}/2", name)
+    } else {
+        name.to_string()
+    };
+    fastq::Record {
+        header,
+        sequence: String::from_utf8_lossy(&sequence).into_owned(),
+        quality: scores
+            .into_iter()
+            .map(|score| {
+                let score = if score == MISSING_QUALITY { 0 } else { score };
+                (score.min(MAX_SCORE) + PHRED_OFFSET) as char
+            })
+            .collect(),
+    }
+}
+
+/// Convenience struct which allows for iteration (e.g. using for..in).
+pub struct Records<R: Read> {
+    reader: Reader<R>,
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Result<fastq::Record>;
+
+    fn next(&mut self) -> Option<Result<fastq::Record>> {
+        match self.reader.read_record() {
+            Ok(None) => None,
+            Ok(Some(record)) => Some(Ok(record)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+// The reader shared by both sides of a split, with the records read ahead for each side.
+struct Split<R: Read> {
+    reader: Reader<R>,
+    queues: [VecDeque<fastq::Record>; 2],
+}
+
+/// Iterates over the first or the last mates of a split Reader (see
+/// [split_mates](struct.Reader.html#method.split_mates)).
+pub struct Mates<R: Read> {
+    split: Rc<RefCell<Split<R>>>,
+    side: usize,
+}
+
+impl<R: Read> Iterator for Mates<R> {
+    type Item = Result<fastq::Record>;
+
+    fn next(&mut self) -> Option<Result<fastq::Record>> {
+        let split = &mut *self.split.borrow_mut();
+        loop {
+            if let Some(record) = split.queues[self.side].pop_front() {
+                return Some(Ok(record));
+            }
+            match split.reader.read_flagged() {
+                Ok(None) => return None,
+                Ok(Some((flag, record))) => match flag & (FIRST | LAST) {
+                    FIRST => split.queues[0].push_back(record),
+                    LAST => split.queues[1].push_back(record),
+                    _ => {}
+                },
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+    }
+    errors {
+        /// A malformed record, with its record number
+        Malformed(record: usize, message: String) {
+            description("Malformed SAM/BAM record")
+            display("SAM/BAM record {}: {}", record, message)
+        }
+        /// The input ended in the middle of a record or header
+        Truncated {
+            description("Unexpected end of input")
+            display("Unexpected end of input")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::io::compression;
+
+    const SAM: &[u8] = b"@HD\tVN:1.6\tSO:unsorted\n\
+        @RG\tID:A\n\
+        read1\t77\t*\t0\t0\t*\t*\t0\t0\tGATTACA\tIIIII##\tRG:Z:A\n\
+        read1\t141\t*\t0\t0\t*\t*\t0\t0\tTGTAATC\t*\n\
+        read2\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t!!I?\n\
+        read2\t2308\t*\t0\t0\t*\t*\t0\t0\tACGT\t!!I?\n\
+        read3\t20\t*\t0\t0\t*\t*\t0\t0\tAACG\t#+5?\n";
+
+    fn records<R: Read>(reader: R, flags: Flags) -> Vec<(String, String, String)> {
+        Reader::new(reader, flags)
+            .unwrap()
+            .records()
+            .map(|record| {
+                let record = record.unwrap();
+                (record.header, record.sequence, record.quality)
+            })
+            .collect()
+    }
+
+    fn record(header: &str, sequence: &str, quality: &str) -> (String, String, String) {
+        (
+            header.to_string(),
+            sequence.to_string(),
+            quality.to_string(),
+        )
+    }
+
+    fn bam_record(name: &str, flag: u16, sequence: &[u8], scores: &[u8]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend_from_slice(&(-1_i32).to_le_bytes());
+        block.extend_from_slice(&(-1_i32).to_le_bytes());
+        block.push(name.len() as u8 + 1);
+        block.push(0);
+        block.extend_from_slice(&4680_u16.to_le_bytes());
+        block.extend_from_slice(&0_u16.to_le_bytes());
+        block.extend_from_slice(&flag.to_le_bytes());
+        block.extend_from_slice(&(sequence.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(-1_i32).to_le_bytes());
+        block.extend_from_slice(&(-1_i32).to_le_bytes());
+        block.extend_from_slice(&0_i32.to_le_bytes());
+        block.extend_from_slice(name.as_bytes());
+        block.push(0);
+        for pair in sequence.chunks(2) {
+            let code = |b: u8| BAM_BASES.iter().position(|&c| c == b).unwrap() as u8;
+            block.push((code(pair[0]) << 4) | pair.get(1).map_or(0, |&b| code(b)));
+        }
+        block.extend_from_slice(scores);
+        block.extend_from_slice(b"RGZA\0");
+        let mut bytes = (block.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(block);
+        bytes
+    }
+
+    #[test]
+    fn test_sam() {
+        assert_eq!(
+            vec![
+                record("read1/1", "GATTACA", "IIIII##"),
+                record("read1/2", "TGTAATC", "!!!!!!!"),
+                record("read2", "ACGT", "!!I?"),
+                record("read3", "CGTT", "?5+#"),
+            ],
+            records(SAM, Flags::default())
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let first = Flags {
+            required: FIRST,
+            ..Flags::default()
+        };
+        assert_eq!(
+            vec![record("read1/1", "GATTACA", "IIIII##")],
+            records(SAM, first)
+        );
+        let all = Flags {
+            required: 0,
+            excluded: 0,
+        };
+        assert_eq!(5, records(SAM, all).len());
+        assert_eq!(Ok(0x900), parse_flags("0x900"));
+        assert_eq!(Ok(2304), parse_flags("2304"));
+    }
+
+    #[test]
+    fn test_split_mates() {
+        let sam = [SAM, b"read4\t73\t*\t0\t0\t*\t*\t0\t0\tAC\tII\n"].concat();
+        let (first, last) = Reader::new(&sam[..], Flags::default())
+            .unwrap()
+            .split_mates();
+        let headers = |mates: Mates<&[u8]>| {
+            mates
+                .map(|record| record.unwrap().header)
+                .collect::<Vec<_>>()
+        };
+        // The first mates are read to the end before the last ones, which are kept meanwhile
+        assert_eq!(vec!["read1/1", "read4/1"], headers(first));
+        assert_eq!(vec!["read1/2"], headers(last));
+    }
+
+    #[test]
+    fn test_bam() {
+        let mut bam = BAM_MAGIC.to_vec();
+        let text = b"@HD\tVN:1.6\n";
+        bam.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        bam.extend_from_slice(text);
+        bam.extend_from_slice(&1_u32.to_le_bytes());
+        bam.extend_from_slice(&5_u32.to_le_bytes());
+        bam.extend_from_slice(b"chr1\0");
+        bam.extend_from_slice(&1000_u32.to_le_bytes());
+        bam.extend(bam_record(
+            "read1",
+            77,
+            b"GATTACA",
+            &[40, 40, 40, 40, 40, 2, 2],
+        ));
+        bam.extend(bam_record("read1", 141, b"TGTAATC", &[0xff; 7]));
+        bam.extend(bam_record("read2", 20, b"AACG", &[2, 10, 20, 30]));
+
+        // BGZF is gzip compressed, so it is decompressed like any other gzip file.
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&bam).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            vec![
+                record("read1/1", "GATTACA", "IIIII##"),
+                record("read1/2", "TGTAATC", "!!!!!!!"),
+                record("read2", "CGTT", "?5+#"),
+            ],
+            records(
+                compression::decompress(&compressed[..]).unwrap(),
+                Flags::default()
+            )
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut reader = Reader::new(&b"read1\t4\t*\t0\n"[..], Flags::default()).unwrap();
+        assert_matches!(
+            reader.read_record(),
+            Err(Error(ErrorKind::Malformed(1, _), _))
+        );
+        let mut bam = BAM_MAGIC.to_vec();
+        bam.extend_from_slice(&[0; 8]);
+        bam.extend_from_slice(&100_u32.to_le_bytes());
+        bam.extend_from_slice(&[0; 10]);
+        let mut reader = Reader::new(&bam[..], Flags::default()).unwrap();
+        assert_matches!(reader.read_record(), Err(Error(ErrorKind::Truncated, _)));
+
+        // Corrupt block sizes are rejected before reading the record.
+        for &block_size in &[31_u32, u32::MAX] {
+            let mut bam = BAM_MAGIC.to_vec();
+            bam.extend_from_slice(&[0; 8]);
+            bam.extend_from_slice(&block_size.to_le_bytes());
+            let mut reader = Reader::new(&bam[..], Flags::default()).unwrap();
+            assert_matches!(
+                reader.read_record(),
+                Err(Error(ErrorKind::Malformed(1, _), _))
+            );
+        }
+
+        // A corrupt reference name length runs into the end of the header.
+        let mut bam = BAM_MAGIC.to_vec();
+        bam.extend_from_slice(&0_u32.to_le_bytes());
+        bam.extend_from_slice(&1_u32.to_le_bytes());
+        bam.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_matches!(
+            Reader::new(&bam[..], Flags::default()).err(),
+            Some(Error(ErrorKind::Truncated, _))
+        );
+    }
+}
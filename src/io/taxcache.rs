@@ -0,0 +1,566 @@
+//! A binary taxonomy format, which is memory-mapped instead of parsed.
+//!
+//! Parsing a taxonomy TSV-file and building its taxon tree take most of the running time of the
+//! commands on small inputs. A taxonomy cache contains the same taxa in arrays indexed by taxon ID,
+//! along with the children of each taxon, the Euler tour through the taxon tree and the depth of
+//! each step. The [TaxonTree](../../taxon/struct.TaxonTree.html) and
+//! [TaxonList](../../taxon/struct.TaxonList.html) of a cache read from these arrays directly, so
+//! the taxonomy can be used as soon as it's mapped into memory.
+//!
+//! All numbers are stored in little-endian byte order, and each array starts at a multiple of 8
+//! bytes. The IDs range from 0 up to the highest taxon ID.
+//!
+//! | Contents                                              | Type                      |
+//! |-------------------------------------------------------|---------------------------|
+//! | Magic bytes `UMGAPTAX`                                | 8 bytes                   |
+//! | Version, number of IDs, root, Euler tour length       | `u32` each                |
+//! | Parents                                               | `u32` per ID              |
+//! | Ranks (the index of the rank)                         | `u8` per ID               |
+//! | Validity (0 or 1)                                     | `u8` per ID               |
+//! | Offsets of the names, and the end of the last name    | `u64` per ID, plus one    |
+//! | Names                                                 | UTF-8                     |
+//! | Offsets of the children, and the end of the last ones | `u32` per ID, plus one    |
+//! | Children, in the order of the taxonomy file           | `u32` per child           |
+//! | Euler tour                                            | `u32` per step            |
+//! | Depths                                                | `u32` per step            |
+//!
+//! Missing IDs have parent `u32::MAX`, rank 255, an empty name and no children.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::str;
+
+use fst::raw::MmapReadOnly;
+use strum::IntoEnumIterator;
+
+use crate::rank::Rank;
+use crate::taxon;
+use crate::taxon::Depth;
+use crate::taxon::Taxon;
+use crate::taxon::TaxonId;
+use crate::taxon::TaxonRef;
+use crate::taxon::TaxonTree;
+
+/// The magic bytes a taxonomy cache starts with.
+pub const MAGIC: &[u8] = b"UMGAPTAX";
+
+/// The ranks are stored by their index, so this changes whenever the ranks or the layout do.
+const VERSION: u32 = 3;
+const HEADER_LENGTH: usize = 24;
+const MISSING_PARENT: u32 = u32::MAX;
+const MISSING_RANK: u8 = 255;
+
+/// Whether the file at the given path is a taxonomy cache, judging by its magic bytes. Only
+/// regular files are read, as a cache is mapped into memory and other paths, such as pipes, can
+/// only be read once.
+pub fn is_cache<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    if !fs::metadata(&path)?.is_file() {
+        return Ok(false);
+    }
+    let mut magic = [0; 8];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(&magic[..read] == MAGIC)
+}
+
+/// Writes the taxonomy cache of the given taxa.
+pub fn write<W: Write>(taxa: &[Taxon], out: W) -> Result<()> {
+    let slots = taxa.iter().map(|t| t.id).max().map_or(0, |max| max + 1);
+    if slots > MISSING_PARENT as usize {
+        bail!(ErrorKind::Malformed(
+            "taxon IDs don't fit in 32 bits".into()
+        ));
+    }
+    let mut by_id: Vec<Option<&Taxon>> = vec![None; slots];
+    for taxon in taxa {
+        by_id[taxon.id] = Some(taxon);
+    }
+    let tree = TaxonTree::new(taxa)?;
+    let root = tree.root;
+    let children = (0..slots)
+        .map(|id| tree.children(id).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let euler = tree.into_iter().collect::<Vec<_>>();
+
+    let mut out = Sections { out, position: 0 };
+    out.write(MAGIC)?;
+    for &number in &[VERSION, slots as u32, root as u32, euler.len() as u32] {
+        out.write(&number.to_le_bytes())?;
+    }
+    for taxon in by_id.iter() {
+        let parent = taxon.map_or(MISSING_PARENT, |t| t.parent as u32);
+        out.write(&parent.to_le_bytes())?;
+    }
+    out.pad()?;
+    for taxon in by_id.iter() {
+        out.write(&[taxon.map_or(MISSING_RANK, |t| t.rank.index() as u8)])?;
+    }
+    out.pad()?;
+    for taxon in by_id.iter() {
+        out.write(&[taxon.map_or(0, |t| t.valid as u8)])?;
+    }
+    out.pad()?;
+    let mut offset = 0_u64;
+    for taxon in by_id.iter() {
+        out.write(&offset.to_le_bytes())?;
+        offset += taxon.map_or(0, |t| t.name.len()) as u64;
+    }
+    out.write(&offset.to_le_bytes())?;
+    for taxon in by_id.iter().flatten() {
+        out.write(taxon.name.as_bytes())?;
+    }
+    out.pad()?;
+    let mut offset = 0_u32;
+    for siblings in children.iter() {
+        out.write(&offset.to_le_bytes())?;
+        offset += siblings.len() as u32;
+    }
+    out.write(&offset.to_le_bytes())?;
+    out.pad()?;
+    for &child in children.iter().flatten() {
+        out.write(&(child as u32).to_le_bytes())?;
+    }
+    out.pad()?;
+    for &(taxon, _) in euler.iter() {
+        out.write(&(taxon as u32).to_le_bytes())?;
+    }
+    out.pad()?;
+    for &(_, depth) in euler.iter() {
+        out.write(&(depth as u32).to_le_bytes())?;
+    }
+    out.pad()?;
+    out.out.flush()?;
+    Ok(())
+}
+
+// A writer keeping track of its position, to align the arrays.
+struct Sections<W: Write> {
+    out: W,
+    position: usize,
+}
+
+impl<W: Write> Sections<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.position += bytes.len();
+        self.out.write_all(bytes)
+    }
+
+    fn pad(&mut self) -> io::Result<()> {
+        let padding = align(self.position) - self.position;
+        self.write(&[0; 8][..padding])
+    }
+}
+
+fn align(position: usize) -> usize {
+    (position + 7) & !7
+}
+
+// The end of an array of count numbers of the given size starting at start, if it ends within the
+// given length.
+fn end_of(start: usize, count: usize, size: usize, length: usize) -> Result<usize> {
+    count
+        .checked_mul(size)
+        .and_then(|size| size.checked_add(start))
+        .filter(|&end| end <= length)
+        .ok_or_else(truncated)
+}
+
+fn truncated() -> Error {
+    ErrorKind::Malformed("truncated".into()).into()
+}
+
+/// The bytes of a taxonomy cache.
+pub enum Data {
+    /// A memory-mapped file
+    Mapped(MmapReadOnly),
+    /// Bytes in memory
+    Owned(Vec<u8>),
+}
+
+impl AsRef<[u8]> for Data {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Data::Mapped(mmap) => mmap.as_slice(),
+            Data::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// A taxonomy cache, read directly from its bytes.
+pub struct TaxonomyCache {
+    data: Data,
+    rank_values: Vec<Rank>,
+    slots: usize,
+    root: TaxonId,
+    tour: usize,
+    ranks: usize,
+    valid: usize,
+    offsets: usize,
+    names: usize,
+    child_offsets: usize,
+    children: usize,
+    euler: usize,
+    depths: usize,
+}
+
+impl TaxonomyCache {
+    /// Maps the taxonomy cache at the given path into memory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        // The file shouldn't be modified while it's mapped, like the FST indices.
+        let mmap = unsafe { MmapReadOnly::open_path(path) }?;
+        TaxonomyCache::new(Data::Mapped(mmap))
+    }
+
+    /// Reads a taxonomy cache from its bytes, checking whether they're complete and well-formed,
+    /// so the taxa can be read without checking them again.
+    pub fn new(data: Data) -> Result<Self> {
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_LENGTH || &bytes[..MAGIC.len()] != MAGIC {
+            bail!(ErrorKind::Malformed("not a taxonomy cache".into()));
+        }
+        if read_u32(bytes, 8) != VERSION {
+            bail!(ErrorKind::Malformed("unsupported version".into()));
+        }
+        let slots = read_u32(bytes, 12) as usize;
+        let root = read_u32(bytes, 16) as TaxonId;
+        let tour = read_u32(bytes, 20) as usize;
+        let length = bytes.len();
+        let ranks = align(end_of(HEADER_LENGTH, slots, 4, length)?);
+        let valid = align(end_of(ranks, slots, 1, length)?);
+        let offsets = align(end_of(valid, slots, 1, length)?);
+        let names = end_of(offsets, slots + 1, 8, length)?;
+        let names_length = usize::try_from(read_u64(bytes, names - 8)).map_err(|_| truncated())?;
+        let child_offsets = align(end_of(names, names_length, 1, length)?);
+        let children = align(end_of(child_offsets, slots + 1, 4, length)?);
+        let child_count = read_u32(bytes, child_offsets + 4 * slots) as usize;
+        let euler = align(end_of(children, child_count, 4, length)?);
+        let depths = align(end_of(euler, tour, 4, length)?);
+        end_of(depths, tour, 4, length)?;
+        let cache = TaxonomyCache {
+            data,
+            rank_values: Rank::iter().collect(),
+            slots,
+            root,
+            tour,
+            ranks,
+            valid,
+            offsets,
+            names,
+            child_offsets,
+            children,
+            euler,
+            depths,
+        };
+        cache.check()?;
+        Ok(cache)
+    }
+
+    // Checks the parents, ranks, names, children and Euler tour, which are used without checking
+    // them.
+    fn check(&self) -> Result<()> {
+        let bytes = self.data.as_ref();
+        if self.slots > 0 && self.root >= self.slots {
+            bail!(ErrorKind::Malformed("root out of range".into()));
+        }
+        let names_length = read_u64(bytes, self.offsets + 8 * self.slots);
+        let child_count = read_u32(bytes, self.child_offsets + 4 * self.slots);
+        for id in 0..self.slots {
+            let parent = read_u32(bytes, HEADER_LENGTH + 4 * id);
+            if parent as usize >= self.slots && parent != MISSING_PARENT {
+                bail!(ErrorKind::Malformed(format!(
+                    "parent of taxon {} out of range",
+                    id
+                )));
+            }
+            let rank = bytes[self.ranks + id];
+            if rank as usize >= self.rank_values.len() && rank != MISSING_RANK {
+                bail!(ErrorKind::Malformed(format!(
+                    "unknown rank of taxon {}",
+                    id
+                )));
+            }
+            let start = read_u64(bytes, self.offsets + 8 * id);
+            let end = read_u64(bytes, self.offsets + 8 * (id + 1));
+            if start > end || end > names_length {
+                bail!(ErrorKind::Malformed(format!("name of taxon {}", id)));
+            }
+            // Both fit, as they're at most the length of the names checked by new
+            let (start, end) = (start as usize, end as usize);
+            str::from_utf8(&bytes[self.names + start..self.names + end])?;
+            let start = read_u32(bytes, self.child_offsets + 4 * id);
+            let end = read_u32(bytes, self.child_offsets + 4 * (id + 1));
+            if start > end || end > child_count {
+                bail!(ErrorKind::Malformed(format!("children of taxon {}", id)));
+            }
+        }
+        let children = (0..child_count as usize).map(|n| read_u32(bytes, self.children + 4 * n));
+        let tour = (0..self.tour).map(|step| read_u32(bytes, self.euler + 4 * step));
+        if children.chain(tour).any(|id| id as usize >= self.slots) {
+            bail!(ErrorKind::Malformed("taxon ID out of range".into()));
+        }
+        Ok(())
+    }
+
+    /// The root of the taxonomy.
+    pub fn root(&self) -> TaxonId {
+        self.root
+    }
+
+    /// The number of IDs in the cache: the highest taxon ID plus one.
+    pub fn len(&self) -> usize {
+        self.slots
+    }
+
+    /// Whether the cache contains no taxa.
+    pub fn is_empty(&self) -> bool {
+        self.slots == 0
+    }
+
+    /// The parent of a taxon, if it exists.
+    pub fn parent(&self, id: TaxonId) -> Option<TaxonId> {
+        if id >= self.slots {
+            return None;
+        }
+        match read_u32(self.data.as_ref(), HEADER_LENGTH + 4 * id) {
+            MISSING_PARENT => None,
+            parent => Some(parent as TaxonId),
+        }
+    }
+
+    /// Retrieves a taxon by its ID, borrowing its name from the cache.
+    pub fn get(&self, id: TaxonId) -> Option<TaxonRef<'_>> {
+        let parent = self.parent(id)?;
+        let bytes = self.data.as_ref();
+        let rank = *self.rank_values.get(bytes[self.ranks + id] as usize)?;
+        let start = read_u64(bytes, self.offsets + 8 * id) as usize;
+        let end = read_u64(bytes, self.offsets + 8 * (id + 1)) as usize;
+        // The names have been checked when the cache was read
+        let name = str::from_utf8(&bytes[self.names + start..self.names + end]).unwrap_or("");
+        Some(TaxonRef {
+            id,
+            name,
+            rank,
+            parent,
+            valid: bytes[self.valid + id] != 0,
+        })
+    }
+
+    /// All taxa in the cache, ordered by ID.
+    pub fn taxa(&self) -> Vec<Taxon> {
+        (0..self.slots)
+            .filter_map(|id| self.get(id))
+            .map(TaxonRef::to_taxon)
+            .collect()
+    }
+
+    /// The number of children of a taxon.
+    pub fn child_count(&self, id: TaxonId) -> usize {
+        if id >= self.slots {
+            return 0;
+        }
+        let bytes = self.data.as_ref();
+        let start = read_u32(bytes, self.child_offsets + 4 * id);
+        let end = read_u32(bytes, self.child_offsets + 4 * (id + 1));
+        (end - start) as usize
+    }
+
+    /// The n-th child of a taxon, in the order of the taxonomy file.
+    pub fn child(&self, id: TaxonId, n: usize) -> TaxonId {
+        let bytes = self.data.as_ref();
+        let start = read_u32(bytes, self.child_offsets + 4 * id) as usize;
+        read_u32(bytes, self.children + 4 * (start + n)) as TaxonId
+    }
+
+    /// The given step of the Euler tour through the taxon tree, with its depth, as taken by
+    /// [TaxonTree::into_iter()](../../taxon/struct.TaxonTree.html#method.into_iter).
+    pub fn euler_step(&self, step: usize) -> Option<(TaxonId, Depth)> {
+        if step >= self.tour {
+            return None;
+        }
+        let bytes = self.data.as_ref();
+        Some((
+            read_u32(bytes, self.euler + 4 * step) as TaxonId,
+            read_u32(bytes, self.depths + 4 * step) as Depth,
+        ))
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut number = [0; 4];
+    number.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(number)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut number = [0; 8];
+    number.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(number)
+}
+
+error_chain! {
+    links {
+        Taxon(taxon::Error, taxon::ErrorKind) #[doc = "Taxon"];
+    }
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+        Utf8(str::Utf8Error) #[doc = "Indicates a name that isn't valid UTF-8"];
+    }
+    errors {
+        /// A malformed taxonomy cache
+        Malformed(message: String) {
+            description("Malformed taxonomy cache")
+            display("Malformed taxonomy cache: {}", message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::taxon::TaxonList;
+    use std::sync::Arc;
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write(&fixtures::taxon_list(), &mut bytes).unwrap();
+        bytes
+    }
+
+    fn cache() -> TaxonomyCache {
+        TaxonomyCache::new(Data::Owned(bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_layout() {
+        let cache = cache();
+        assert_eq!(0, cache.data.as_ref().len() % 8);
+        assert_eq!(MAGIC, &cache.data.as_ref()[..8]);
+        assert_eq!(185_753, cache.len());
+        assert_eq!(fixtures::ROOT, cache.root());
+    }
+
+    #[test]
+    fn test_taxa() {
+        let cache = cache();
+        assert_eq!(fixtures::taxon_list(), cache.taxa());
+        assert_eq!(Some(12884), cache.parent(185_751));
+        assert_eq!(None, cache.parent(3));
+        assert_eq!(None, cache.get(185_753));
+        assert_eq!(Some("Viroids"), cache.get(12884).map(|t| t.name));
+
+        let by_id = TaxonList::from_cache(Arc::new(cache));
+        let expected = fixtures::by_id();
+        for id in 0..185_753 {
+            assert_eq!(expected.get(id), by_id.get(id));
+        }
+    }
+
+    #[test]
+    fn test_children() {
+        let expected = fixtures::tree();
+        let cache = Arc::new(cache());
+        assert_eq!(
+            vec![2, 10239, 12884],
+            (0..3).map(|n| cache.child(1, n)).collect::<Vec<_>>()
+        );
+        assert_eq!(0, cache.child_count(2));
+        assert_eq!(0, cache.child_count(185_753));
+        let tree = TaxonTree::from_cache(cache);
+        for id in 0..185_753 {
+            assert_eq!(
+                expected.children(id).collect::<Vec<_>>(),
+                tree.children(id).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_euler_tour() {
+        let expected = fixtures::tree().into_iter().collect::<Vec<_>>();
+        let cache = Arc::new(cache());
+        let steps = (0..).map(|step| cache.euler_step(step));
+        assert_eq!(
+            expected,
+            steps
+                .take_while(Option::is_some)
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+        let tree = TaxonTree::from_cache(cache);
+        assert_eq!(expected, tree.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_even_ids() {
+        let taxa = vec![
+            Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+            Taxon::from_static(2, "Bacteria", Rank::Domain, 1, true),
+            Taxon::from_static(3, "Bacillota", Rank::Phylum, 2, true),
+        ];
+        let mut bytes = Vec::new();
+        write(&taxa, &mut bytes).unwrap();
+        let cache = Arc::new(TaxonomyCache::new(Data::Owned(bytes)).unwrap());
+        assert_eq!(4, cache.len());
+        assert_eq!(
+            vec![3],
+            TaxonTree::from_cache(Arc::clone(&cache)).descendants(3)
+        );
+        assert_eq!(
+            TaxonTree::new(&taxa)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            TaxonTree::from_cache(cache).into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_is_cache() {
+        assert!(!is_cache("/dev/null").unwrap());
+        assert!(is_cache("/nonexistent/taxons.tsv").is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let malformed = |bytes: &[u8]| {
+            TaxonomyCache::new(Data::Owned(bytes.to_vec()))
+                .err()
+                .unwrap()
+                .kind()
+                .to_string()
+        };
+        let bytes = bytes();
+        assert_eq!(
+            "Malformed taxonomy cache: truncated",
+            malformed(&bytes[..bytes.len() - 16])
+        );
+        assert_eq!(
+            "Malformed taxonomy cache: not a taxonomy cache",
+            malformed(b"1\troot\tno rank\t1\t\x01")
+        );
+        let mut orphan = bytes.clone();
+        orphan[HEADER_LENGTH + 8..HEADER_LENGTH + 12].copy_from_slice(&185_753_u32.to_le_bytes());
+        assert_eq!(
+            "Malformed taxonomy cache: parent of taxon 2 out of range",
+            malformed(&orphan)
+        );
+        let mut unranked = bytes.clone();
+        unranked[align(HEADER_LENGTH + 4 * 185_753) + 2] = 100;
+        assert_eq!(
+            "Malformed taxonomy cache: unknown rank of taxon 2",
+            malformed(&unranked)
+        );
+    }
+}
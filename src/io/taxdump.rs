@@ -0,0 +1,326 @@
+//! Reads the NCBI taxonomy from the [taxdump](https://ftp.ncbi.nlm.nih.gov/pub/taxonomy/) files.
+//!
+//! The taxa are read from `nodes.dmp` and `names.dmp`, and marked as valid or invalid the way
+//! Unipept does when it preprocesses the taxonomy, so the result is the same as reading the
+//! taxonomy TSV-file distributed by Unipept. The other names of the taxa, such as their synonyms
+//! and common names, are kept as well. The IDs of merged and deleted taxa are read from
+//! `merged.dmp` and `delnodes.dmp`.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::num::ParseIntError;
+use std::path::Path;
+
+use crate::io::compression;
+use crate::rank::Rank;
+use crate::taxon::Taxon;
+use crate::taxon::TaxonId;
+
+/// The IDs of taxa which Unipept considers invalid: "other sequences", "environmental samples" and
+/// "unclassified entries".
+const INVALID_TAXA: &[TaxonId] = &[28384, 48479, 1_869_227];
+
+/// Parts of the names of taxa which Unipept considers invalid.
+const INVALID_NAME_PARTS: &[&str] = &[
+    "enrichment culture",
+    "mixed culture",
+    "uncultured",
+    "unidentified",
+    "unspecified",
+    "undetermined",
+    "sample",
+];
+
+/// Endings of the names of taxa which Unipept considers invalid.
+const INVALID_NAME_ENDINGS: &[&str] = &["metagenome", "library"];
+
+/// The classes of names in `names.dmp` which are kept as synonyms.
+const SYNONYM_CLASSES: &[&str] = &[
+    "synonym",
+    "equivalent name",
+    "common name",
+    "genbank common name",
+    "acronym",
+    "genbank acronym",
+];
+
+/// The contents of a taxonomy dump.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Taxdump {
+    /// The taxa, ordered by ID
+    pub taxa: Vec<Taxon>,
+
+    /// The other names of the taxa, such as synonyms and common names
+    pub synonyms: Vec<(TaxonId, String)>,
+
+    /// The IDs of the taxa that were merged into another taxon, with the ID of that taxon
+    pub merged: Vec<(TaxonId, TaxonId)>,
+
+    /// The IDs of the deleted taxa
+    pub deleted: Vec<TaxonId>,
+}
+
+impl Taxdump {
+    /// Reads the `nodes.dmp`, `names.dmp`, `merged.dmp` and `delnodes.dmp` files (optionally
+    /// compressed) in the given directory, as extracted from `taxdump.tar.gz`. The merged and
+    /// deleted taxa are left empty if their files are missing.
+    pub fn from_dir<P: AsRef<Path>>(directory: P) -> Result<Self> {
+        let directory = directory.as_ref();
+        let open = |name: &str| -> Result<Box<dyn BufRead>> {
+            Ok(Box::new(BufReader::new(compression::open(
+                directory.join(name),
+            )?)))
+        };
+        let optional = |name: &str| -> Result<Box<dyn BufRead>> {
+            if directory.join(name).exists() {
+                open(name)
+            } else {
+                Ok(Box::new(io::empty()))
+            }
+        };
+        Taxdump::read(
+            open("nodes.dmp")?,
+            open("names.dmp")?,
+            optional("merged.dmp")?,
+            optional("delnodes.dmp")?,
+        )
+    }
+
+    /// Reads a taxonomy dump from the contents of its `nodes.dmp`, `names.dmp`, `merged.dmp` and
+    /// `delnodes.dmp` files.
+    pub fn read<N, M, G, D>(nodes: N, names: M, merged: G, deleted: D) -> Result<Self>
+    where
+        N: BufRead,
+        M: BufRead,
+        G: BufRead,
+        D: BufRead,
+    {
+        let mut scientific = HashMap::new();
+        let mut synonyms = Vec::new();
+        for_each_row(names, "names.dmp", |row| {
+            match row.get(3) {
+                Some(&"scientific name") => {
+                    scientific.insert(row[0].parse::<TaxonId>()?, row[1].to_string());
+                }
+                Some(class) if SYNONYM_CLASSES.contains(class) => {
+                    synonyms.push((row[0].parse::<TaxonId>()?, row[1].to_string()));
+                }
+                _ => (),
+            }
+            Ok(())
+        })?;
+
+        let mut taxa = Vec::new();
+        for_each_row(nodes, "nodes.dmp", |row| {
+            if row.len() < 3 {
+                bail!("expected at least 3 fields");
+            }
+            let id = row[0].parse::<TaxonId>()?;
+            let parent = row[1].parse::<TaxonId>()?;
+            // Ranks that aren't known (yet) are treated like taxa without a rank.
+            let rank = row[2].parse::<Rank>().unwrap_or(Rank::NoRank);
+            let name = scientific
+                .remove(&id)
+                .ok_or_else(|| format!("taxon {} has no scientific name", id))?;
+            taxa.push(Taxon::new(id, name, rank, parent, true));
+            Ok(())
+        })?;
+        taxa.sort_by_key(|taxon| taxon.id);
+        invalidate(&mut taxa)?;
+
+        let mut merges = Vec::new();
+        for_each_row(merged, "merged.dmp", |row| {
+            if row.len() < 2 {
+                bail!("expected 2 fields");
+            }
+            merges.push((row[0].parse::<TaxonId>()?, row[1].parse::<TaxonId>()?));
+            Ok(())
+        })?;
+
+        let mut deletions = Vec::new();
+        for_each_row(deleted, "delnodes.dmp", |row| {
+            deletions.push(row[0].parse::<TaxonId>()?);
+            Ok(())
+        })?;
+
+        Ok(Taxdump {
+            taxa,
+            synonyms,
+            merged: merges,
+            deleted: deletions,
+        })
+    }
+}
+
+// Splits each line of a dump file into its fields, which are separated by `\t|\t` and end with
+// `\t|`, and passes them to the given function.
+fn for_each_row<R, F>(reader: R, file: &str, mut f: F) -> Result<()>
+where
+    R: BufRead,
+    F: FnMut(&[&str]) -> Result<()>,
+{
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let line = line.trim_end_matches(['\t', '|']);
+        let row = line.split("\t|\t").collect::<Vec<_>>();
+        f(&row).map_err(|err| ErrorKind::Malformed(file.to_string(), i + 1, err.to_string()))?;
+    }
+    Ok(())
+}
+
+// Whether Unipept considers a taxon valid on its own, regardless of its ancestors.
+fn is_valid(taxon: &Taxon) -> bool {
+    let name = &taxon.name;
+    let invalid_species = taxon.rank == Rank::Species
+        && ((name.chars().any(|c| c.is_ascii_digit()) && !name.contains("virus"))
+            || name.ends_with(" sp.")
+            || name.ends_with(" genomosp.")
+            || name.contains(" bacterium"));
+    !(invalid_species
+        || INVALID_NAME_PARTS.iter().any(|part| name.contains(part))
+        || INVALID_NAME_ENDINGS.iter().any(|end| name.ends_with(end))
+        || INVALID_TAXA.contains(&taxon.id))
+}
+
+// Marks the taxa that are invalid on their own or have an invalid ancestor as invalid. The taxa
+// should be ordered by ID.
+fn invalidate(taxa: &mut [Taxon]) -> Result<()> {
+    let index = |id: TaxonId| taxa.binary_search_by_key(&id, |taxon| taxon.id).ok();
+    let mut valid: Vec<Option<bool>> = vec![None; taxa.len()];
+    for start in 0..taxa.len() {
+        // Walk up until a taxon of which the validity is known, or the root
+        let mut path = Vec::new();
+        let mut current = start;
+        let mut ancestors_valid = true;
+        while valid[current].is_none() {
+            path.push(current);
+            let taxon = &taxa[current];
+            if taxon.parent == taxon.id {
+                break;
+            }
+            if path.len() > taxa.len() {
+                bail!(ErrorKind::Cycle(taxon.id));
+            }
+            current =
+                index(taxon.parent).ok_or(ErrorKind::DanglingParent(taxon.id, taxon.parent))?;
+        }
+        if let Some(known) = valid[current] {
+            ancestors_valid = known;
+        }
+        for &i in path.iter().rev() {
+            ancestors_valid = ancestors_valid && is_valid(&taxa[i]);
+            valid[i] = Some(ancestors_valid);
+        }
+    }
+    for (taxon, valid) in taxa.iter_mut().zip(valid) {
+        taxon.valid = valid.unwrap_or(false);
+    }
+    Ok(())
+}
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+        ParseInt(ParseIntError) #[doc = "Indicates failure to parse a taxon ID"];
+    }
+    errors {
+        /// A malformed line, with its file and line number
+        Malformed(file: String, line: usize, message: String) {
+            description("Malformed taxonomy dump")
+            display("{} line {}: {}", file, line, message)
+        }
+        /// A taxon of which the parent doesn't exist
+        DanglingParent(taxon: TaxonId, parent: TaxonId) {
+            description("Taxon with an unknown parent")
+            display("Taxon {} has an unknown parent {}", taxon, parent)
+        }
+        /// A taxon which is its own ancestor
+        Cycle(taxon: TaxonId) {
+            description("Cycle in the taxonomy")
+            display("Taxon {} is part of a cycle", taxon)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODES: &[u8] = b"1\t|\t1\t|\tno rank\t|\t\t|\t8\t|\n\
+        2\t|\t131567\t|\tsuperkingdom\t|\t\t|\t0\t|\n\
+        561\t|\t2\t|\tgenus\t|\t\t|\t0\t|\n\
+        562\t|\t561\t|\tspecies\t|\tEC\t|\t0\t|\n\
+        1639\t|\t561\t|\tspecies\t|\t\t|\t0\t|\n\
+        48479\t|\t1\t|\tno rank\t|\t\t|\t8\t|\n\
+        77133\t|\t48479\t|\tspecies\t|\t\t|\t0\t|\n\
+        131567\t|\t1\t|\tcellular root\t|\t\t|\t8\t|\n";
+
+    const NAMES: &[u8] = b"1\t|\tall\t|\t\t|\tsynonym\t|\n\
+        1\t|\troot\t|\t\t|\tscientific name\t|\n\
+        2\t|\tBacteria\t|\tBacteria <bacteria>\t|\tscientific name\t|\n\
+        561\t|\tEscherichia\t|\t\t|\tscientific name\t|\n\
+        562\t|\tEscherichia coli\t|\t\t|\tscientific name\t|\n\
+        562\t|\tBacterium coli\t|\t\t|\tsynonym\t|\n\
+        1639\t|\tEscherichia sp. 12\t|\t\t|\tscientific name\t|\n\
+        48479\t|\tenvironmental samples\t|\t\t|\tscientific name\t|\n\
+        77133\t|\tbacterium in the soil\t|\t\t|\tscientific name\t|\n\
+        131567\t|\tcellular organisms\t|\t\t|\tscientific name\t|\n";
+
+    const MERGED: &[u8] = b"12\t|\t74109\t|\n30\t|\t29\t|\n";
+
+    const DELNODES: &[u8] = b"3417211\t|\n3417210\t|\n";
+
+    #[test]
+    fn test_read() {
+        let dump = Taxdump::read(NODES, NAMES, MERGED, DELNODES).unwrap();
+        assert_eq!(
+            vec![
+                Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+                Taxon::from_static(2, "Bacteria", Rank::Superkingdom, 131567, true),
+                Taxon::from_static(561, "Escherichia", Rank::Genus, 2, true),
+                Taxon::from_static(562, "Escherichia coli", Rank::Species, 561, true),
+                Taxon::from_static(1639, "Escherichia sp. 12", Rank::Species, 561, false),
+                Taxon::from_static(48479, "environmental samples", Rank::NoRank, 1, false),
+                Taxon::from_static(77133, "bacterium in the soil", Rank::Species, 48479, false),
+                Taxon::from_static(131567, "cellular organisms", Rank::NoRank, 1, true),
+            ],
+            dump.taxa
+        );
+        assert_eq!(
+            vec![(1, "all".to_string()), (562, "Bacterium coli".to_string())],
+            dump.synonyms
+        );
+        assert_eq!(vec![(12, 74109), (30, 29)], dump.merged);
+        assert_eq!(vec![3_417_211, 3_417_210], dump.deleted);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let nodes = b"1\t|\t1\t|\tno rank\t|\n2\t|\t5\t|\tsuperkingdom\t|\n";
+        let names = b"1\t|\troot\t|\t\t|\tscientific name\t|\n\
+            2\t|\tBacteria\t|\t\t|\tscientific name\t|\n";
+        assert_matches!(
+            *Taxdump::read(&nodes[..], &names[..], &b""[..], &b""[..])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::DanglingParent(2, 5)
+        );
+        assert_matches!(
+            *Taxdump::read(&nodes[..], &names[..12], &b""[..], &b""[..])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Malformed(_, 1, _)
+        );
+        assert_matches!(
+            *Taxdump::read(&b"x\t|\t1\t|\n"[..], &names[..], &b""[..], &b""[..])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Malformed(_, 1, _)
+        );
+    }
+}
@@ -26,9 +26,11 @@ extern crate strum_macros;
 
 pub mod agg;
 pub mod commands;
+pub mod diversity;
 pub mod dna;
 pub mod errors;
 pub mod io;
+pub mod names;
 pub mod rank;
 pub mod rmq;
 pub mod taxon;
@@ -8,10 +8,16 @@ use umgap::errors::Result;
 quick_main!(|| -> Result<()> {
     match Opt::from_args() {
         Opt::BestOf(args) => commands::bestof::bestof(args),
+        Opt::BuildGtdb(args) => commands::buildgtdb::buildgtdb(args),
         Opt::BuildIndex(args) => commands::buildindex::buildindex(args),
+        Opt::BuildTaxCache(args) => commands::buildtaxcache::buildtaxcache(args),
+        Opt::BuildTaxonomy(args) => commands::buildtaxonomy::buildtaxonomy(args),
+        Opt::CheckTaxonomy(args) => commands::checktaxonomy::checktaxonomy(args),
+        Opt::Compare(args) => commands::compare::compare(args),
         Opt::FastqToFasta(args) => commands::fastq2fasta::fastq2fasta(args),
         Opt::Filter(args) => commands::filter::filter(args),
         Opt::JoinKmers(args) => commands::joinkmers::joinkmers(args),
+        Opt::MapTaxa(args) => commands::maptaxa::maptaxa(args),
         Opt::PeptToLca(args) => commands::pept2lca::pept2lca(args),
         Opt::PrintIndex(args) => commands::printindex::printindex(args),
         Opt::ProtToKmer(args) => commands::prot2kmer::prot2kmer(args),
@@ -19,13 +25,19 @@ quick_main!(|| -> Result<()> {
         Opt::ProtToKmerToLca(args) => commands::prot2kmer2lca::prot2kmer2lca(args),
         Opt::ProtToTryp(args) => commands::prot2tryp::prot2tryp(args),
         Opt::ProtToTrypToLca(args) => commands::prot2tryp2lca::prot2tryp2lca(args),
+        Opt::RemapTaxa(args) => commands::remaptaxa::remaptaxa(args),
         Opt::SeedExtend(args) => commands::seedextend::seedextend(args),
         Opt::SnapTaxon(args) => commands::snaptaxon::snaptaxon(args),
         Opt::SplitKmers(args) => commands::splitkmers::splitkmers(args),
+        Opt::SubTaxonomy(args) => commands::subtaxonomy::subtaxonomy(args),
         Opt::TaxaToAgg(args) => commands::taxa2agg::taxa2agg(args),
         Opt::TaxaToFreq(args) => commands::taxa2freq::taxa2freq(args),
+        Opt::TaxaToKrona(args) => commands::taxa2krona::taxa2krona(args),
+        Opt::TaxaToReport(args) => commands::taxa2report::taxa2report(args),
         Opt::TaxaToTree(args) => commands::taxa2tree::taxa2tree(args),
         Opt::Taxonomy(args) => commands::taxonomy::taxonomy(args),
+        Opt::TaxonomyDiff(args) => commands::taxonomydiff::taxonomydiff(args),
+        Opt::TaxonSearch(args) => commands::taxonsearch::taxonsearch(args),
         Opt::Translate(args) => commands::translate::translate(args),
         Opt::Uniq(args) => commands::uniq::uniq(args),
     }
@@ -41,23 +53,35 @@ quick_main!(|| -> Result<()> {
 #[derive(Debug, StructOpt)]
 pub enum Opt {
     #[structopt(name = "bestof")] BestOf(commands::bestof::BestOf),
+    #[structopt(name = "buildgtdb")] BuildGtdb(commands::buildgtdb::BuildGtdb),
     #[structopt(name = "buildindex")] BuildIndex(commands::buildindex::BuildIndex),
+    #[structopt(name = "buildtaxcache")] BuildTaxCache(commands::buildtaxcache::BuildTaxCache),
+    #[structopt(name = "buildtaxonomy")] BuildTaxonomy(commands::buildtaxonomy::BuildTaxonomy),
+    #[structopt(name = "checktaxonomy")] CheckTaxonomy(commands::checktaxonomy::CheckTaxonomy),
+    #[structopt(name = "compare")] Compare(commands::compare::Compare),
     #[structopt(name = "fastq2fasta")] FastqToFasta(commands::fastq2fasta::FastqToFasta),
     #[structopt(name = "filter")] Filter(commands::filter::Filter),
     #[structopt(name = "joinkmers")] JoinKmers(commands::joinkmers::JoinKmers),
+    #[structopt(name = "maptaxa")] MapTaxa(commands::maptaxa::MapTaxa),
     #[structopt(name = "pept2lca")] PeptToLca(commands::pept2lca::PeptToLca),
     #[structopt(name = "printindex")] PrintIndex(commands::printindex::PrintIndex),
     #[cfg(target_family = "unix")] #[structopt(name = "prot2kmer2lca")] ProtToKmerToLca(commands::prot2kmer2lca::ProtToKmerToLca),
     #[structopt(name = "prot2kmer")] ProtToKmer(commands::prot2kmer::ProtToKmer),
     #[structopt(name = "prot2tryp2lca")] ProtToTrypToLca(commands::prot2tryp2lca::ProtToTrypToLca),
     #[structopt(name = "prot2tryp")] ProtToTryp(commands::prot2tryp::ProtToTryp),
+    #[structopt(name = "remaptaxa")] RemapTaxa(commands::remaptaxa::RemapTaxa),
     #[structopt(name = "seedextend")] SeedExtend(commands::seedextend::SeedExtend),
     #[structopt(name = "snaptaxon")] SnapTaxon(commands::snaptaxon::SnapTaxon),
     #[structopt(name = "splitkmers")] SplitKmers(commands::splitkmers::SplitKmers),
+    #[structopt(name = "subtaxonomy")] SubTaxonomy(commands::subtaxonomy::SubTaxonomy),
     #[structopt(name = "taxa2agg")] TaxaToAgg(commands::taxa2agg::TaxaToAgg),
     #[structopt(name = "taxa2freq")] TaxaToFreq(commands::taxa2freq::TaxaToFreq),
+    #[structopt(name = "taxa2krona")] TaxaToKrona(commands::taxa2krona::TaxaToKrona),
+    #[structopt(name = "taxa2report")] TaxaToReport(commands::taxa2report::TaxaToReport),
     #[structopt(name = "taxa2tree")] TaxaToTree(commands::taxa2tree::TaxaToTree),
     #[structopt(name = "taxonomy")] Taxonomy(commands::taxonomy::Taxonomy),
+    #[structopt(name = "taxonomydiff")] TaxonomyDiff(commands::taxonomydiff::TaxonomyDiff),
+    #[structopt(name = "taxonsearch")] TaxonSearch(commands::taxonsearch::TaxonSearch),
     #[structopt(name = "translate")] Translate(commands::translate::Translate),
     #[structopt(name = "uniq")] Uniq(commands::uniq::Uniq),
 }
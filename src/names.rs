@@ -0,0 +1,364 @@
+//! Defines an index on the names of taxa, to look up taxa by name.
+//!
+//! Besides the scientific names of the taxa, the index may contain their synonyms, such as the
+//! synonyms and common names in the NCBI taxonomy dump. These are read from the file with the name
+//! of the taxonomy and `.synonyms` appended, with a taxon ID and a name on each line.
+
+use std::cmp;
+use std::cmp::Ordering;
+use std::io;
+use std::io::BufRead;
+use std::num::ParseIntError;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::io::compression;
+use crate::taxon::TaxonId;
+use crate::taxon::TaxonList;
+
+/// The ways to match names against a query.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matching {
+    Exact,
+    Insensitive,
+    Prefix,
+    Fuzzy,
+}
+
+static MATCHINGS: &[&str] = &["exact", "insensitive", "prefix", "fuzzy"];
+
+impl Matching {
+    /// The names of the ways to match.
+    pub fn variants() -> &'static [&'static str] {
+        MATCHINGS
+    }
+}
+
+impl FromStr for Matching {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "exact" => Ok(Matching::Exact),
+            "insensitive" => Ok(Matching::Insensitive),
+            "prefix" => Ok(Matching::Prefix),
+            "fuzzy" => Ok(Matching::Fuzzy),
+            _ => Err(ErrorKind::ParseMatchingError(s.to_string()).into()),
+        }
+    }
+}
+
+/// A name matching a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    /// The taxon with this name
+    pub taxon: TaxonId,
+    /// The name
+    pub name: String,
+    /// Whether this is the scientific name of the taxon, rather than a synonym
+    pub scientific: bool,
+    /// The number of characters inserted, deleted or substituted in the query (ignoring case) to
+    /// get this name
+    pub distance: usize,
+}
+
+struct Entry {
+    key: String,
+    name: String,
+    taxon: TaxonId,
+    scientific: bool,
+}
+
+impl Entry {
+    fn to_match(&self, distance: usize) -> Match {
+        Match {
+            taxon: self.taxon,
+            name: self.name.clone(),
+            scientific: self.scientific,
+            distance,
+        }
+    }
+}
+
+/// An index on the names of the taxa in a taxonomy.
+pub struct NameIndex {
+    // Ordered on the lowercase names
+    entries: Vec<Entry>,
+}
+
+impl NameIndex {
+    /// Creates an index on the scientific names of the given taxa, and the given synonyms of
+    /// these taxa.
+    pub fn new(taxons: &TaxonList, synonyms: Vec<(TaxonId, String)>) -> Self {
+        let scientific = taxons
+            .iter()
+            .map(|taxon| (taxon.id, taxon.name.to_string(), true));
+        let synonyms = synonyms
+            .into_iter()
+            .filter(|&(id, _)| taxons.get(id).is_some())
+            .map(|(id, name)| (id, name, false));
+        let mut entries = scientific
+            .chain(synonyms)
+            .map(|(taxon, name, scientific)| Entry {
+                key: name.to_lowercase(),
+                name,
+                taxon,
+                scientific,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(a.taxon.cmp(&b.taxon)));
+        NameIndex { entries }
+    }
+
+    /// The path of the synonyms file that belongs to a taxonomy file: the same path, with
+    /// `.synonyms` appended.
+    pub fn synonyms_path<P: AsRef<Path>>(taxon_file: P) -> PathBuf {
+        let mut path = taxon_file.as_ref().as_os_str().to_owned();
+        path.push(".synonyms");
+        PathBuf::from(path)
+    }
+
+    /// Creates an index on the names of the given taxa, and the synonyms in the file that belongs
+    /// to their taxonomy file (see [synonyms_path](#method.synonyms_path)), if there is one.
+    pub fn for_taxonomy<P: AsRef<Path>>(taxon_file: P, taxons: &TaxonList) -> Result<Self> {
+        let path = NameIndex::synonyms_path(taxon_file);
+        let synonyms = if path.exists() {
+            read_synonyms_file(path)?
+        } else {
+            Vec::new()
+        };
+        Ok(NameIndex::new(taxons, synonyms))
+    }
+
+    /// Searches the names matching a query, ordered by distance, with the scientific names first.
+    /// Fuzzy matches are at most the given distance away from the query, while prefix matches
+    /// have the number of extra characters as distance.
+    pub fn search(&self, query: &str, matching: Matching, distance: usize) -> Vec<Match> {
+        let query = query.trim();
+        let key = query.to_lowercase();
+        let length = key.chars().count();
+        let mut matches = match matching {
+            Matching::Exact => self
+                .prefixed(&key)
+                .filter(|entry| entry.name == query)
+                .map(|entry| entry.to_match(0))
+                .collect::<Vec<_>>(),
+            Matching::Insensitive => self
+                .prefixed(&key)
+                .filter(|entry| entry.key == key)
+                .map(|entry| entry.to_match(0))
+                .collect(),
+            Matching::Prefix => self
+                .prefixed(&key)
+                .map(|entry| entry.to_match(entry.key.chars().count() - length))
+                .collect(),
+            Matching::Fuzzy => self
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    levenshtein(&key, &entry.key, distance).map(|d| entry.to_match(d))
+                })
+                .collect(),
+        };
+        matches.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then(b.scientific.cmp(&a.scientific))
+                .then(a.name.cmp(&b.name))
+                .then(a.taxon.cmp(&b.taxon))
+        });
+        matches
+    }
+
+    /// Resolves a taxon given by its ID or its name. The name should match exactly, or else
+    /// ignoring case, the name of a single taxon, in which scientific names take precedence over
+    /// synonyms.
+    pub fn resolve(&self, query: &str) -> Result<TaxonId> {
+        if let Ok(id) = query.trim().parse::<TaxonId>() {
+            return Ok(id);
+        }
+        for &matching in &[Matching::Exact, Matching::Insensitive] {
+            let matches = self.search(query, matching, 0);
+            let scientific = matches.iter().any(|m| m.scientific);
+            let mut taxa = matches
+                .into_iter()
+                .filter(|m| m.scientific || !scientific)
+                .map(|m| m.taxon)
+                .collect::<Vec<_>>();
+            taxa.sort();
+            taxa.dedup();
+            match taxa.len() {
+                0 => continue,
+                1 => return Ok(taxa[0]),
+                _ => bail!(ErrorKind::AmbiguousName(query.to_string(), taxa)),
+            }
+        }
+        bail!(ErrorKind::UnknownName(query.to_string()))
+    }
+
+    // The entries of which the lowercase name starts with the given prefix.
+    fn prefixed<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a Entry> + 'a {
+        // Never equal, so the search ends at the first entry not before the prefix.
+        let start = match self
+            .entries
+            .binary_search_by(|entry| entry.key.as_str().cmp(prefix).then(Ordering::Greater))
+        {
+            Ok(index) | Err(index) => index,
+        };
+        self.entries[start..]
+            .iter()
+            .take_while(move |entry| entry.key.starts_with(prefix))
+    }
+}
+
+/// Resolves taxa given by their ID or name, as
+/// [NameIndex::resolve](struct.NameIndex.html#method.resolve) does. The names are only indexed if
+/// there are taxa given by name.
+pub fn resolve_all<P: AsRef<Path>>(
+    queries: &[String],
+    taxon_file: P,
+    taxons: &TaxonList,
+) -> Result<Vec<TaxonId>> {
+    if queries.iter().all(|q| q.trim().parse::<TaxonId>().is_ok()) {
+        return Ok(queries.iter().map(|q| q.trim().parse().unwrap()).collect());
+    }
+    let index = NameIndex::for_taxonomy(taxon_file, taxons)?;
+    queries.iter().map(|query| index.resolve(query)).collect()
+}
+
+/// Reads a synonyms file, with a taxon ID and one of its names on each line, separated by a tab.
+pub fn read_synonyms_file<P: AsRef<Path>>(filename: P) -> Result<Vec<(TaxonId, String)>> {
+    let file = compression::open(filename)?;
+    let mut synonyms = Vec::new();
+    for (i, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let mut fields = line.splitn(2, '\t');
+        match (fields.next(), fields.next()) {
+            (Some(id), Some(name)) => synonyms.push((id.parse()?, name.to_string())),
+            _ => bail!(ErrorKind::Malformed(i + 1)),
+        }
+    }
+    Ok(synonyms)
+}
+
+// The edit distance between two strings, if it's at most the given maximum.
+fn levenshtein(left: &str, right: &str, max: usize) -> Option<usize> {
+    let left = left.chars().collect::<Vec<_>>();
+    let right = right.chars().collect::<Vec<_>>();
+    if cmp::max(left.len(), right.len()) - cmp::min(left.len(), right.len()) > max {
+        return None;
+    }
+    let mut previous = (0..=right.len()).collect::<Vec<_>>();
+    for (i, l) in left.iter().enumerate() {
+        let mut current = vec![i + 1; right.len() + 1];
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + if l == r { 0 } else { 1 };
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+        if current.iter().all(|&distance| distance > max) {
+            return None;
+        }
+        previous = current;
+    }
+    Some(previous[right.len()]).filter(|&distance| distance <= max)
+}
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "IO"];
+        ParseInt(ParseIntError) #[doc = "Indicates failure to parse a taxon ID"];
+    }
+    errors {
+        /// A name which no taxon has
+        UnknownName(name: String) {
+            description("Unknown taxon name")
+            display("No taxon is named {}", name)
+        }
+        /// A name which several taxa have
+        AmbiguousName(name: String, taxa: Vec<TaxonId>) {
+            description("Ambiguous taxon name")
+            display("Several taxa are named {}, use one of the IDs {:?}", name, taxa)
+        }
+        /// A malformed line in a synonyms file
+        Malformed(line: usize) {
+            description("Malformed synonyms file")
+            display("Malformed synonyms file on line {}", line)
+        }
+        /// Unparseable matching
+        ParseMatchingError(matching: String) {
+            description("Unparseable matching")
+            display("Unparseable matching: {}", matching)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn index() -> NameIndex {
+        let synonyms = vec![
+            (12884, "viroid group".to_string()),
+            (2, "Eubacteria".to_string()),
+            (2, "bacteria".to_string()),
+            (3, "Missing".to_string()),
+        ];
+        NameIndex::new(&fixtures::by_id(), synonyms)
+    }
+
+    fn taxa(matches: Vec<Match>) -> Vec<TaxonId> {
+        matches.into_iter().map(|m| m.taxon).collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let index = index();
+        assert_eq!(vec![2], taxa(index.search("Bacteria", Matching::Exact, 0)));
+        assert!(!index.search("bacteria ", Matching::Exact, 0)[0].scientific);
+        assert!(index.search("BACTERIA", Matching::Exact, 0).is_empty());
+        assert_eq!(
+            vec![2, 2],
+            taxa(index.search("BACTERIA", Matching::Insensitive, 0))
+        );
+        assert_eq!(
+            vec![12884, 10239, 12884],
+            taxa(index.search("vir", Matching::Prefix, 0))
+        );
+        let matches = index.search("Posp1viroida", Matching::Fuzzy, 2);
+        assert_eq!(vec![185_751], taxa(matches.clone()));
+        assert_eq!(2, matches[0].distance);
+        assert!(index.search("Missing", Matching::Fuzzy, 2).is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let index = index();
+        assert_eq!(185_751, index.resolve("185751").unwrap());
+        assert_eq!(2, index.resolve("bacteria").unwrap());
+        assert_eq!(2, index.resolve("eubacteria").unwrap());
+        assert_eq!(12884, index.resolve("Viroid group").unwrap());
+        assert_matches!(
+            *index.resolve("Eukaryota").unwrap_err().kind(),
+            ErrorKind::UnknownName(_)
+        );
+
+        let synonyms = vec![(2, "Vira".to_string()), (10239, "vira".to_string())];
+        let index = NameIndex::new(&fixtures::by_id(), synonyms);
+        assert_eq!(10239, index.resolve("vira").unwrap());
+        assert_matches!(
+            *index.resolve("VIRA").unwrap_err().kind(),
+            ErrorKind::AmbiguousName(_, _)
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(Some(0), levenshtein("abc", "abc", 0));
+        assert_eq!(Some(3), levenshtein("kitten", "sitting", 3));
+        assert_eq!(None, levenshtein("kitten", "sitting", 2));
+        assert_eq!(Some(2), levenshtein("", "ab", 2));
+    }
+}
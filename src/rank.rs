@@ -8,6 +8,8 @@ use strum::IntoEnumIterator;
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Display, EnumString, EnumIter)]
 pub enum Rank {
     #[strum(serialize="no rank")]          NoRank,
+    #[strum(serialize="realm")]            Realm,
+    #[strum(serialize="domain")]           Domain,
     #[strum(serialize="superkingdom")]     Superkingdom,
     #[strum(serialize="kingdom")]          Kingdom,
     #[strum(serialize="subkingdom")]       Subkingdom,
@@ -18,6 +20,8 @@ pub enum Rank {
     #[strum(serialize="class")]            Class,
     #[strum(serialize="subclass")]         Subclass,
     #[strum(serialize="infraclass")]       Infraclass,
+    #[strum(serialize="cohort")]           Cohort,
+    #[strum(serialize="subcohort")]        Subcohort,
     #[strum(serialize="superorder")]       Superorder,
     #[strum(serialize="order")]            Order,
     #[strum(serialize="suborder")]         Suborder,
@@ -30,18 +34,32 @@ pub enum Rank {
     #[strum(serialize="subtribe")]         Subtribe,
     #[strum(serialize="genus")]            Genus,
     #[strum(serialize="subgenus")]         Subgenus,
+    #[strum(serialize="section")]          Section,
+    #[strum(serialize="subsection")]       Subsection,
+    #[strum(serialize="series")]           Series,
     #[strum(serialize="species group")]    SpeciesGroup,
     #[strum(serialize="species subgroup")] SpeciesSubgroup,
     #[strum(serialize="species")]          Species,
     #[strum(serialize="subspecies")]       Subspecies,
     #[strum(serialize="varietas")]         Varietas,
+    #[strum(serialize="subvariety")]       Subvariety,
     #[strum(serialize="forma")]            Forma,
+    #[strum(serialize="serogroup")]        Serogroup,
+    #[strum(serialize="serotype")]         Serotype,
+    #[strum(serialize="biotype")]          Biotype,
+    #[strum(serialize="pathogroup")]       Pathogroup,
+    #[strum(serialize="genotype")]         Genotype,
+    #[strum(serialize="morph")]            Morph,
     #[strum(serialize="strain")]           Strain,
+    #[strum(serialize="isolate")]          Isolate,
+    #[strum(serialize="clade")]            Clade,
 }
 
-pub const RANK_COUNT: usize = 30;
+pub const RANK_COUNT: usize = 46;
 
 static RANKS: &[&str] = &[
+    "realm",
+    "domain",
     "superkingdom",
     "kingdom",
     "subkingdom",
@@ -52,6 +70,8 @@ static RANKS: &[&str] = &[
     "class",
     "subclass",
     "infraclass",
+    "cohort",
+    "subcohort",
     "superorder",
     "order",
     "suborder",
@@ -64,13 +84,24 @@ static RANKS: &[&str] = &[
     "subtribe",
     "genus",
     "subgenus",
+    "section",
+    "subsection",
+    "series",
     "species group",
     "species subgroup",
     "species",
     "subspecies",
     "varietas",
+    "subvariety",
     "forma",
+    "serogroup",
+    "serotype",
+    "biotype",
+    "pathogroup",
+    "genotype",
+    "morph",
     "strain",
+    "isolate",
 ];
 
 impl Rank {
@@ -78,6 +109,14 @@ impl Rank {
         *self as usize
     }
 
+    /// Whether this is a rank in the hierarchy. Taxa without a rank and clades can appear anywhere
+    /// in a lineage, so they are not ordered with respect to the other ranks.
+    pub fn is_ranked(&self) -> bool {
+        *self != Rank::NoRank && *self != Rank::Clade
+    }
+
+    /// The score of this rank when picking an extended seed: 10 for the ranks above species, and
+    /// none for species, the ranks below it and unordered ranks.
     #[rustfmt::skip]
     pub fn score(&self) -> Option<usize> {
         if self < &Rank::Species { Some(10) }
@@ -92,19 +131,34 @@ impl Rank {
         else { None }
     }
 
-    /// Iterator over all the real ranks (NoRank is skipped)
+    /// Iterator over all the real ranks (NoRank and Clade are skipped)
     pub fn ranks() -> impl Iterator<Item = Rank> {
-        Self::iter().skip(1)
+        Self::iter().filter(Rank::is_ranked)
     }
 
     pub fn variants() -> &'static [&'static str] {
         RANKS
     }
+
+    /// The letter of this rank in a Kraken report, for the ranks Kraken reports on.
+    pub fn kraken_code(&self) -> Option<char> {
+        match self {
+            Rank::Domain | Rank::Superkingdom => Some('D'),
+            Rank::Kingdom => Some('K'),
+            Rank::Phylum => Some('P'),
+            Rank::Class => Some('C'),
+            Rank::Order => Some('O'),
+            Rank::Family => Some('F'),
+            Rank::Genus => Some('G'),
+            Rank::Species => Some('S'),
+            _ => None,
+        }
+    }
 }
 
 impl PartialOrd for Rank {
     fn partial_cmp(&self, other: &Rank) -> Option<Ordering> {
-        if self == &Rank::NoRank || other == &Rank::NoRank {
+        if !self.is_ranked() || !other.is_ranked() {
             None
         } else {
             Some(self.index().cmp(&other.index()))
@@ -117,3 +171,53 @@ impl Ord for Rank {
         self.index().cmp(&other.index())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modern_ranks() {
+        assert_eq!(Ok(Rank::Domain), "domain".parse());
+        assert_eq!(Ok(Rank::Realm), "realm".parse());
+        assert_eq!(Ok(Rank::Clade), "clade".parse());
+        assert_eq!(Ok(Rank::Isolate), "isolate".parse());
+        assert_eq!(Ok(Rank::Subcohort), "subcohort".parse());
+        assert_eq!("serogroup", Rank::Serogroup.to_string());
+    }
+
+    #[test]
+    fn test_rank_lists() {
+        assert_eq!(RANK_COUNT, Rank::iter().count());
+        assert_eq!(
+            RANKS.to_vec(),
+            Rank::ranks().map(|r| r.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(Rank::Realm < Rank::Domain);
+        assert!(Rank::Domain < Rank::Superkingdom);
+        assert!(Rank::Infraclass < Rank::Cohort);
+        assert!(Rank::Subcohort < Rank::Superorder);
+        assert!(Rank::Subgenus < Rank::Section);
+        assert!(Rank::Series < Rank::SpeciesGroup);
+        assert!(Rank::Species < Rank::Serotype);
+        assert!(Rank::Strain < Rank::Isolate);
+        assert_eq!(None, Rank::Clade.partial_cmp(&Rank::Species));
+        assert_eq!(None, Rank::NoRank.partial_cmp(&Rank::Clade));
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(Some(10), Rank::Domain.score());
+        assert_eq!(Some(10), Rank::Superkingdom.score());
+        assert_eq!(Some(10), Rank::Cohort.score());
+        assert_eq!(Some(10), Rank::Section.score());
+        assert_eq!(None, Rank::Species.score());
+        assert_eq!(None, Rank::Isolate.score());
+        assert_eq!(None, Rank::Clade.score());
+        assert_eq!(None, Rank::NoRank.score());
+    }
+}
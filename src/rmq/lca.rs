@@ -152,7 +152,7 @@ mod tests {
 
     #[test]
     fn test_with_deeper_interns() {
-        let large_aggregator = LCACalculator::new(TaxonTree::new(&large_taxon_list()));
+        let large_aggregator = LCACalculator::new(TaxonTree::new(&large_taxon_list()).unwrap());
         assert_matches!(large_aggregator.counting_aggregate(&vec![9, 7]),  Ok(3));
         assert_matches!(large_aggregator.counting_aggregate(&vec![9, 10]), Ok(3));
         assert_matches!(large_aggregator.counting_aggregate(&vec![7, 9]),  Ok(3));
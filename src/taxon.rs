@@ -4,14 +4,20 @@ use std;
 use std::cmp::Ordering::{Equal, Greater, Less};
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::fs::File;
+use std::fmt;
 use std::io;
 use std::io::BufRead;
 use std::ops::Index;
 use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
 use strum;
 
+use crate::io::compression;
+use crate::io::taxcache;
+use crate::io::taxcache::TaxonomyCache;
 use crate::rank::*;
 
 /// A unique identifier for a [Taxon](struct.Taxon.html).
@@ -50,9 +56,44 @@ impl Taxon {
     }
 }
 
+/// A taxon borrowed from a [TaxonList](struct.TaxonList.html), so its name isn't copied out of a
+/// [taxonomy cache](../io/taxcache/index.html).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TaxonRef<'a> {
+    /// The taxon's unique id
+    pub id: TaxonId,
+    /// The taxon's name
+    pub name: &'a str,
+    /// The rank of the taxon
+    pub rank: Rank,
+    /// The taxon's parent
+    pub parent: TaxonId,
+    /// Whether the taxon is valid. `false` taxons are discarded in some calculations
+    pub valid: bool,
+}
+
+impl<'a> TaxonRef<'a> {
+    /// Copies the borrowed taxon into a taxon of its own.
+    pub fn to_taxon(self) -> Taxon {
+        Taxon::from_static(self.id, self.name, self.rank, self.parent, self.valid)
+    }
+}
+
+impl<'a> From<&'a Taxon> for TaxonRef<'a> {
+    fn from(taxon: &'a Taxon) -> Self {
+        TaxonRef {
+            id: taxon.id,
+            name: &taxon.name,
+            rank: taxon.rank,
+            parent: taxon.parent,
+            valid: taxon.valid,
+        }
+    }
+}
+
 /// The full lineage of a taxon
 #[derive(Debug)]
-pub struct Lineage([Option<Taxon>; RANK_COUNT]);
+pub struct Lineage(Vec<Option<Taxon>>);
 
 impl Index<Rank> for Lineage {
     type Output = Option<Taxon>;
@@ -62,6 +103,39 @@ impl Index<Rank> for Lineage {
     }
 }
 
+/// The main ranks of a QIIME-style lineage, with their prefixes.
+const QIIME_RANKS: &[(&str, Rank)] = &[
+    ("k__", Rank::Superkingdom),
+    ("p__", Rank::Phylum),
+    ("c__", Rank::Class),
+    ("o__", Rank::Order),
+    ("f__", Rank::Family),
+    ("g__", Rank::Genus),
+    ("s__", Rank::Species),
+];
+
+impl Lineage {
+    /// The names in this lineage on the main ranks in QIIME (Greengenes) style, prefixed with
+    /// `k__`, `p__`, ... up to `s__`, and empty for the missing ranks. The kingdom is the
+    /// superkingdom, the domain if there is no superkingdom, or else the kingdom.
+    pub fn qiime(&self) -> Vec<String> {
+        QIIME_RANKS
+            .iter()
+            .map(|&(prefix, rank)| {
+                let taxon = match (rank, &self[rank]) {
+                    (Rank::Superkingdom, None) => match &self[Rank::Domain] {
+                        None => &self[Rank::Kingdom],
+                        domain => domain,
+                    },
+                    (_, taxon) => taxon,
+                };
+                let name = taxon.as_ref().map(|t| t.name.as_str()).unwrap_or("");
+                format!("{}{}", prefix, name)
+            })
+            .collect()
+    }
+}
+
 impl FromStr for Taxon {
     type Err = Error;
 
@@ -113,11 +187,97 @@ impl FromStr for Taxon {
     }
 }
 
-/// Reads in a file where each line can be parsed as a taxon.
+impl fmt::Display for Taxon {
+    /// Formats the taxon as a line of a taxonomy TSV-file, the inverse of
+    /// [Taxon::from_str()](struct.Taxon.html#method.from_str).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        TaxonRef::from(self).fmt(f)
+    }
+}
+
+impl<'a> fmt::Display for TaxonRef<'a> {
+    /// Formats the taxon like [Taxon](struct.Taxon.html).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            self.id,
+            self.name,
+            self.rank,
+            self.parent,
+            if self.valid { "\x01" } else { "\x00" }
+        )
+    }
+}
+
+/// A difference in a taxon between two releases of a taxonomy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The taxon is new, with its name
+    Added(String),
+    /// The taxon is gone, with its old name
+    Removed(String),
+    /// The name of the taxon changed from the first to the second
+    Renamed(String, String),
+    /// The rank of the taxon changed from the first to the second
+    Reranked(Rank, Rank),
+    /// The parent of the taxon changed from the first to the second
+    Reparented(TaxonId, TaxonId),
+    /// The taxon became valid (true) or invalid (false)
+    Validity(bool),
+}
+
+impl Change {
+    /// The names of the kinds of changes.
+    pub const KINDS: &'static [&'static str] = &[
+        "added",
+        "removed",
+        "renamed",
+        "reranked",
+        "reparented",
+        "validity",
+    ];
+
+    /// The name of the kind of this change.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::Added(_) => "added",
+            Change::Removed(_) => "removed",
+            Change::Renamed(_, _) => "renamed",
+            Change::Reranked(_, _) => "reranked",
+            Change::Reparented(_, _) => "reparented",
+            Change::Validity(_) => "validity",
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    /// Formats the change as its kind, the old and the new value, separated by tabs.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t", self.kind())?;
+        match self {
+            Change::Added(name) => write!(f, "\t{}", name),
+            Change::Removed(name) => write!(f, "{}\t", name),
+            Change::Renamed(old, new) => write!(f, "{}\t{}", old, new),
+            Change::Reranked(old, new) => write!(f, "{}\t{}", old, new),
+            Change::Reparented(old, new) => write!(f, "{}\t{}", old, new),
+            Change::Validity(true) => write!(f, "invalid\tvalid"),
+            Change::Validity(false) => write!(f, "valid\tinvalid"),
+        }
+    }
+}
+
+/// Reads in a file where each line can be parsed as a taxon, or a
+/// [taxonomy cache](../io/taxcache/index.html).
 ///
 /// See [Taxon::from_str()](struct.Taxon.html#method.from_str) for more details on the line format.
 pub fn read_taxa_file<P: AsRef<Path>>(filename: P) -> Result<Vec<Taxon>> {
-    let file = File::open(filename).chain_err(|| "Failed opening taxon file.")?;
+    if taxcache::is_cache(&filename).chain_err(|| "Failed opening taxon file.")? {
+        return TaxonomyCache::open(filename)
+            .map(|cache| cache.taxa())
+            .chain_err(|| "Failed reading taxonomy cache.");
+    }
+    let file = compression::open(filename).chain_err(|| "Failed opening taxon file.")?;
     let reader = io::BufReader::new(file);
     let mut taxa = Vec::new();
     for mline in reader.lines() {
@@ -127,53 +287,331 @@ pub fn read_taxa_file<P: AsRef<Path>>(filename: P) -> Result<Vec<Taxon>> {
     Ok(taxa)
 }
 
-/// A newtype definition for a (pretty dense) list of taxons by ID.
-pub struct TaxonList(pub Vec<Option<Taxon>>);
+/// The taxon IDs of older releases of a taxonomy which have been merged into another taxon or
+/// deleted since.
+///
+/// A remapping is read from a TSV-file with the old and the new ID of a taxon on each line, where
+/// the new ID of a deleted taxon is 0. It keeps track of the IDs it remapped, so they can be
+/// reported afterwards.
+#[derive(Debug, Default)]
+pub struct Remapping {
+    /// The merged taxon IDs, with the ID of the taxon they were merged into
+    pub merged: HashMap<TaxonId, TaxonId>,
+    /// The deleted taxon IDs
+    pub deleted: HashSet<TaxonId>,
+    remapped: Mutex<HashMap<TaxonId, usize>>,
+}
+
+impl Remapping {
+    /// Creates a remapping from the merged and deleted taxon IDs.
+    pub fn new(merged: HashMap<TaxonId, TaxonId>, deleted: HashSet<TaxonId>) -> Self {
+        Remapping {
+            merged,
+            deleted,
+            remapped: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The path of the remapping file that belongs to a taxonomy file: the same path, with
+    /// `.remap` appended.
+    pub fn path_for<P: AsRef<Path>>(taxon_file: P) -> PathBuf {
+        let mut path = taxon_file.as_ref().as_os_str().to_owned();
+        path.push(".remap");
+        PathBuf::from(path)
+    }
+
+    /// Reads the remapping that belongs to a taxonomy file (see
+    /// [path_for](#method.path_for)), or returns an empty remapping if there is none.
+    pub fn for_taxonomy<P: AsRef<Path>>(taxon_file: P) -> Result<Self> {
+        let path = Remapping::path_for(taxon_file);
+        if path.exists() {
+            read_remap_file(path)
+        } else {
+            Ok(Remapping::default())
+        }
+    }
+
+    /// Returns the current ID of a taxon: the ID of the taxon it was merged into, 0 if it was
+    /// deleted, or the ID itself otherwise.
+    pub fn remap(&self, id: TaxonId) -> TaxonId {
+        match self.resolve(id) {
+            Some(new) => {
+                *self.remapped.lock().unwrap().entry(id).or_insert(0) += 1;
+                new
+            }
+            None => id,
+        }
+    }
+
+    // The current ID of a merged or deleted taxon, following the merges of taxa that were merged
+    // before, or None if the taxon wasn't merged or deleted.
+    fn resolve(&self, id: TaxonId) -> Option<TaxonId> {
+        let mut new = id;
+        for _ in 0..=self.merged.len() {
+            if self.deleted.contains(&new) {
+                return Some(0);
+            }
+            match self.merged.get(&new) {
+                Some(&next) if next != new => new = next,
+                _ => break,
+            }
+        }
+        if new == id {
+            None
+        } else {
+            Some(new)
+        }
+    }
+
+    /// The taxon IDs that have been remapped so far, with their number of occurrences, ordered by
+    /// ID.
+    pub fn remapped(&self) -> Vec<(TaxonId, usize)> {
+        let mut remapped = self
+            .remapped
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, &count)| (id, count))
+            .collect::<Vec<_>>();
+        remapped.sort();
+        remapped
+    }
+
+    /// Writes a warning to standard error for each taxon ID that has been remapped so far.
+    pub fn report(&self) {
+        for (id, count) in self.remapped() {
+            match self.resolve(id).unwrap_or(id) {
+                0 => eprintln!("Warning: deleted taxon {} ({} times) became 0", id, count),
+                new => eprintln!(
+                    "Warning: merged taxon {} ({} times) became {}",
+                    id, count, new
+                ),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Remapping {
+    /// Formats the remapping as the contents of a remapping file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut merged = self.merged.iter().collect::<Vec<_>>();
+        merged.sort();
+        for (old, new) in merged {
+            writeln!(f, "{}\t{}", old, new)?;
+        }
+        let mut deleted = self.deleted.iter().collect::<Vec<_>>();
+        deleted.sort();
+        for old in deleted {
+            writeln!(f, "{}\t0", old)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a remapping file, with the old and new ID of a taxon on each line, separated by a tab,
+/// and 0 as new ID for deleted taxa.
+pub fn read_remap_file<P: AsRef<Path>>(filename: P) -> Result<Remapping> {
+    let file = compression::open(filename).chain_err(|| "Failed opening remapping file.")?;
+    let mut merged = HashMap::new();
+    let mut deleted = HashSet::new();
+    for line in io::BufReader::new(file).lines() {
+        let line = line.map_err(|_| "Failed to read all lines.")?;
+        let mut fields = line.trim_end().split('\t');
+        let old = fields.next().unwrap_or("").parse::<TaxonId>()?;
+        let new = fields.next().ok_or("Remapping requires two fields")?;
+        let new = new.parse::<TaxonId>()?;
+        if new == 0 {
+            deleted.insert(old);
+        } else {
+            merged.insert(old, new);
+        }
+    }
+    Ok(Remapping::new(merged, deleted))
+}
+
+/// Reads in a taxonomy file (see [read_taxa_file](fn.read_taxa_file.html)) as a taxon tree and a
+/// list of its taxa. Both read the taxa of a taxonomy cache from the mapped file, instead of
+/// copying them.
+pub fn read_taxonomy<P: AsRef<Path>>(filename: P) -> Result<(TaxonTree, TaxonList)> {
+    if taxcache::is_cache(&filename).chain_err(|| "Failed opening taxon file.")? {
+        let cache = TaxonomyCache::open(filename).chain_err(|| "Failed reading taxonomy cache.")?;
+        let cache = Arc::new(cache);
+        Ok((
+            TaxonTree::from_cache(Arc::clone(&cache)),
+            TaxonList::from_cache(cache),
+        ))
+    } else {
+        let taxa = read_taxa_file(filename)?;
+        Ok((TaxonTree::new(&taxa)?, TaxonList::new(taxa)?))
+    }
+}
+
+/// The number of taxon IDs a [TaxonList](struct.TaxonList.html) may hold for each taxon, as it
+/// reserves room for every ID up to the largest one.
+const MAX_SPARSITY: usize = 16;
+
+/// The number of taxon IDs a [TaxonList](struct.TaxonList.html) may always hold.
+const MIN_ID_SPACE: usize = 1 << 24;
+
+/// Checks the given taxa for the problems that keep them from forming a taxonomy: duplicate IDs,
+/// parents that don't exist, cycles, and a missing root or more than one. All of the problems are
+/// returned, ordered by kind and then by taxon ID.
+pub fn validate(taxa: &[Taxon]) -> Vec<ErrorKind> {
+    let mut problems = Vec::new();
+    let mut parents = HashMap::with_capacity(taxa.len());
+    let mut duplicates = Vec::new();
+    for taxon in taxa {
+        if parents.insert(taxon.id, taxon.parent).is_some() {
+            duplicates.push(taxon.id);
+        }
+    }
+    duplicates.sort();
+    duplicates.dedup();
+    problems.extend(duplicates.into_iter().map(ErrorKind::DuplicateTaxon));
+
+    let mut ids = parents.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let roots = ids
+        .iter()
+        .cloned()
+        .filter(|id| parents[id] == *id)
+        .collect::<Vec<_>>();
+    match roots.len() {
+        0 => problems.push(ErrorKind::NoRoot),
+        1 => {}
+        _ => problems.push(ErrorKind::MultipleRoots(roots)),
+    }
+    for &id in ids.iter() {
+        if !parents.contains_key(&parents[&id]) {
+            problems.push(ErrorKind::DanglingParent(id, parents[&id]));
+        }
+    }
+
+    // Walk up from each taxon, marking the taxa on the current path as unfinished (false). Running
+    // into an unfinished taxon again closes a cycle.
+    let mut finished: HashMap<TaxonId, bool> = HashMap::with_capacity(ids.len());
+    for &start in ids.iter() {
+        let mut path = Vec::new();
+        let mut current = start;
+        loop {
+            match finished.get(&current) {
+                Some(true) => break,
+                Some(false) => {
+                    let cycle = path.iter().cloned().skip_while(|&id| id != current);
+                    problems.push(ErrorKind::Cycle(cycle.collect()));
+                    break;
+                }
+                None => {}
+            }
+            finished.insert(current, false);
+            path.push(current);
+            match parents.get(&current) {
+                Some(&parent) if parent != current && parents.contains_key(&parent) => {
+                    current = parent
+                }
+                _ => break,
+            }
+        }
+        for id in path {
+            finished.insert(id, true);
+        }
+    }
+    problems
+}
+
+/// A (pretty dense) list of taxons by ID, parsed or read from a
+/// [taxonomy cache](../io/taxcache/index.html).
+pub struct TaxonList {
+    taxa: Taxa,
+    unknown: Option<Taxon>,
+}
+
+enum Taxa {
+    Parsed(Vec<Option<Taxon>>),
+    Cached(Arc<TaxonomyCache>),
+}
 
 impl TaxonList {
-    /// Groups a list of taxons by their TaxonId.
-    pub fn new(taxons: Vec<Taxon>) -> Self {
+    /// Groups a list of taxons by their TaxonId. Fails if the IDs are spread too sparsely to
+    /// index them.
+    pub fn new(taxons: Vec<Taxon>) -> Result<Self> {
         // new vec, with at least the length of the current one
         let max_id = taxons.iter().map(|t: &Taxon| t.id).max().unwrap_or(0);
+        if max_id >= MIN_ID_SPACE.max(taxons.len().saturating_mul(MAX_SPARSITY)) {
+            bail!(ErrorKind::SparseTaxa(max_id, taxons.len()));
+        }
         let mut by_id = Vec::with_capacity(max_id + 1);
         by_id.resize(max_id + 1, None);
         for taxon in taxons {
             let id = taxon.id;
             by_id[id] = Some(taxon);
         }
-        TaxonList(by_id)
+        Ok(TaxonList {
+            taxa: Taxa::Parsed(by_id),
+            unknown: None,
+        })
+    }
+
+    /// Lists the taxa in a taxonomy cache, which are read from the cache when they're retrieved.
+    pub fn from_cache(cache: Arc<TaxonomyCache>) -> Self {
+        TaxonList {
+            taxa: Taxa::Cached(cache),
+            unknown: None,
+        }
     }
 
     /// Returns an index of given taxa on their ID. The with_unknown flag allows the insertion of
     /// an unknown taxon on index 0 if that position isn't taken.
-    pub fn new_with_unknown(taxa: Vec<Taxon>, with_unknown: bool) -> Self {
-        let mut new = TaxonList::new(taxa);
-        if with_unknown && new.0[0].is_none() {
-            new.0[0] = Some(Taxon::from_static(0, "unknown", Rank::NoRank, 0, false));
+    pub fn new_with_unknown(taxa: Vec<Taxon>, with_unknown: bool) -> Result<Self> {
+        let mut new = TaxonList::new(taxa)?;
+        if with_unknown && new.get(0).is_none() {
+            new.unknown = Some(Taxon::from_static(0, "unknown", Rank::NoRank, 0, false));
+        }
+        Ok(new)
+    }
+
+    /// The number of IDs in the list: the highest taxon ID plus one.
+    pub fn len(&self) -> usize {
+        match &self.taxa {
+            Taxa::Parsed(taxa) => taxa.len(),
+            Taxa::Cached(cache) => cache.len(),
         }
-        new
+    }
+
+    /// Whether the list has no IDs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The taxa in the list, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = TaxonRef<'_>> {
+        (0..self.len()).filter_map(move |id| self.get(id))
     }
 
     /// Constructs a vector mapping a TaxonId to the id of its parent, if it has one.
     pub fn ancestry(&self) -> Vec<Option<TaxonId>> {
-        self.0
-            .iter()
-            .map(|opttaxon| opttaxon.as_ref().map(|taxon| taxon.parent))
+        (0..self.len())
+            .map(|id| self.get(id).map(|taxon| taxon.parent))
             .collect()
     }
 
     /// Retrieve a taxon from the taxon list by id.
-    pub fn get(&self, index: TaxonId) -> Option<&Taxon> {
-        if index >= self.0.len() {
-            None
-        } else {
-            self.0[index].as_ref()
-        }
+    pub fn get(&self, index: TaxonId) -> Option<TaxonRef<'_>> {
+        let taxon = match &self.taxa {
+            Taxa::Parsed(taxa) => taxa.get(index).and_then(Option::as_ref).map(TaxonRef::from),
+            Taxa::Cached(cache) => cache.get(index),
+        };
+        taxon.or_else(|| {
+            self.unknown
+                .as_ref()
+                .filter(|unknown| unknown.id == index)
+                .map(TaxonRef::from)
+        })
     }
 
     /// Retrieve a taxon from the taxon list by id, returns UnknownTaxon if
     /// the taxon is not present.
-    pub fn get_or_unknown(&self, index: TaxonId) -> Result<&Taxon> {
+    pub fn get_or_unknown(&self, index: TaxonId) -> Result<TaxonRef<'_>> {
         Ok(self.get(index).ok_or(ErrorKind::UnknownTaxon(index))?)
     }
 
@@ -181,7 +619,7 @@ impl TaxonList {
     pub fn score(&self, index: TaxonId) -> Option<usize> {
         let mut current = index;
         while let Some(t) = self.get(current) {
-            if t.parent == current || t.rank != Rank::NoRank {
+            if t.parent == current || t.rank.is_ranked() {
                 return t.rank.score();
             } else {
                 current = t.parent;
@@ -190,17 +628,58 @@ impl TaxonList {
         None
     }
 
+    /// The IDs of the given taxon and all its ancestors, up to the root.
+    pub fn ancestors(&self, index: TaxonId) -> Vec<TaxonId> {
+        let mut ancestors = Vec::new();
+        let mut current = index;
+        while let Some(taxon) = self.get(current) {
+            ancestors.push(current);
+            if taxon.parent == current {
+                break;
+            }
+            current = taxon.parent;
+        }
+        ancestors
+    }
+
+    /// Lists the changes from this to a newer release of the taxonomy, ordered by taxon ID.
+    pub fn diff(&self, new: &TaxonList) -> Vec<(TaxonId, Change)> {
+        let mut changes = Vec::new();
+        for id in 0..self.len().max(new.len()) {
+            match (self.get(id), new.get(id)) {
+                (None, None) => {}
+                (None, Some(taxon)) => changes.push((id, Change::Added(taxon.name.into()))),
+                (Some(taxon), None) => changes.push((id, Change::Removed(taxon.name.into()))),
+                (Some(old), Some(new)) => {
+                    if old.name != new.name {
+                        changes.push((id, Change::Renamed(old.name.into(), new.name.into())));
+                    }
+                    if old.rank != new.rank {
+                        changes.push((id, Change::Reranked(old.rank, new.rank)));
+                    }
+                    if old.parent != new.parent {
+                        changes.push((id, Change::Reparented(old.parent, new.parent)));
+                    }
+                    if old.valid != new.valid {
+                        changes.push((id, Change::Validity(new.valid)));
+                    }
+                }
+            }
+        }
+        changes
+    }
+
     /// Create the full lineage for the given taxon
     pub fn lineage(&self, index: TaxonId) -> Result<Lineage> {
-        let mut lineage_arr: [Option<Taxon>; RANK_COUNT] = Default::default();
+        let mut lineage_arr = vec![None; RANK_COUNT];
         let mut next_id = Some(index);
         let mut prev_id = None;
 
         while next_id != prev_id {
             let taxon = self.get_or_unknown(next_id.unwrap())?;
 
-            if taxon.rank != Rank::NoRank {
-                lineage_arr[taxon.rank.index()] = Some(taxon.clone());
+            if taxon.rank.is_ranked() {
+                lineage_arr[taxon.rank.index()] = Some(taxon.to_taxon());
             }
             prev_id = next_id;
             next_id = Some(taxon.parent);
@@ -214,35 +693,68 @@ impl TaxonList {
 pub struct TaxonTree {
     /// The root taxon
     pub root: TaxonId,
-    /// A map that maps each taxon to its children
-    pub children: HashMap<TaxonId, Vec<TaxonId>>,
+    children: Children,
     max: TaxonId,
 }
 
+// The children of each taxon, in a map or in the arrays of a taxonomy cache.
+enum Children {
+    Parsed(HashMap<TaxonId, Vec<TaxonId>>),
+    Cached(Arc<TaxonomyCache>),
+}
+
 impl TaxonTree {
-    /// Creates a taxon tree from the given taxons.
-    pub fn new(taxons: &[Taxon]) -> TaxonTree {
+    /// Creates a taxon tree from the given taxons. Fails if they have no root or more than one.
+    /// The other problems are only found by [validate](fn.validate.html), as checking for them
+    /// on every load would be too slow.
+    pub fn new(taxons: &[Taxon]) -> Result<TaxonTree> {
         let mut map = HashMap::with_capacity(taxons.len());
-        let mut max = taxons[0].id;
-        let mut roots: HashSet<TaxonId> = taxons.iter().map(|t| t.id).collect();
+        let mut roots = Vec::new();
+        let mut max = 0;
         for taxon in taxons {
             if taxon.id > max {
                 max = taxon.id
             }
             if taxon.id == taxon.parent {
+                roots.push(taxon.id);
                 continue;
             }
             let siblings = map.entry(taxon.parent).or_insert_with(Vec::new);
             siblings.push(taxon.id);
-            roots.remove(&taxon.id);
         }
-        if roots.len() > 1 {
-            panic!("More than one root!");
+        roots.sort();
+        roots.dedup();
+        match roots.len() {
+            0 => bail!(ErrorKind::NoRoot),
+            1 => Ok(TaxonTree {
+                root: roots[0],
+                max,
+                children: Children::Parsed(map),
+            }),
+            _ => bail!(ErrorKind::MultipleRoots(roots)),
         }
+    }
+
+    /// Creates a taxon tree from a taxonomy cache, which provides its root, the children of each
+    /// taxon and its Euler tour.
+    pub fn from_cache(cache: Arc<TaxonomyCache>) -> TaxonTree {
         TaxonTree {
-            root: roots.into_iter().next().expect("There's no root!"),
-            max,
-            children: map,
+            root: cache.root(),
+            max: cache.len().saturating_sub(1),
+            children: Children::Cached(cache),
+        }
+    }
+
+    /// Returns the children of a given taxon in this tree.
+    pub fn children(&self, whose: TaxonId) -> impl Iterator<Item = TaxonId> + '_ {
+        (0..self.child_count(whose)).map(move |n| self.child(whose, n))
+    }
+
+    // The n-th child of a taxon, which must exist.
+    fn child(&self, whose: TaxonId, n: usize) -> TaxonId {
+        match &self.children {
+            Children::Parsed(map) => map[&whose][n],
+            Children::Cached(cache) => cache.child(whose, n),
         }
     }
 
@@ -263,10 +775,8 @@ impl TaxonTree {
             ancestor
         };
         ancestors[current] = ancestor;
-        if let Some(children) = self.children.get(&current) {
-            for child in children {
-                self.with_filtered(&mut ancestors, *child, ancestor, filter);
-            }
+        for child in self.children(current) {
+            self.with_filtered(&mut ancestors, child, ancestor, filter);
         }
     }
 
@@ -282,7 +792,50 @@ impl TaxonTree {
 
     /// Returns the amount of children a given taxon has in this tree.
     pub fn child_count(&self, whose: TaxonId) -> usize {
-        self.children.get(&whose).map(|v| v.len()).unwrap_or(0)
+        match &self.children {
+            Children::Parsed(map) => map.get(&whose).map(|v| v.len()).unwrap_or(0),
+            Children::Cached(cache) => cache.child_count(whose),
+        }
+    }
+
+    /// Returns the IDs of the given taxon and all taxa below it in this tree.
+    pub fn descendants(&self, whose: TaxonId) -> Vec<TaxonId> {
+        let mut descendants = Vec::new();
+        let mut stack = vec![whose];
+        while let Some(current) = stack.pop() {
+            descendants.push(current);
+            let first = stack.len();
+            stack.extend(self.children(current));
+            stack[first..].reverse();
+        }
+        descendants
+    }
+
+    /// Restricts the given taxons to those in this tree passing the filter. Each remaining taxon
+    /// is attached to its closest ancestor that passed the filter as well. The root is always kept,
+    /// so the result is again a consistent taxonomy.
+    ///
+    /// # Arguments:
+    /// * `taxons`: a vector of taxons, indexed by their TaxonId.
+    /// * `filter`: whether to keep a taxon.
+    pub fn prune<F>(&self, taxons: &TaxonList, filter: F) -> Vec<Taxon>
+    where
+        F: Fn(TaxonRef) -> bool,
+    {
+        let kept = self.filter_ancestors(|i: TaxonId| {
+            i == self.root || taxons.get(i).map(&filter).unwrap_or(false)
+        });
+        taxons
+            .iter()
+            .filter(|taxon| kept.get(taxon.id) == Some(&Some(taxon.id)))
+            .map(|taxon| {
+                let mut pruned = taxon.to_taxon();
+                if taxon.id != self.root {
+                    pruned.parent = kept[taxon.parent].unwrap_or(self.root);
+                }
+                pruned
+            })
+            .collect()
     }
 
     /// Converts a list of taxons into their respective taxon id's for this tree. Replaces each
@@ -295,7 +848,7 @@ impl TaxonTree {
         self.filter_ancestors(|i: TaxonId| {
             taxons
                 .get(i)
-                .map(|t| t.valid && (!ranked_only || t.rank != Rank::NoRank))
+                .map(|t| t.valid && (!ranked_only || t.rank.is_ranked()))
                 .unwrap_or(false)
         })
     }
@@ -312,26 +865,18 @@ pub struct EulerIterator {
     current: TaxonId,
     currentn: usize,
     children: usize,
+    step: usize,
 }
 
 impl EulerIterator {
     fn new(tree: TaxonTree) -> EulerIterator {
-        let child_count = tree.child_count(tree.root);
-        let TaxonTree {
-            root,
-            children,
-            max,
-        } = tree;
         EulerIterator {
-            tree: TaxonTree {
-                root,
-                children,
-                max,
-            },
             path: Vec::new(),
-            current: root,
+            current: tree.root,
             currentn: 0,
-            children: child_count,
+            children: tree.child_count(tree.root),
+            step: 0,
+            tree,
         }
     }
 }
@@ -340,6 +885,10 @@ impl Iterator for EulerIterator {
     type Item = (TaxonId, Depth);
 
     fn next(&mut self) -> Option<(TaxonId, Depth)> {
+        if let Children::Cached(cache) = &self.tree.children {
+            self.step += 1;
+            return cache.euler_step(self.step - 1);
+        }
         match self.currentn.cmp(&self.children) {
             Greater => match self.path.pop() {
                 None => None,
@@ -370,10 +919,10 @@ impl Iterator for EulerIterator {
             Less => {
                 let current = self.current;
                 // there must be unvisited children, as currentn < children
-                let children = self.tree.children.get(&current).unwrap();
+                let child = self.tree.child(current, self.currentn);
                 self.path
                     .push((self.current, self.currentn + 1, self.children));
-                self.current = children[self.currentn];
+                self.current = child;
                 self.currentn = 0;
                 self.children = self.tree.child_count(self.current);
                 Some((current, self.path.len() - 1))
@@ -402,6 +951,36 @@ error_chain! {
             description("Encountered an unknown taxon ID")
             display("Unknown Taxon ID: {}", tid)
         }
+        /// The taxonomy has no root, a taxon which is its own parent
+        NoRoot {
+            description("The taxonomy has no root")
+            display("The taxonomy has no root")
+        }
+        /// The taxonomy has more than one root
+        MultipleRoots(roots: Vec<TaxonId>) {
+            description("The taxonomy has more than one root")
+            display("The taxonomy has more than one root: {:?}", roots)
+        }
+        /// A taxon ID occurs more than once
+        DuplicateTaxon(tid: TaxonId) {
+            description("Encountered a duplicate taxon ID")
+            display("Duplicate Taxon ID: {}", tid)
+        }
+        /// The parent of a taxon doesn't exist
+        DanglingParent(tid: TaxonId, parent: TaxonId) {
+            description("Encountered a taxon with an unknown parent")
+            display("Taxon {} has an unknown parent {}", tid, parent)
+        }
+        /// The taxa are their own ancestors
+        Cycle(tids: Vec<TaxonId>) {
+            description("Encountered a cycle of taxa")
+            display("Taxa forming a cycle: {:?}", tids)
+        }
+        /// The taxon IDs are too sparse to index on
+        SparseTaxa(max: TaxonId, count: usize) {
+            description("The taxon IDs are too sparse")
+            display("Taxon ID {} is too large for {} taxa", max, count)
+        }
     }
 }
 
@@ -428,6 +1007,45 @@ mod tests {
         assert_matches!(*"1	root	no rank	7	hello".parse::<Taxon>().unwrap_err().kind(), ErrorKind::Msg(_));
     }
 
+    #[test]
+    fn test_remapping() {
+        let mut merged = HashMap::new();
+        merged.insert(12, 74109);
+        merged.insert(30, 29);
+        merged.insert(29, 28);
+        merged.insert(40, 41);
+        let mut deleted = HashSet::new();
+        deleted.insert(41);
+        deleted.insert(3_417_211);
+        let remapping = Remapping::new(merged, deleted);
+
+        assert_eq!(1, remapping.remap(1));
+        assert_eq!(74109, remapping.remap(12));
+        assert_eq!(74109, remapping.remap(12));
+        assert_eq!(28, remapping.remap(30));
+        assert_eq!(0, remapping.remap(40));
+        assert_eq!(0, remapping.remap(3_417_211));
+        assert_eq!(
+            vec![(12, 2), (30, 1), (40, 1), (3_417_211, 1)],
+            remapping.remapped()
+        );
+        assert_eq!(
+            "12\t74109\n29\t28\n30\t29\n40\t41\n41\t0\n3417211\t0\n",
+            remapping.to_string()
+        );
+    }
+
+    #[test]
+    fn test_taxon_display() {
+        for taxon in fixtures::taxon_list() {
+            assert_eq!(taxon, taxon.to_string().parse().unwrap());
+        }
+        assert_eq!(
+            "1\troot\tno rank\t1\t\x00",
+            Taxon::from_static(1, "root", Rank::NoRank, 1, false).to_string()
+        );
+    }
+
     #[test]
     #[rustfmt::skip]
     fn test_euler_tour() {
@@ -449,8 +1067,8 @@ mod tests {
     fn test_taxon_list() {
         let list = fixtures::taxon_list();
         let by_id = fixtures::by_id();
-        assert_eq!(Some(&list[0]), by_id.get(1));
-        assert_eq!(Some(&list[1]), by_id.get(2));
+        assert_eq!(Some(TaxonRef::from(&list[0])), by_id.get(1));
+        assert_eq!(Some(TaxonRef::from(&list[1])), by_id.get(2));
         assert_eq!(None, by_id.get(3));
 
         let ancestry = by_id.ancestry();
@@ -478,4 +1096,205 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_lineage_qiime() {
+        let by_id = fixtures::by_id();
+        assert_eq!(
+            vec![
+                "k__Viroids",
+                "p__",
+                "c__",
+                "o__",
+                "f__Pospiviroidae",
+                "g__",
+                "s__"
+            ],
+            by_id.lineage(185751).unwrap().qiime()
+        );
+        assert_eq!(
+            vec!["k__", "p__", "c__", "o__", "f__", "g__", "s__"],
+            by_id.lineage(1).unwrap().qiime()
+        );
+    }
+
+    #[test]
+    fn test_lineage_modern_ranks() {
+        let list = vec![
+            Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+            Taxon::from_static(2, "Bacteria", Rank::Domain, 1, true),
+            Taxon::from_static(3, "Terrabacteria group", Rank::Clade, 2, true),
+            Taxon::from_static(4, "Bacillota", Rank::Phylum, 3, true),
+            Taxon::from_static(5, "Bacillus cereus group", Rank::SpeciesGroup, 4, true),
+            Taxon::from_static(6, "Bacillus anthracis", Rank::Species, 5, true),
+            Taxon::from_static(7, "Bacillus anthracis Ames", Rank::Isolate, 6, true),
+        ];
+        let by_id = TaxonList::new(list.clone()).unwrap();
+        let lineage = by_id.lineage(7).unwrap();
+        assert_eq!(lineage[Rank::Domain].as_ref(), Some(&list[1]));
+        assert_eq!(lineage[Rank::Clade], None);
+        assert_eq!(lineage[Rank::Isolate].as_ref(), Some(&list[6]));
+        assert_eq!(
+            vec![
+                "k__Bacteria",
+                "p__Bacillota",
+                "c__",
+                "o__",
+                "f__",
+                "g__",
+                "s__Bacillus anthracis"
+            ],
+            lineage.qiime()
+        );
+        assert_eq!(None, by_id.score(7));
+        assert_eq!(Some(10), by_id.score(3));
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let by_id = fixtures::by_id();
+        assert_eq!(vec![185751, 12884, 1], by_id.ancestors(185751));
+        assert_eq!(vec![1], by_id.ancestors(1));
+        assert_eq!(Vec::<TaxonId>::new(), by_id.ancestors(3));
+    }
+
+    #[test]
+    fn test_descendants() {
+        let tree = fixtures::tree();
+        assert_eq!(vec![12884, 185751, 185752], tree.descendants(12884));
+        assert_eq!(vec![2], tree.descendants(2));
+        assert_eq!(6, tree.descendants(1).len());
+    }
+
+    #[test]
+    fn test_prune() {
+        let list = fixtures::taxon_list();
+        let tree = fixtures::tree();
+        let by_id = fixtures::by_id();
+
+        let families = tree.prune(&by_id, |t| t.rank == Rank::Family);
+        assert_eq!(
+            vec![
+                Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+                Taxon::from_static(185751, "Pospiviroidae", Rank::Family, 1, true),
+                Taxon::from_static(185752, "Avsunviroidae", Rank::Family, 1, true),
+            ],
+            families
+        );
+
+        let viroids = tree.prune(&by_id, |t| t.id != 2 && t.id != 10239);
+        assert_eq!(
+            vec![&list[0], &list[3], &list[4], &list[5]],
+            viroids.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = fixtures::by_id();
+        let new = TaxonList::new(vec![
+            Taxon::from_static(1, "root", Rank::NoRank, 1, true),
+            Taxon::from_static(2, "Bacteria", Rank::Domain, 1, true),
+            Taxon::from_static(10239, "Viruses", Rank::Realm, 1, true),
+            Taxon::from_static(185751, "Pospiviroidae", Rank::Family, 10239, false),
+            Taxon::from_static(185752, "Avsunviroidae", Rank::Family, 10239, true),
+            Taxon::from_static(2_732_396, "Orthornavirae", Rank::Kingdom, 10239, true),
+        ])
+        .unwrap();
+        assert_eq!(
+            vec![
+                (2, Change::Reranked(Rank::Superkingdom, Rank::Domain)),
+                (10239, Change::Reranked(Rank::Superkingdom, Rank::Realm)),
+                (12884, Change::Removed("Viroids".into())),
+                (185751, Change::Reparented(12884, 10239)),
+                (185751, Change::Validity(false)),
+                (185752, Change::Reparented(12884, 10239)),
+                (2_732_396, Change::Added("Orthornavirae".into())),
+            ],
+            old.diff(&new)
+        );
+        assert_eq!(
+            Vec::<(TaxonId, Change)>::new(),
+            old.diff(&fixtures::by_id())
+        );
+    }
+
+    #[test]
+    fn test_change_display() {
+        assert_eq!(
+            "added\t\tViruses",
+            Change::Added("Viruses".into()).to_string()
+        );
+        assert_eq!(
+            "removed\tViroids\t",
+            Change::Removed("Viroids".into()).to_string()
+        );
+        assert_eq!(
+            "reranked\tsuperkingdom\tdomain",
+            Change::Reranked(Rank::Superkingdom, Rank::Domain).to_string()
+        );
+        assert_eq!(
+            "validity\tvalid\tinvalid",
+            Change::Validity(false).to_string()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&fixtures::taxon_list()).is_empty());
+        assert_matches!(validate(&[]).as_slice(), [ErrorKind::NoRoot]);
+
+        let taxon = |id, parent| Taxon::from_static(id, "", Rank::NoRank, parent, true);
+        let taxa = vec![
+            taxon(1, 1),
+            taxon(2, 1),
+            taxon(2, 1),
+            taxon(3, 4),
+            taxon(4, 5),
+            taxon(5, 3),
+            taxon(6, 5),
+            taxon(7, 8),
+            taxon(9, 9),
+        ];
+        let problems = validate(&taxa)
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "Duplicate Taxon ID: 2",
+                "The taxonomy has more than one root: [1, 9]",
+                "Taxon 7 has an unknown parent 8",
+                "Taxa forming a cycle: [3, 4, 5]",
+            ],
+            problems
+        );
+    }
+
+    #[test]
+    fn test_invalid_taxonomies() {
+        let taxon = |id, parent| Taxon::from_static(id, "", Rank::NoRank, parent, true);
+        assert_matches!(
+            TaxonTree::new(&[])
+                .map(|tree| tree.root)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NoRoot
+        );
+        assert_matches!(
+            TaxonTree::new(&[taxon(1, 1), taxon(2, 2)])
+                .map(|tree| tree.root)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::MultipleRoots(_)
+        );
+        assert!(TaxonTree::new(&[taxon(1, 1), taxon(2, 3), taxon(4, 5), taxon(5, 4)]).is_ok());
+        assert_matches!(
+            TaxonList::new(vec![taxon(1, 1), taxon(1 << 40, 1)])
+                .map(|list| list.len())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::SparseTaxa(_, 2)
+        );
+    }
 }
@@ -3,12 +3,18 @@
 pub mod lca;
 pub mod mix;
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::ops::Add;
+
+use serde_json::json;
+use serde_json::Value;
 
 use crate::taxon;
 use crate::taxon::TaxonId;
+use crate::taxon::TaxonList;
 
 /// A recursive tree of TaxonId's and a label.
 pub struct Tree<T: Default + Copy> {
@@ -101,6 +107,92 @@ impl<T: Default + Copy> Tree<T> {
     }
 }
 
+impl<T> Tree<T>
+where
+    T: Default + Copy + PartialOrd + Add<Output = T> + ToString + Into<Value>,
+{
+    /// Converts a tree of counts to nested JSON. Each node is an object with the `id`, `name` and
+    /// `rank` of its taxon, its own count as `self_count`, the total count of its subtree as `count`
+    /// and its `children`, ordered by descending total count.
+    pub fn to_json(&self, taxons: &TaxonList) -> taxon::Result<Value> {
+        self.to_json_with(&self.aggregate(&Add::add), taxons)
+    }
+
+    fn to_json_with(&self, clade: &Tree<T>, taxons: &TaxonList) -> taxon::Result<Value> {
+        let taxon = taxons.get_or_unknown(self.root)?;
+        let children = self
+            .sorted_children(clade)
+            .into_iter()
+            .map(|(direct, clade)| direct.to_json_with(clade, taxons))
+            .collect::<taxon::Result<Vec<_>>>()?;
+        let self_count: Value = self.value.into();
+        let count: Value = clade.value.into();
+        Ok(json!({
+            "id": taxon.id,
+            "name": taxon.name,
+            "rank": taxon.rank.to_string(),
+            "self_count": self_count,
+            "count": count,
+            "children": children,
+        }))
+    }
+
+    /// Converts a tree of counts to the Newick format. Each node is labeled with the name of its
+    /// taxon and the total count of its subtree, separated by a `|`, e.g. `'Homo sapiens|42'`.
+    /// The children are ordered by descending total count.
+    pub fn to_newick(&self, taxons: &TaxonList) -> taxon::Result<String> {
+        let mut newick = String::new();
+        self.to_newick_with(&self.aggregate(&Add::add), taxons, &mut newick)?;
+        newick.push(';');
+        Ok(newick)
+    }
+
+    fn to_newick_with(
+        &self,
+        clade: &Tree<T>,
+        taxons: &TaxonList,
+        newick: &mut String,
+    ) -> taxon::Result<()> {
+        let taxon = taxons.get_or_unknown(self.root)?;
+        if !self.children.is_empty() {
+            newick.push('(');
+            for (i, (direct, clade)) in self.sorted_children(clade).into_iter().enumerate() {
+                if i > 0 {
+                    newick.push(',');
+                }
+                direct.to_newick_with(clade, taxons, newick)?;
+            }
+            newick.push(')');
+        }
+        // Quoted labels may contain any character, with single quotes doubled.
+        let label = format!("{}|{}", taxon.name, clade.value.to_string());
+        newick.push('\'');
+        newick.push_str(&label.replace('\'', "''"));
+        newick.push('\'');
+        Ok(())
+    }
+
+    /// Pairs the children of this tree with those of its aggregate, ordered by descending aggregate
+    /// value and then by taxon ID.
+    pub(crate) fn sorted_children<'a>(
+        &'a self,
+        clade: &'a Tree<T>,
+    ) -> Vec<(&'a Tree<T>, &'a Tree<T>)> {
+        let mut children = self
+            .children
+            .iter()
+            .zip(clade.children.iter())
+            .collect::<Vec<_>>();
+        children.sort_by(|(a, x), (b, y)| {
+            y.value
+                .partial_cmp(&x.value)
+                .unwrap_or(Ordering::Equal)
+                .then(a.root.cmp(&b.root))
+        });
+        children
+    }
+}
+
 impl<T: Default + Copy + ToString> Tree<T> {
     fn _print(&self, depth: usize) {
         let mut string = "".to_string();
@@ -113,3 +205,42 @@ impl<T: Default + Copy + ToString> Tree<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Tree;
+    use crate::fixtures;
+
+    fn tree() -> Tree<f32> {
+        let mut counts = HashMap::new();
+        counts.insert(2, 1.0);
+        counts.insert(185751, 2.0);
+        counts.insert(185752, 3.0);
+        counts.insert(12884, 1.0);
+        Tree::new(fixtures::ROOT, &fixtures::by_id().ancestry(), &counts).unwrap()
+    }
+
+    #[test]
+    fn test_to_newick() {
+        assert_eq!(
+            "(('Avsunviroidae|3','Pospiviroidae|2')'Viroids|6','Bacteria|1')'root|7';",
+            tree().to_newick(&fixtures::by_id()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = tree().to_json(&fixtures::by_id()).unwrap();
+        assert_eq!(json["id"], 1);
+        assert_eq!(json["self_count"], 0.0);
+        assert_eq!(json["count"], 7.0);
+        assert_eq!(json["children"][0]["name"], "Viroids");
+        assert_eq!(json["children"][0]["rank"], "superkingdom");
+        assert_eq!(json["children"][0]["self_count"], 1.0);
+        assert_eq!(json["children"][0]["count"], 6.0);
+        assert_eq!(json["children"][0]["children"][1]["id"], 185751);
+        assert_eq!(json["children"][1]["children"].as_array().unwrap().len(), 0);
+    }
+}
@@ -1,14 +1,23 @@
 //! Some utils.
 
-/// Interleaving iterator.
+/// Interleaving iterator. Stops as soon as any of its parts runs out.
 pub struct Zip<E, I: Iterator<Item = E>> {
     parts: Vec<I>,
+    uneven: bool,
 }
 
 impl<E, I: Iterator<Item = E>> Zip<E, I> {
     /// Constructor for Zip.
     pub fn new(parts: Vec<I>) -> Self {
-        Zip { parts }
+        Zip {
+            parts,
+            uneven: false,
+        }
+    }
+
+    /// Whether some parts still had items left when another one ran out.
+    pub fn uneven(&self) -> bool {
+        self.uneven
     }
 }
 
@@ -16,6 +25,35 @@ impl<E, I: Iterator<Item = E>> Iterator for Zip<E, I> {
     type Item = Vec<E>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.parts.iter_mut().map(|part| part.next()).collect()
+        let items = self
+            .parts
+            .iter_mut()
+            .map(|part| part.next())
+            .collect::<Vec<_>>();
+        let some = items.iter().filter(|item| item.is_some()).count();
+        if some < items.len() {
+            self.uneven |= some > 0;
+            return None;
+        }
+        items.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip() {
+        let mut zip = Zip::new(vec![vec![1, 2].into_iter(), vec![3, 4].into_iter()]);
+        assert_eq!(
+            vec![vec![1, 3], vec![2, 4]],
+            zip.by_ref().collect::<Vec<_>>()
+        );
+        assert!(!zip.uneven());
+
+        let mut zip = Zip::new(vec![vec![1, 2].into_iter(), vec![3].into_iter()]);
+        assert_eq!(vec![vec![1, 3]], zip.by_ref().collect::<Vec<_>>());
+        assert!(zip.uneven());
     }
 }